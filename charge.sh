@@ -15,7 +15,7 @@ for keypair_path in $KEYPAIR_ROOT/*; do
     if [ $(echo "$amount < $target_amount" | bc) -eq 1 ]; then
         increment=$(printf "%.9f" $(echo "$target_amount - $amount" | bc))
         solana transfer $pubkey $increment \
-            --keypair $WALLET_KEYPAIR
+            --keypair $WALLET_KEYPAIR \
             --url $DEFAULT_RPC \
             --with-compute-unit-price $DEFAULT_PRIORITY_FEE \
             --allow-unfunded-recipient \
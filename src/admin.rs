@@ -0,0 +1,133 @@
+use std::str::FromStr;
+
+use ore::{state::Treasury, utils::AccountDeserialize, TREASURY_ADDRESS};
+use solana_sdk::{
+    instruction::Instruction,
+    keccak::{Hash as KeccakHash, HASH_BYTES},
+    pubkey::Pubkey,
+    signature::Signature,
+};
+
+use crate::{
+    errors::Result,
+    factory::{invalid_account, Ore},
+    ledger::TransactionKind,
+    transaction::{SendPolicy, Transaction},
+};
+
+/// A difficulty target, given either as the number of leading zero bits a hash needs, e.g. `10`,
+/// or as the 32 byte target in hex, e.g. `003fffff...`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Difficulty(pub KeccakHash);
+
+impl Difficulty {
+    /// The target whose first `bits` bits are zero and every other bit is one.
+    pub fn from_leading_zeros(bits: u32) -> Self {
+        let mut target = [0xffu8; HASH_BYTES];
+        for (index, byte) in target.iter_mut().enumerate() {
+            let zeros = bits.saturating_sub(index as u32 * 8).min(8);
+            *byte = (0xffu16 >> zeros) as u8;
+        }
+        Difficulty(KeccakHash::new_from_array(target))
+    }
+
+    pub fn leading_zeros(&self) -> u32 {
+        let mut bits = 0;
+        for byte in self.0.to_bytes() {
+            bits += byte.leading_zeros();
+            if byte != 0 {
+                break;
+            }
+        }
+        bits
+    }
+}
+
+impl From<ore::state::Hash> for Difficulty {
+    fn from(value: ore::state::Hash) -> Self {
+        Difficulty(value.into())
+    }
+}
+
+impl std::fmt::Display for Difficulty {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} ({} leading zero bits)",
+            hex::encode(self.0.to_bytes()),
+            self.leading_zeros()
+        )
+    }
+}
+
+impl FromStr for Difficulty {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.len() <= 3 && s.bytes().all(|byte| byte.is_ascii_digit()) {
+            let bits = s.parse::<u32>().map_err(|err| err.to_string())?;
+            if bits > HASH_BYTES as u32 * 8 {
+                return Err(format!("{} leading zero bits is more than a hash has", bits));
+            }
+            return Ok(Difficulty::from_leading_zeros(bits));
+        }
+
+        let digits = s.strip_prefix("0x").unwrap_or(s);
+        let bytes = hex::decode(digits).map_err(|err| format!("invalid hex target: {}", err))?;
+        let target = <[u8; HASH_BYTES]>::try_from(bytes.as_slice()).map_err(|_| {
+            format!("a hex target is {} bytes, got {}", HASH_BYTES, bytes.len())
+        })?;
+        Ok(Difficulty(KeccakHash::new_from_array(target)))
+    }
+}
+
+impl Ore {
+    /// Fetches the treasury, `None` before the program is initialized.
+    pub async fn get_treasury_if_initialized(&self) -> Result<Option<Treasury>> {
+        let accounts = self
+            .get_client(None)
+            .get_multiple_accounts(&[TREASURY_ADDRESS])
+            .await?;
+        let Some(account) = &accounts[0] else {
+            return Ok(None);
+        };
+        let treasury = Treasury::try_from_bytes(&account.data)
+            .map_err(|err| invalid_account(format!("failed to parse treasury account: {}", err)))?;
+        Ok(Some(*treasury))
+    }
+
+    /// Initializes the program with the owner as admin.
+    pub async fn initialize(&self, policy: &SendPolicy) -> Result<Signature> {
+        let instruction = ore::instruction::initialize(self.owner.pubkey());
+        self.send_as_admin(instruction, policy).await
+    }
+
+    pub async fn update_admin(&self, new_admin: Pubkey, policy: &SendPolicy) -> Result<Signature> {
+        let instruction = ore::instruction::update_admin(self.owner.pubkey(), new_admin);
+        self.send_as_admin(instruction, policy).await
+    }
+
+    pub async fn update_difficulty(
+        &self,
+        difficulty: Difficulty,
+        policy: &SendPolicy,
+    ) -> Result<Signature> {
+        let instruction =
+            ore::instruction::update_difficulty(self.owner.pubkey(), difficulty.0.into());
+        self.send_as_admin(instruction, policy).await
+    }
+
+    /// The owner signs admin instructions and pays for them.
+    async fn send_as_admin(
+        &self,
+        instruction: Instruction,
+        policy: &SendPolicy,
+    ) -> Result<Signature> {
+        let mut transaction = Transaction::new(vec![instruction]);
+        transaction.set_kind(TransactionKind::Admin);
+        transaction
+            .send_and_confirm(self.get_client(None), &[], Some(self.fee_payer()), policy, None)
+            .await
+    }
+}
@@ -0,0 +1,202 @@
+use std::{
+    fmt::Display,
+    io::{self, ErrorKind},
+    str::FromStr,
+};
+
+use clap::ValueEnum;
+use core_affinity::CoreId;
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use serde::{Deserialize, Serialize};
+use tokio::runtime::{Builder, Runtime};
+use tracing::warn;
+
+use crate::{
+    errors::{self, CliError, Error},
+    mine::Backend,
+};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BackendKind {
+    #[default]
+    Cpu,
+    Gpu,
+}
+
+/// Placement of the hashing workers and the async RPC runtime on the machine's cores.
+#[derive(Clone, Debug)]
+pub struct WorkerConfig {
+    pub threads: Option<usize>,
+    pub cores: Vec<usize>,
+    pub rpc_core: Option<usize>,
+    /// Miners hashed at full parallelism at the same time, the rest wait in the scheduler queue.
+    /// On the GPU this is the number of jobs in each batched dispatch.
+    pub slots: usize,
+    pub backend: BackendKind,
+}
+
+impl Default for WorkerConfig {
+    fn default() -> Self {
+        WorkerConfig {
+            threads: None,
+            cores: vec![],
+            rpc_core: None,
+            slots: 1,
+            backend: BackendKind::Cpu,
+        }
+    }
+}
+
+impl WorkerConfig {
+    /// Cores the hashers are pinned to, empty to leave them unpinned. Without an explicit list,
+    /// reserving an RPC core pins the hashers to every other core so they never preempt the
+    /// runtime. It is an error for the RPC core to leave no core to hash on.
+    pub fn hasher_cores(&self) -> Result<Vec<usize>, String> {
+        let Some(rpc_core) = self.rpc_core else {
+            return Ok(self.cores.clone());
+        };
+
+        let cores = match self.cores.is_empty() {
+            true => available_cores(),
+            false => self.cores.clone(),
+        };
+        let cores = cores
+            .into_iter()
+            .filter(|core| *core != rpc_core)
+            .collect::<Vec<_>>();
+        if cores.is_empty() {
+            return Err(format!(
+                "no cores left for hashing after reserving rpc core {}",
+                rpc_core
+            ));
+        }
+        Ok(cores)
+    }
+
+    pub fn num_threads(&self) -> Result<usize, String> {
+        if let Some(threads) = self.threads {
+            return Ok(threads.max(1));
+        }
+
+        Ok(match self.hasher_cores()?.len() {
+            0 => available_cores().len().max(1),
+            cores => cores,
+        })
+    }
+
+    pub fn build_pool(&self) -> errors::Result<ThreadPool> {
+        let config_error = |err| Error::CliError(CliError::Config(err));
+        let cores = self.hasher_cores().map_err(config_error)?;
+
+        let pool = ThreadPoolBuilder::new()
+            .num_threads(self.num_threads().map_err(config_error)?)
+            .thread_name(|index| format!("ore-hasher-{}", index))
+            .start_handler(move |index| {
+                if !cores.is_empty() {
+                    pin_current_thread(cores[index % cores.len()]);
+                }
+            })
+            .build()
+            .map_err(|err| io::Error::new(ErrorKind::Other, err))?;
+        Ok(pool)
+    }
+
+    /// Builds the requested backend, falling back to the CPU pool if no GPU can be used.
+    pub fn build_backend(&self) -> errors::Result<Backend> {
+        if self.backend == BackendKind::Gpu {
+            #[cfg(feature = "gpu")]
+            match crate::mine_gpu::GpuSearcher::new() {
+                Ok(gpu) => return Ok(Backend::Gpu(std::sync::Arc::new(gpu))),
+                Err(err) => warn!(error = %err, "GPU unavailable, falling back to CPU"),
+            }
+            #[cfg(not(feature = "gpu"))]
+            warn!("Built without the gpu feature, falling back to CPU");
+        }
+
+        Ok(Backend::Cpu(std::sync::Arc::new(self.build_pool()?)))
+    }
+
+    /// Builds the tokio runtime used for RPC, confined to the reserved core if there is one.
+    pub fn build_runtime(&self) -> Runtime {
+        let mut builder = Builder::new_multi_thread();
+        builder.enable_all().thread_name("ore-rpc");
+
+        if let Some(rpc_core) = self.rpc_core {
+            builder
+                .worker_threads(2)
+                .on_thread_start(move || pin_current_thread(rpc_core));
+        }
+
+        builder.build().expect("Failed to build RPC runtime")
+    }
+}
+
+pub fn available_cores() -> Vec<usize> {
+    core_affinity::get_core_ids()
+        .unwrap_or_default()
+        .into_iter()
+        .map(|core| core.id)
+        .collect()
+}
+
+fn pin_current_thread(core: usize) {
+    if !core_affinity::set_for_current(CoreId { id: core }) {
+        warn!(core, "Failed to pin thread to core");
+    }
+}
+
+/// A list of core ids such as `0-3,6,8`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct CoreList(pub Vec<usize>);
+
+impl FromStr for CoreList {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse = |core: &str| {
+            core.trim()
+                .parse::<usize>()
+                .map_err(|err| format!("invalid core {:?}: {}", core.trim(), err))
+        };
+
+        let mut cores = vec![];
+        for part in s.split(',').map(str::trim).filter(|part| !part.is_empty()) {
+            match part.split_once('-') {
+                Some((start, end)) => {
+                    let (start, end) = (parse(start)?, parse(end)?);
+                    if start > end {
+                        return Err(format!("core range {} is reversed", part));
+                    }
+                    cores.extend(start..=end);
+                }
+                None => cores.push(parse(part)?),
+            }
+        }
+        cores.sort_unstable();
+        cores.dedup();
+        Ok(CoreList(cores))
+    }
+}
+
+impl TryFrom<String> for CoreList {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl Display for CoreList {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let cores = self.0.iter().map(ToString::to_string).collect::<Vec<_>>();
+        write!(f, "{}", cores.join(","))
+    }
+}
+
+impl From<CoreList> for String {
+    fn from(value: CoreList) -> Self {
+        value.to_string()
+    }
+}
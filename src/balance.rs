@@ -0,0 +1,262 @@
+//! Keeps the fee payer from running dry while mining. Its balance is checked on start and every
+//! `CHECK_INTERVAL`: under the warning threshold an alert is raised and, with a funding keypair,
+//! the wallet is topped up; at the floor the pipeline stops.
+
+use std::{
+    collections::HashMap,
+    process::Command,
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use solana_sdk::{
+    native_token::{lamports_to_sol, LAMPORTS_PER_SOL},
+    pubkey::Pubkey,
+    signature::{Keypair, Signature},
+    signer::Signer,
+    system_instruction,
+};
+use tracing::{error, info, warn};
+
+use crate::{
+    errors::Result,
+    events::Event,
+    factory::Ore,
+    fleet::Transfer,
+    ledger::TransactionKind,
+    transaction::{SendPolicy, Transaction},
+};
+
+pub const DEFAULT_FLOOR: u64 = LAMPORTS_PER_SOL / 1000;
+pub const DEFAULT_WARNING: u64 = LAMPORTS_PER_SOL / 100;
+pub const DEFAULT_TOP_UP_TARGET: u64 = LAMPORTS_PER_SOL / 10;
+
+pub const CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+#[derive(Clone, Debug)]
+pub struct BalanceConfig {
+    /// Mining stops once a fee payer has this many lamports or fewer.
+    pub floor: u64,
+    /// Alerts and top-ups start under this many lamports.
+    pub warning: u64,
+    /// Shell command run on every alert, with `ORE_ALERT`, `ORE_WALLET` and
+    /// `ORE_BALANCE_LAMPORTS` set.
+    pub alert_command: Option<String>,
+    /// Pays top-ups, none without it.
+    pub funder: Option<Arc<Keypair>>,
+    /// Balance a wallet under the warning threshold is topped up to.
+    pub top_up_target: u64,
+}
+
+impl Default for BalanceConfig {
+    fn default() -> Self {
+        BalanceConfig {
+            floor: DEFAULT_FLOOR,
+            warning: DEFAULT_WARNING,
+            alert_command: None,
+            funder: None,
+            top_up_target: DEFAULT_TOP_UP_TARGET,
+        }
+    }
+}
+
+impl BalanceConfig {
+    pub fn level(&self, lamports: u64) -> BalanceLevel {
+        if lamports <= self.floor {
+            BalanceLevel::Floor
+        } else if lamports < self.warning {
+            BalanceLevel::Warning
+        } else {
+            BalanceLevel::Healthy
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BalanceLevel {
+    Healthy,
+    Warning,
+    /// At or under the floor.
+    Floor,
+}
+
+impl BalanceLevel {
+    pub fn label(&self) -> &'static str {
+        match self {
+            BalanceLevel::Healthy => "healthy",
+            BalanceLevel::Warning => "warning",
+            BalanceLevel::Floor => "floor",
+        }
+    }
+}
+
+/// Why the alert command runs, its `ORE_ALERT`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Alert {
+    Warning,
+    Floor,
+    ToppedUp,
+    TopUpFailed,
+}
+
+impl Alert {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Alert::Warning => "warning",
+            Alert::Floor => "floor",
+            Alert::ToppedUp => "topped-up",
+            Alert::TopUpFailed => "top-up-failed",
+        }
+    }
+}
+
+/// Watches the balance of the wallets paying fees. Alerts fire when a wallet's level changes,
+/// not on every check.
+#[derive(Debug)]
+pub struct BalanceGuard {
+    pub config: BalanceConfig,
+    pub wallets: Vec<Pubkey>,
+    levels: Mutex<HashMap<Pubkey, BalanceLevel>>,
+}
+
+impl BalanceGuard {
+    pub fn new(config: BalanceConfig, wallets: Vec<Pubkey>) -> Self {
+        BalanceGuard {
+            config,
+            wallets,
+            levels: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Fetches every wallet's balance, topping up the ones under the warning threshold, and
+    /// returns the first wallet left at the floor.
+    pub async fn check(&self, ore: &Ore, policy: &SendPolicy) -> Result<Option<Pubkey>> {
+        let accounts = ore
+            .get_client(None)
+            .get_multiple_accounts(&self.wallets)
+            .await?;
+        let mut depleted = None;
+        for (wallet, account) in self.wallets.iter().zip(accounts) {
+            let mut lamports = account.map_or(0, |account| account.lamports);
+            if self.config.level(lamports) != BalanceLevel::Healthy {
+                lamports += self.top_up(ore, *wallet, lamports, policy).await;
+            }
+            let previous = self.levels.lock().unwrap().get(wallet).copied();
+            let level = self.observe(*wallet, lamports);
+            if let Some(events) = &policy.events {
+                if previous != Some(level) && level != BalanceLevel::Healthy {
+                    events.emit(Event::FeePayerLow {
+                        wallet: wallet.to_string(),
+                        lamports,
+                        level: level.label(),
+                    });
+                }
+            }
+            if level == BalanceLevel::Floor {
+                depleted = depleted.or(Some(*wallet));
+            }
+        }
+        Ok(depleted)
+    }
+
+    /// The first wallet last seen at the floor.
+    pub fn depleted(&self) -> Option<Pubkey> {
+        let levels = self.levels.lock().unwrap();
+        self.wallets
+            .iter()
+            .find(|wallet| levels.get(wallet) == Some(&BalanceLevel::Floor))
+            .copied()
+    }
+
+    /// Records a wallet's balance, logging and alerting if its level changed.
+    pub fn observe(&self, wallet: Pubkey, lamports: u64) -> BalanceLevel {
+        let level = self.config.level(lamports);
+        let previous = self.levels.lock().unwrap().insert(wallet, level);
+        if previous == Some(level) {
+            return level;
+        }
+        let sol = lamports_to_sol(lamports);
+        match level {
+            BalanceLevel::Healthy if previous.is_some() => {
+                info!(%wallet, sol, "Fee payer balance recovered")
+            }
+            BalanceLevel::Healthy => {}
+            BalanceLevel::Warning => {
+                warn!(%wallet, sol, "Fee payer balance is low");
+                self.alert(Alert::Warning, wallet, lamports);
+            }
+            BalanceLevel::Floor => {
+                let floor = lamports_to_sol(self.config.floor);
+                error!(%wallet, sol, floor, "Fee payer balance is at the floor");
+                self.alert(Alert::Floor, wallet, lamports);
+            }
+        }
+        level
+    }
+
+    /// Transfers the funder's SOL up to the target, returning the lamports that landed.
+    async fn top_up(&self, ore: &Ore, wallet: Pubkey, lamports: u64, policy: &SendPolicy) -> u64 {
+        let Some(funder) = &self.config.funder else {
+            return 0;
+        };
+        let amount = self.config.top_up_target.saturating_sub(lamports);
+        if amount == 0 || funder.pubkey() == wallet {
+            return 0;
+        }
+        match send_top_up(ore, funder, wallet, amount, policy).await {
+            Ok(signature) => {
+                info!(%wallet, sol = lamports_to_sol(amount), %signature, "Topped up fee payer");
+                self.alert(Alert::ToppedUp, wallet, lamports + amount);
+                amount
+            }
+            Err(err) => {
+                warn!(%wallet, error = %err.to_string(), "Failed to top up fee payer");
+                self.alert(Alert::TopUpFailed, wallet, lamports);
+                0
+            }
+        }
+    }
+
+    fn alert(&self, alert: Alert, wallet: Pubkey, lamports: u64) {
+        let Some(command) = &self.config.alert_command else {
+            return;
+        };
+        let child = Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .env("ORE_ALERT", alert.label())
+            .env("ORE_WALLET", wallet.to_string())
+            .env("ORE_BALANCE_LAMPORTS", lamports.to_string())
+            .spawn();
+        match child {
+            Ok(mut child) => {
+                thread::spawn(move || child.wait());
+            }
+            Err(err) => warn!(error = %err, "Failed to run the balance alert command"),
+        }
+    }
+}
+
+async fn send_top_up(
+    ore: &Ore,
+    funder: &Keypair,
+    wallet: Pubkey,
+    lamports: u64,
+    policy: &SendPolicy,
+) -> Result<Signature> {
+    let instruction = system_instruction::transfer(&funder.pubkey(), &wallet, lamports);
+    let mut transaction = Transaction::new(vec![instruction]);
+    transaction.set_kind(TransactionKind::Fund);
+    let signature = transaction
+        .send_and_confirm(ore.get_client(None), &[funder], None, policy, None)
+        .await?;
+    if let Some(ledger) = &policy.ledger {
+        let transfer = Transfer {
+            miner: wallet,
+            lamports,
+        };
+        ledger.record_transfer(TransactionKind::Fund, &transfer, signature);
+    }
+    Ok(signature)
+}
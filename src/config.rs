@@ -0,0 +1,545 @@
+//! Layered settings. From highest to lowest precedence a setting comes from:
+//!
+//! 1. its command line flag,
+//! 2. its environment variable,
+//! 3. the selected profile of the config file, `[profiles.<name>]`,
+//! 4. the `[default]` table of the config file,
+//! 5. the `default_rpc_list` of `rpc_list.json`, for `rpc` only,
+//! 6. the Solana CLI config, for `rpc` and `owner` only,
+//! 7. the built-in default.
+//!
+//! Config file keys are the long flag names with underscores:
+//!
+//! ```toml
+//! profile = "mainnet"
+//!
+//! [default]
+//! miners_dir = "~/.config/solana/keypairs"
+//! priority_fee = 10000
+//!
+//! [profiles.mainnet]
+//! rpc = ["https://api.mainnet-beta.solana.com"]
+//! dynamic_fee = true
+//! dynamic_fee_max = 40000000
+//!
+//! [profiles.devnet]
+//! rpc = ["https://api.devnet.solana.com"]
+//! backend = "gpu"
+//! ```
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::{self, ErrorKind},
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+use solana_sdk::native_token::{lamports_to_sol, sol_to_lamports};
+
+use crate::{
+    affinity::{BackendKind, CoreList, WorkerConfig},
+    balance::BalanceConfig,
+    dynamic_fee::{DynamicFee, DEFAULT_PERCENTILE},
+    events::{EventConfig, EventKind, DEFAULT_SUBMISSION_FAILURES},
+    fleet::{DeriveRange, KeySource},
+    jito::DEFAULT_TIP,
+    logging::{LogConfig, LogFormat, LogRotation},
+    pipeline::PipelineConfig,
+    profitability::{
+        GuardAction, PriceFeed, ProfitConfig, DEFAULT_PRICE_REFRESH, DEFAULT_SLOW_DOWN,
+    },
+    transaction::SendPolicy,
+};
+
+/// Every setting that can come from a flag, the environment or the config file. `None` leaves the
+/// setting to the layer below.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    /// RPC urls in order of preference, the next one is used once the current stops answering.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rpc: Option<Vec<String>>,
+    /// Keypair or keystore file of the owner, or a signer daemon endpoint.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub miners: Option<Vec<PathBuf>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub miners_dir: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub miners_seed: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub miners_derive: Option<DeriveRange>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub priority_fee: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retries: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub confirm_wait_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub skip_preflight: Option<bool>,
+    /// Follows the recent priority fees instead of the static `priority_fee`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dynamic_fee: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dynamic_fee_min: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dynamic_fee_max: Option<u64>,
+    /// Percentile of the recent priority fees the dynamic fee starts from.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dynamic_fee_percentile: Option<u8>,
+    /// Micro-lamports per compute unit added to the percentile.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dynamic_fee_uplift: Option<u64>,
+    /// Block engine transactions are sent to as bundles, the RPC pool when unset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jito_url: Option<String>,
+    /// Lamports tipped with every bundle.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jito_tip: Option<u64>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub backend: Option<BackendKind>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub threads: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cores: Option<CoreList>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rpc_core: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub slots: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub drain_timeout_secs: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pending_file: Option<PathBuf>,
+    /// Where `ore mine` serves Prometheus metrics, off when unset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metrics_addr: Option<SocketAddr>,
+    /// Static price of one ORE in SOL for the profitability guard.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ore_price: Option<f64>,
+    /// JSON endpoint of the ORE price in SOL, instead of `ore_price`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub price_url: Option<String>,
+    /// JSON pointer to the price in the `price_url` response, the whole response when empty.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub price_pointer: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub price_refresh_secs: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_margin: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unprofitable: Option<GuardAction>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub slow_down_secs: Option<u64>,
+    /// SOL balance of the fee payer mining stops at.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub balance_floor: Option<f64>,
+    /// SOL balance of the fee payer under which alerts and top-ups start.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub balance_warning: Option<f64>,
+    /// Shell command run on every balance alert.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub balance_alert: Option<String>,
+    /// Keypair or keystore file that tops the fee payer up, no top-ups when unset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_up_keypair: Option<PathBuf>,
+    /// SOL balance the fee payer is topped up to.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_up_target: Option<f64>,
+    /// Urls every event is POSTed to as JSON.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub webhooks: Option<Vec<String>>,
+    /// Shell command run on every event.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub event_script: Option<String>,
+    /// File every event is appended to as a JSON line.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub event_log: Option<PathBuf>,
+    /// Event kinds delivered, every kind when unset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub events: Option<Vec<EventKind>>,
+    /// Least seconds between two events of a kind, e.g. `{ submission-failed = 300 }`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub event_rate_limits: Option<BTreeMap<EventKind, u64>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub submission_failures: Option<usize>,
+    /// SQLite database of work, transactions, proofs, claims and transfers.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ledger_file: Option<PathBuf>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub log_format: Option<LogFormat>,
+    /// Levels in `RUST_LOG` syntax, e.g. `info,ore_cli::transaction=debug`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub log_level: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub log_file: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub log_rotation: Option<LogRotation>,
+}
+
+impl Settings {
+    /// The built-in defaults, the lowest layer.
+    pub fn builtin() -> Self {
+        let defaults = SendPolicy::default();
+        let log = LogConfig::default();
+        let balance = BalanceConfig::default();
+        Settings {
+            retries: Some(defaults.retries),
+            confirm_wait_ms: Some(defaults.confirm_interval.as_millis() as u64),
+            skip_preflight: Some(defaults.skip_preflight),
+            dynamic_fee: Some(false),
+            dynamic_fee_percentile: Some(DEFAULT_PERCENTILE),
+            dynamic_fee_uplift: Some(0),
+            jito_tip: Some(DEFAULT_TIP),
+            backend: Some(BackendKind::default()),
+            slots: Some(WorkerConfig::default().slots),
+            drain_timeout_secs: Some(PipelineConfig::default().drain_timeout.as_secs()),
+            pending_file: home_dir().map(|home| home.join(".config/ore-cli/pending.json")),
+            price_pointer: Some(String::new()),
+            price_refresh_secs: Some(DEFAULT_PRICE_REFRESH.as_secs()),
+            min_margin: Some(0.0),
+            unprofitable: Some(GuardAction::default()),
+            slow_down_secs: Some(DEFAULT_SLOW_DOWN.as_secs()),
+            balance_floor: Some(lamports_to_sol(balance.floor)),
+            balance_warning: Some(lamports_to_sol(balance.warning)),
+            top_up_target: Some(lamports_to_sol(balance.top_up_target)),
+            ledger_file: home_dir().map(|home| home.join(".config/ore-cli/ledger.sqlite3")),
+            submission_failures: Some(DEFAULT_SUBMISSION_FAILURES),
+            log_format: Some(log.format),
+            log_level: Some(log.filter),
+            log_rotation: Some(log.rotation),
+            ..Default::default()
+        }
+    }
+
+    /// The RPC url and keypair of the Solana CLI config, or its defaults when there is none.
+    pub fn solana_cli() -> Self {
+        let config = solana_cli_config::CONFIG_FILE
+            .as_ref()
+            .and_then(|path| solana_cli_config::Config::load(path).ok())
+            .unwrap_or_default();
+        Settings {
+            rpc: Some(vec![config.json_rpc_url]),
+            owner: Some(config.keypair_path),
+            ..Default::default()
+        }
+    }
+
+    /// The `default_rpc_list` of an `rpc_list.json`, the pool of the pm2 scripts, as `rpc`. An
+    /// empty list leaves `rpc` unset.
+    pub fn rpc_list(path: &Path) -> io::Result<Self> {
+        #[derive(Deserialize)]
+        struct RpcList {
+            default_rpc_list: Vec<String>,
+        }
+
+        let contents = fs::read_to_string(path)?;
+        let list = serde_json::from_str::<RpcList>(&contents).map_err(|err| {
+            io::Error::new(ErrorKind::InvalidData, format!("{}: {}", path.display(), err))
+        })?;
+        Ok(Settings {
+            rpc: (!list.default_rpc_list.is_empty()).then_some(list.default_rpc_list),
+            ..Default::default()
+        })
+    }
+
+    /// Overrides every setting `over` has.
+    pub fn merge(self, over: Settings) -> Settings {
+        Settings {
+            rpc: over.rpc.or(self.rpc),
+            owner: over.owner.or(self.owner),
+            miners: over.miners.or(self.miners),
+            miners_dir: over.miners_dir.or(self.miners_dir),
+            miners_seed: over.miners_seed.or(self.miners_seed),
+            miners_derive: over.miners_derive.or(self.miners_derive),
+            priority_fee: over.priority_fee.or(self.priority_fee),
+            retries: over.retries.or(self.retries),
+            confirm_wait_ms: over.confirm_wait_ms.or(self.confirm_wait_ms),
+            skip_preflight: over.skip_preflight.or(self.skip_preflight),
+            dynamic_fee: over.dynamic_fee.or(self.dynamic_fee),
+            dynamic_fee_min: over.dynamic_fee_min.or(self.dynamic_fee_min),
+            dynamic_fee_max: over.dynamic_fee_max.or(self.dynamic_fee_max),
+            dynamic_fee_percentile: over.dynamic_fee_percentile.or(self.dynamic_fee_percentile),
+            dynamic_fee_uplift: over.dynamic_fee_uplift.or(self.dynamic_fee_uplift),
+            jito_url: over.jito_url.or(self.jito_url),
+            jito_tip: over.jito_tip.or(self.jito_tip),
+            backend: over.backend.or(self.backend),
+            threads: over.threads.or(self.threads),
+            cores: over.cores.or(self.cores),
+            rpc_core: over.rpc_core.or(self.rpc_core),
+            slots: over.slots.or(self.slots),
+            drain_timeout_secs: over.drain_timeout_secs.or(self.drain_timeout_secs),
+            pending_file: over.pending_file.or(self.pending_file),
+            metrics_addr: over.metrics_addr.or(self.metrics_addr),
+            ore_price: over.ore_price.or(self.ore_price),
+            price_url: over.price_url.or(self.price_url),
+            price_pointer: over.price_pointer.or(self.price_pointer),
+            price_refresh_secs: over.price_refresh_secs.or(self.price_refresh_secs),
+            min_margin: over.min_margin.or(self.min_margin),
+            unprofitable: over.unprofitable.or(self.unprofitable),
+            slow_down_secs: over.slow_down_secs.or(self.slow_down_secs),
+            balance_floor: over.balance_floor.or(self.balance_floor),
+            balance_warning: over.balance_warning.or(self.balance_warning),
+            balance_alert: over.balance_alert.or(self.balance_alert),
+            top_up_keypair: over.top_up_keypair.or(self.top_up_keypair),
+            top_up_target: over.top_up_target.or(self.top_up_target),
+            webhooks: over.webhooks.or(self.webhooks),
+            event_script: over.event_script.or(self.event_script),
+            event_log: over.event_log.or(self.event_log),
+            events: over.events.or(self.events),
+            event_rate_limits: over.event_rate_limits.or(self.event_rate_limits),
+            submission_failures: over.submission_failures.or(self.submission_failures),
+            ledger_file: over.ledger_file.or(self.ledger_file),
+            log_format: over.log_format.or(self.log_format),
+            log_level: over.log_level.or(self.log_level),
+            log_file: over.log_file.or(self.log_file),
+            log_rotation: over.log_rotation.or(self.log_rotation),
+        }
+    }
+
+    /// Resolves the command line and environment layer `cli` over the config file and the
+    /// defaults.
+    pub fn resolve(
+        cli: Settings,
+        file: Option<&ConfigFile>,
+        profile: Option<&str>,
+    ) -> io::Result<Settings> {
+        let file_settings = match file {
+            Some(file) => file.settings(profile)?,
+            None if profile.is_some() => {
+                return Err(io::Error::new(
+                    ErrorKind::NotFound,
+                    "a profile was selected but there is no config file",
+                ))
+            }
+            None => Settings::default(),
+        };
+        let rpc_list = match rpc_list_path() {
+            Some(path) => Settings::rpc_list(&path)?,
+            None => Settings::default(),
+        };
+        let settings = Settings::builtin()
+            .merge(Settings::solana_cli())
+            .merge(rpc_list)
+            .merge(file_settings)
+            .merge(cli);
+        if settings.rpc.as_ref().is_some_and(|rpc| rpc.is_empty()) {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                "rpc needs at least one url",
+            ));
+        }
+        Ok(settings)
+    }
+
+    /// The policy without a ledger, event bus or block engine, which the caller opens from
+    /// `ledger_path`, `event_config` and `jito_url`.
+    pub fn send_policy(&self) -> SendPolicy {
+        let defaults = SendPolicy::default();
+        SendPolicy {
+            priority_fee: self.priority_fee,
+            retries: self.retries.unwrap_or(defaults.retries),
+            confirm_interval: self
+                .confirm_wait_ms
+                .map_or(defaults.confirm_interval, Duration::from_millis),
+            skip_preflight: self.skip_preflight.unwrap_or(defaults.skip_preflight),
+            dynamic_fee: self.dynamic_fee.unwrap_or(false).then(|| self.dynamic_fee_config()),
+            jito: None,
+            ledger: None,
+            events: None,
+        }
+    }
+
+    pub fn dynamic_fee_config(&self) -> DynamicFee {
+        let defaults = DynamicFee::default();
+        DynamicFee {
+            min_fee: self.dynamic_fee_min,
+            max_fee: self.dynamic_fee_max,
+            uplift_fee: self.dynamic_fee_uplift.unwrap_or(defaults.uplift_fee),
+            baseline_pct: self.dynamic_fee_percentile.unwrap_or(defaults.baseline_pct),
+        }
+    }
+
+    pub fn worker_config(&self) -> WorkerConfig {
+        let defaults = WorkerConfig::default();
+        WorkerConfig {
+            threads: self.threads,
+            cores: self.cores.clone().unwrap_or_default().0,
+            rpc_core: self.rpc_core,
+            slots: self.slots.unwrap_or(defaults.slots),
+            backend: self.backend.unwrap_or(defaults.backend),
+        }
+    }
+
+    pub fn pipeline_config(&self) -> PipelineConfig {
+        let defaults = PipelineConfig::default();
+        PipelineConfig {
+            send: self.send_policy(),
+            drain_timeout: self
+                .drain_timeout_secs
+                .map_or(defaults.drain_timeout, Duration::from_secs),
+            pending_path: self.pending_file.as_deref().map(expand_home),
+            profit: self.profit_config(),
+            balance: Some(self.balance_config()),
+        }
+    }
+
+    /// The balance guard without a funder, which the caller reads from `top_up_keypair`.
+    pub fn balance_config(&self) -> BalanceConfig {
+        let defaults = BalanceConfig::default();
+        BalanceConfig {
+            floor: self.balance_floor.map_or(defaults.floor, sol_to_lamports),
+            warning: self.balance_warning.map_or(defaults.warning, sol_to_lamports),
+            alert_command: self.balance_alert.clone(),
+            funder: None,
+            top_up_target: self
+                .top_up_target
+                .map_or(defaults.top_up_target, sol_to_lamports),
+        }
+    }
+
+    /// The profitability guard, off unless a price is configured. A price url wins over a static
+    /// price.
+    pub fn profit_config(&self) -> Option<ProfitConfig> {
+        let feed = match (&self.price_url, self.ore_price) {
+            (Some(url), _) => PriceFeed::Http {
+                url: url.clone(),
+                pointer: self.price_pointer.clone().unwrap_or_default(),
+            },
+            (None, Some(price)) => PriceFeed::Static(price),
+            (None, None) => return None,
+        };
+        Some(ProfitConfig {
+            feed,
+            min_margin: self.min_margin.unwrap_or(0.0),
+            action: self.unprofitable.unwrap_or_default(),
+            slow_down: self
+                .slow_down_secs
+                .map_or(DEFAULT_SLOW_DOWN, Duration::from_secs),
+            refresh: self
+                .price_refresh_secs
+                .map_or(DEFAULT_PRICE_REFRESH, Duration::from_secs),
+        })
+    }
+
+    pub fn event_config(&self) -> EventConfig {
+        EventConfig {
+            webhooks: self.webhooks.clone().unwrap_or_default(),
+            script: self.event_script.clone(),
+            log_file: self.event_log.as_deref().map(expand_home),
+            kinds: self.events.clone().unwrap_or_default(),
+            rate_limits: self
+                .event_rate_limits
+                .iter()
+                .flatten()
+                .map(|(kind, secs)| (*kind, Duration::from_secs(*secs)))
+                .collect(),
+            submission_failures: self
+                .submission_failures
+                .unwrap_or(DEFAULT_SUBMISSION_FAILURES),
+        }
+    }
+
+    pub fn ledger_path(&self) -> Option<PathBuf> {
+        self.ledger_file.as_deref().map(expand_home)
+    }
+
+    pub fn log_config(&self) -> LogConfig {
+        let defaults = LogConfig::default();
+        LogConfig {
+            format: self.log_format.unwrap_or(defaults.format),
+            filter: self.log_level.clone().unwrap_or(defaults.filter),
+            file: self.log_file.as_deref().map(expand_home),
+            rotation: self.log_rotation.unwrap_or(defaults.rotation),
+            buffer: None,
+        }
+    }
+
+    /// Where the miners are loaded from. `seed_passphrase` is never read from the config file.
+    pub fn key_sources(&self, seed_passphrase: &str) -> Vec<KeySource> {
+        let mut sources = self
+            .miners
+            .iter()
+            .flatten()
+            .map(|path| KeySource::File(expand_home(path)))
+            .collect::<Vec<_>>();
+        if let Some(dir) = &self.miners_dir {
+            sources.push(KeySource::Dir(expand_home(dir)));
+        }
+        if let (Some(seed_file), Some(range)) = (&self.miners_seed, &self.miners_derive) {
+            sources.push(KeySource::Derived {
+                seed_file: expand_home(seed_file),
+                passphrase: seed_passphrase.to_string(),
+                range: range.0.clone(),
+            });
+        }
+        sources
+    }
+}
+
+/// The config file: a `[default]` table and named `[profiles.<name>]` laid over it.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConfigFile {
+    /// Profile used when none is selected with `--profile` or `ORE_PROFILE`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
+    pub default: Settings,
+    pub profiles: BTreeMap<String, Settings>,
+}
+
+impl ConfigFile {
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let contents = fs::read_to_string(path)?;
+        toml::from_str(&contents).map_err(|err| {
+            io::Error::new(ErrorKind::InvalidData, format!("{}: {}", path.display(), err))
+        })
+    }
+
+    /// The `[default]` table with the profile laid over it. `profile` falls back to the file's own
+    /// `profile`.
+    pub fn settings(&self, profile: Option<&str>) -> io::Result<Settings> {
+        let Some(name) = profile.or(self.profile.as_deref()) else {
+            return Ok(self.default.clone());
+        };
+        let profile = self.profiles.get(name).ok_or_else(|| {
+            io::Error::new(ErrorKind::NotFound, format!("no profile named {}", name))
+        })?;
+        Ok(self.default.clone().merge(profile.clone()))
+    }
+}
+
+/// `rpc_list.json` in the working directory, where the pm2 scripts keep it, or beside the
+/// config file.
+pub fn rpc_list_path() -> Option<PathBuf> {
+    let local = PathBuf::from("rpc_list.json");
+    if local.exists() {
+        return Some(local);
+    }
+    home_dir()
+        .map(|home| home.join(".config/ore-cli/rpc_list.json"))
+        .filter(|path| path.exists())
+}
+
+/// `~/.config/ore-cli/config.toml`.
+pub fn default_config_path() -> Option<PathBuf> {
+    home_dir().map(|home| home.join(".config/ore-cli/config.toml"))
+}
+
+/// Expands a leading `~/` so config files can use home relative paths.
+pub fn expand_home(path: &Path) -> PathBuf {
+    match (path.strip_prefix("~"), home_dir()) {
+        (Ok(rest), Some(home)) => home.join(rest),
+        _ => path.to_path_buf(),
+    }
+}
+
+fn home_dir() -> Option<PathBuf> {
+    std::env::var_os("HOME").map(PathBuf::from)
+}
@@ -0,0 +1,62 @@
+use ore::BUS_ADDRESSES;
+use solana_client::client_error::Result;
+
+use crate::rpc::RpcClient;
+
+/// Percentile of the recent priority fees the dynamic fee starts from.
+pub const DEFAULT_PERCENTILE: u8 = 70;
+
+/// A priority fee following what recently landed on the busses: a percentile of the recent
+/// non-zero fees plus an uplift, kept between a minimum and a maximum.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DynamicFee {
+    pub min_fee: Option<u64>,
+    pub max_fee: Option<u64>,
+    pub uplift_fee: u64,
+    pub baseline_pct: u8,
+}
+
+impl Default for DynamicFee {
+    fn default() -> Self {
+        DynamicFee {
+            min_fee: None,
+            max_fee: None,
+            uplift_fee: 0,
+            baseline_pct: DEFAULT_PERCENTILE,
+        }
+    }
+}
+
+impl DynamicFee {
+    pub async fn get(&self, client: &RpcClient) -> Result<u64> {
+        let fees = client
+            .get_recent_prioritization_fees(&BUS_ADDRESSES)
+            .await?
+            .iter()
+            .map(|fee| fee.prioritization_fee)
+            .collect::<Vec<u64>>();
+        Ok(self.fee_for(fees))
+    }
+
+    /// The fee for `recent` prioritization fees. Without any non-zero fee the baseline is zero.
+    pub fn fee_for(&self, mut recent: Vec<u64>) -> u64 {
+        recent.retain(|fee| *fee > 0);
+        recent.sort_unstable();
+
+        let index = self.baseline_pct.min(100) as usize * recent.len() / 100;
+        let baseline = recent
+            .get(index.min(recent.len().saturating_sub(1)))
+            .copied()
+            .unwrap_or(0);
+        let mut final_fee = baseline.saturating_add(self.uplift_fee);
+
+        if let Some(min_fee) = self.min_fee {
+            final_fee = final_fee.max(min_fee);
+        }
+        if let Some(max_fee) = self.max_fee {
+            final_fee = final_fee.min(max_fee);
+        }
+
+        final_fee
+    }
+}
@@ -1,10 +1,11 @@
-use std::fmt::Display;
+use std::{fmt::Display, io};
 
 use ore::error::OreError;
 use solana_client::client_error::ClientError;
-use solana_sdk::{instruction::InstructionError, transaction::TransactionError};
+use solana_sdk::{instruction::InstructionError, pubkey::Pubkey, transaction::TransactionError};
 
 #[derive(Debug)]
+#[allow(clippy::enum_variant_names)]
 pub enum Error {
     ClientError(ClientError),
     OreError(OreError),
@@ -29,6 +30,43 @@ impl From<ClientError> for Error {
     }
 }
 
+impl From<io::Error> for Error {
+    fn from(value: io::Error) -> Self {
+        Error::CliError(CliError::Io(value))
+    }
+}
+
+impl Error {
+    /// The variant without its details, used to label metrics.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Error::ClientError(_) => "ClientError",
+            Error::OreError(ore_error) => match ore_error {
+                OreError::NotStarted => "NotStarted",
+                OreError::NeedsReset => "NeedsReset",
+                OreError::ResetTooEarly => "ResetTooEarly",
+                OreError::HashInvalid => "HashInvalid",
+                OreError::DifficultyNotSatisfied => "DifficultyNotSatisfied",
+                OreError::BusRewardsInsufficient => "BusRewardsInsufficient",
+                OreError::ClaimTooLarge => "ClaimTooLarge",
+            },
+            Error::CliError(cli_error) => match cli_error {
+                CliError::TransactionNotLanded => "TransactionNotLanded",
+                CliError::LockError => "LockError",
+                CliError::WorksEmpty => "WorksEmpty",
+                CliError::PipelineRunning => "PipelineRunning",
+                CliError::TaskFailed => "TaskFailed",
+                CliError::Interrupted => "Interrupted",
+                CliError::Unregistered(_) => "Unregistered",
+                CliError::NothingToClaim(_) => "NothingToClaim",
+                CliError::BalanceBelowFloor(_) => "BalanceBelowFloor",
+                CliError::Config(_) => "Config",
+                CliError::Io(_) => "Io",
+            },
+        }
+    }
+}
+
 impl ToString for Error {
     fn to_string(&self) -> String {
         match self {
@@ -44,11 +82,28 @@ pub enum CliError {
     TransactionNotLanded,
     LockError,
     WorksEmpty,
+    PipelineRunning,
+    TaskFailed,
+    Interrupted,
+    /// A miner still has no proof after registering.
+    Unregistered(Pubkey),
+    /// A miner has no rewards to claim.
+    NothingToClaim(Pubkey),
+    /// A fee payer's balance is at or under the floor.
+    BalanceBelowFloor(Pubkey),
+    /// A setting is missing or invalid.
+    Config(String),
+    /// A local file, keypair or socket could not be used.
+    Io(io::Error),
 }
 
 impl Display for CliError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{:?}", self)
+        match self {
+            CliError::Config(message) => write!(f, "Config: {}", message),
+            CliError::Io(err) => write!(f, "Io: {}", err),
+            _ => write!(f, "{:?}", self),
+        }
     }
 }
 
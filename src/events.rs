@@ -0,0 +1,297 @@
+//! Notifications of what the miners are doing, for finding out a fleet stalled before the rewards
+//! stop growing. Events are filtered and rate limited where they are emitted, then delivered in
+//! the background to every sink: HTTP webhooks get the JSON payload as a POST, scripts get it in
+//! `ORE_EVENT_JSON`, and the event log gets it as a line.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::{File, OpenOptions},
+    io::{self, Write},
+    path::PathBuf,
+    process::Command,
+    str::FromStr,
+    sync::Mutex,
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use chrono::{SecondsFormat, Utc};
+use clap::ValueEnum;
+use flume::Sender;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+pub const DEFAULT_SUBMISSION_FAILURES: usize = 3;
+
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(
+    Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, ValueEnum,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum EventKind {
+    ProofLanded,
+    SubmissionFailed,
+    EpochReset,
+    BusDrained,
+    FeePayerLow,
+    RpcDown,
+    ClaimCompleted,
+}
+
+impl EventKind {
+    pub fn label(&self) -> &'static str {
+        match self {
+            EventKind::ProofLanded => "proof-landed",
+            EventKind::SubmissionFailed => "submission-failed",
+            EventKind::EpochReset => "epoch-reset",
+            EventKind::BusDrained => "bus-drained",
+            EventKind::FeePayerLow => "fee-payer-low",
+            EventKind::RpcDown => "rpc-down",
+            EventKind::ClaimCompleted => "claim-completed",
+        }
+    }
+}
+
+/// `KIND=SECS`, the least seconds between two events of a kind.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RateLimit(pub EventKind, pub u64);
+
+impl FromStr for RateLimit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, secs) = s
+            .split_once('=')
+            .ok_or_else(|| format!("expected KIND=SECS, got {}", s))?;
+        let kind = EventKind::from_str(kind.trim(), true)?;
+        let secs = secs
+            .trim()
+            .parse()
+            .map_err(|err| format!("{}: {}", s, err))?;
+        Ok(RateLimit(kind, secs))
+    }
+}
+
+/// The payload of an event. Keys are base58 strings and amounts raw lamports or ORE base units.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(tag = "event", rename_all = "kebab-case")]
+pub enum Event {
+    ProofLanded {
+        miner: String,
+        signature: String,
+    },
+    /// A miner's submissions failed `failures` times in a row.
+    SubmissionFailed {
+        miner: String,
+        failures: usize,
+        /// The `errors::Error` kind of the last failure.
+        error_kind: &'static str,
+        error: String,
+    },
+    EpochReset {
+        last_reset_at: i64,
+        reward_rate: u64,
+    },
+    /// A bus has less left than one proof's reward.
+    BusDrained {
+        bus: usize,
+        rewards: u64,
+    },
+    /// `level` is `warning` or `floor`.
+    FeePayerLow {
+        wallet: String,
+        lamports: u64,
+        level: &'static str,
+    },
+    RpcDown {
+        endpoint: String,
+        error: String,
+    },
+    ClaimCompleted {
+        miner: String,
+        amount: u64,
+        signature: String,
+    },
+}
+
+impl Event {
+    pub fn kind(&self) -> EventKind {
+        match self {
+            Event::ProofLanded { .. } => EventKind::ProofLanded,
+            Event::SubmissionFailed { .. } => EventKind::SubmissionFailed,
+            Event::EpochReset { .. } => EventKind::EpochReset,
+            Event::BusDrained { .. } => EventKind::BusDrained,
+            Event::FeePayerLow { .. } => EventKind::FeePayerLow,
+            Event::RpcDown { .. } => EventKind::RpcDown,
+            Event::ClaimCompleted { .. } => EventKind::ClaimCompleted,
+        }
+    }
+}
+
+/// What every sink receives.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Notification {
+    /// When the event was emitted, RFC 3339 in UTC.
+    pub time: String,
+    #[serde(flatten)]
+    pub event: Event,
+    /// Events of the same kind dropped by the rate limit since the last one delivered.
+    #[serde(skip_serializing_if = "is_zero")]
+    pub suppressed: u64,
+}
+
+fn is_zero(count: &u64) -> bool {
+    *count == 0
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct EventConfig {
+    /// Urls the JSON payload is POSTed to.
+    pub webhooks: Vec<String>,
+    /// Shell command run with `ORE_EVENT` and `ORE_EVENT_JSON` set.
+    pub script: Option<String>,
+    /// File every payload is appended to as a line.
+    pub log_file: Option<PathBuf>,
+    /// Kinds delivered, every kind when empty.
+    pub kinds: Vec<EventKind>,
+    /// Least time between two events of a kind.
+    pub rate_limits: BTreeMap<EventKind, Duration>,
+    /// Consecutive failed submissions of a miner that make a `submission-failed` event.
+    pub submission_failures: usize,
+}
+
+impl EventConfig {
+    pub fn has_sinks(&self) -> bool {
+        !self.webhooks.is_empty() || self.script.is_some() || self.log_file.is_some()
+    }
+}
+
+/// Filters, rate limits and queues events for a delivery thread. Closing or dropping the bus
+/// delivers what is queued before returning.
+#[derive(Debug)]
+pub struct EventBus {
+    pub config: EventConfig,
+    /// Per kind, when the last event was let through and how many were dropped since.
+    sent: Mutex<HashMap<EventKind, (Instant, u64)>>,
+    sender: Mutex<Option<Sender<Notification>>>,
+    delivery: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl EventBus {
+    /// Opens the event log and starts delivering.
+    pub fn start(config: EventConfig) -> io::Result<Self> {
+        let mut log_file = match &config.log_file {
+            Some(path) => Some(OpenOptions::new().create(true).append(true).open(path)?),
+            None => None,
+        };
+        let (sender, receiver) = flume::unbounded::<Notification>();
+        let webhooks = config.webhooks.clone();
+        let script = config.script.clone();
+        let delivery = thread::spawn(move || {
+            let client = reqwest::blocking::Client::new();
+            for notification in receiver {
+                deliver(&notification, &client, &webhooks, &script, &mut log_file);
+            }
+        });
+        Ok(EventBus {
+            config,
+            sent: Mutex::new(HashMap::new()),
+            sender: Mutex::new(Some(sender)),
+            delivery: Mutex::new(Some(delivery)),
+        })
+    }
+
+    /// Queues the event unless its kind is filtered out or rate limited, or the bus is closed.
+    /// Returns whether it was queued.
+    pub fn emit(&self, event: Event) -> bool {
+        let kind = event.kind();
+        if !self.config.kinds.is_empty() && !self.config.kinds.contains(&kind) {
+            return false;
+        }
+
+        let suppressed = {
+            let mut sent = self.sent.lock().unwrap();
+            let limit = self.config.rate_limits.get(&kind);
+            match (sent.get_mut(&kind), limit) {
+                (Some((last, suppressed)), Some(limit)) if last.elapsed() < *limit => {
+                    *suppressed += 1;
+                    return false;
+                }
+                _ => {}
+            }
+            sent.insert(kind, (Instant::now(), 0))
+                .map_or(0, |(_, suppressed)| suppressed)
+        };
+
+        let notification = Notification {
+            time: Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+            event,
+            suppressed,
+        };
+        match self.sender.lock().unwrap().as_ref() {
+            Some(sender) => sender.send(notification).is_ok(),
+            None => false,
+        }
+    }
+
+    /// Delivers the queued events and stops. Later events are dropped.
+    pub fn close(&self) {
+        self.sender.lock().unwrap().take();
+        if let Some(delivery) = self.delivery.lock().unwrap().take() {
+            let _ = delivery.join();
+        }
+    }
+}
+
+impl Drop for EventBus {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+fn deliver(
+    notification: &Notification,
+    client: &reqwest::blocking::Client,
+    webhooks: &[String],
+    script: &Option<String>,
+    log_file: &mut Option<File>,
+) {
+    let payload = serde_json::to_string(notification).unwrap();
+    let kind = notification.event.kind().label();
+
+    for url in webhooks {
+        let response = client
+            .post(url)
+            .header("Content-Type", "application/json")
+            .body(payload.clone())
+            .timeout(WEBHOOK_TIMEOUT)
+            .send()
+            .and_then(|response| response.error_for_status());
+        if let Err(err) = response {
+            warn!(event = kind, error = %err, "Failed to deliver event to webhook");
+        }
+    }
+
+    if let Some(command) = script {
+        let status = Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .env("ORE_EVENT", kind)
+            .env("ORE_EVENT_JSON", &payload)
+            .status();
+        match status {
+            Ok(status) if !status.success() => {
+                warn!(event = kind, %status, "Event script failed")
+            }
+            Ok(_) => {}
+            Err(err) => warn!(event = kind, error = %err, "Failed to run event script"),
+        }
+    }
+
+    if let Some(file) = log_file {
+        if let Err(err) = writeln!(file, "{}", payload) {
+            warn!(event = kind, error = %err, "Failed to write event log");
+        }
+    }
+}
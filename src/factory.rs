@@ -1,26 +1,33 @@
 use std::{
-    collections::{hash_map::RandomState, HashMap},
-    sync::{mpsc, Arc, Mutex}, time::Instant,
+    io::{self, ErrorKind},
+    time::Instant,
 };
 
 use crate::{
+    affinity::WorkerConfig,
     errors::Result,
-    mine::{MineLine, Miner, Work},
+    mine::Miner,
     rpc::{RpcClient, RpcPool},
+    scheduler::EpochWindow,
 };
-use ore::{state::Treasury, utils::AccountDeserialize, TREASURY_ADDRESS};
-use solana_sdk::{keccak::Hash, pubkey::Pubkey, signature::Keypair};
-use threadpool::ThreadPool;
+use ore::{
+    state::{Bus, Treasury},
+    utils::AccountDeserialize,
+    BUS_ADDRESSES, BUS_COUNT, TREASURY_ADDRESS,
+};
+use solana_sdk::{clock::Clock, signer::Signer, sysvar};
 
 pub struct Ore {
-    pub owner: Keypair,
+    /// Pays every fee. A `Keypair`, or a `RemoteSigner` when the key lives in a signer daemon.
+    pub owner: Box<dyn Signer + Send + Sync>,
     pub rpc_pool: RpcPool,
     pub miners: Vec<Miner>,
+    pub workers: WorkerConfig,
 }
 
 impl Ore {
-    pub fn fee_payer(&self) -> &Keypair {
-        &self.owner
+    pub fn fee_payer(&self) -> &(dyn Signer + Send + Sync) {
+        &*self.owner
     }
 
     pub fn get_client(&self, key: Option<usize>) -> &RpcClient {
@@ -32,31 +39,50 @@ impl Ore {
             .get_client(None)
             .get_account_data(&TREASURY_ADDRESS)
             .await?;
-        Ok(*Treasury::try_from_bytes(&data).expect("Failed to parse treasury account"))
+        let treasury = Treasury::try_from_bytes(&data)
+            .map_err(|err| invalid_account(format!("failed to parse treasury account: {}", err)))?;
+        Ok(*treasury)
     }
 
-    pub async fn mine(self) -> Result<()> {
-        // rayon::ThreadPoolBuilder::new().num_threads(8).build_global().unwrap();
-        let treasury = self.get_treasury().await?;
-        println!("Treasury: {:?}", treasury);
-
-        let difficulty = Hash::new_from_array([
-            0, 0, 0, 0, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255,
-            255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255,
-        ]);
-
-        let mut mineline = MineLine::init(&self.miners, self.get_client(None), Some(self.fee_payer()))
-            .await
-            .unwrap();
-        println!("Miners inited: {:?}", mineline);
-
-        rayon::scope(|scope| {
-            let receiver = mineline.mine_in_scope(scope, difficulty.into());
-            loop {
-                let work = receiver.recv().unwrap();
-            }
-        });
-
-        Ok(())
+    /// Fetches the treasury, every bus and the clock in a single request.
+    pub async fn get_epoch_window(&self) -> Result<EpochWindow> {
+        let mut addresses = vec![TREASURY_ADDRESS, sysvar::clock::ID];
+        addresses.extend_from_slice(&BUS_ADDRESSES);
+        let accounts = self.get_client(None).get_multiple_accounts(&addresses).await?;
+
+        let treasury = accounts[0]
+            .as_ref()
+            .ok_or_else(|| invalid_account("treasury account not found".into()))?;
+        let treasury = Treasury::try_from_bytes(&treasury.data)
+            .map_err(|err| invalid_account(format!("failed to parse treasury account: {}", err)))?;
+        let clock = accounts[1]
+            .as_ref()
+            .ok_or_else(|| invalid_account("clock sysvar not found".into()))?;
+        let clock = bincode::deserialize::<Clock>(&clock.data)
+            .map_err(|err| invalid_account(format!("failed to parse clock sysvar: {}", err)))?;
+
+        let mut bus_rewards = [0u64; BUS_COUNT];
+        for account in accounts[2..].iter().flatten() {
+            let bus = Bus::try_from_bytes(&account.data)
+                .map_err(|err| invalid_account(format!("failed to parse bus account: {}", err)))?;
+            let reward = bus_rewards
+                .get_mut(bus.id as usize)
+                .ok_or_else(|| invalid_account(format!("bus {} is out of range", bus.id)))?;
+            *reward = bus.rewards;
+        }
+
+        Ok(EpochWindow {
+            last_reset_at: treasury.last_reset_at,
+            reward_rate: treasury.reward_rate,
+            bus_rewards,
+            unix_timestamp: clock.unix_timestamp,
+            fetched_at: Instant::now(),
+        })
     }
 }
+
+/// An Ore account that is missing or does not parse, usually because the RPC is on another
+/// cluster or the program is not initialized.
+pub(crate) fn invalid_account(message: String) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, message)
+}
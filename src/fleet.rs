@@ -0,0 +1,571 @@
+use std::{
+    fs,
+    io::{self, ErrorKind},
+    ops::Range,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use bip39::{Language, Mnemonic, Seed};
+use futures::{stream, StreamExt};
+use ore::{state::Proof, utils::AccountDeserialize};
+use serde::{Deserialize, Serialize};
+use solana_program::program_pack::Pack;
+use solana_sdk::{
+    derivation_path::DerivationPath,
+    instruction::Instruction,
+    pubkey::Pubkey,
+    signature::{write_keypair_file, Keypair, Signature},
+    signer::{keypair::keypair_from_seed_and_derivation_path, Signer},
+    system_instruction,
+};
+use tracing::{info, warn};
+use zeroize::Zeroizing;
+
+use crate::{
+    errors::{CliError, Error, Result},
+    events::Event,
+    factory::Ore,
+    keystore::Unlocker,
+    ledger::TransactionKind,
+    mine::{proof_pubkey, Miner},
+    transaction::{SendPolicy, Transaction},
+};
+
+/// Register instructions packed into one transaction, each adds a miner signature.
+pub const REGISTER_BATCH: usize = 4;
+
+/// Transfers from the owner packed into one transaction, each only adds the recipient.
+pub const FUND_BATCH: usize = 16;
+
+/// Transfers from miners packed into one transaction, each adds a miner signature.
+pub const SWEEP_BATCH: usize = 8;
+
+/// Compute units of a claim into an existing token account.
+pub const CU_LIMIT_CLAIM: u32 = 11_000;
+
+/// Compute units of each system transfer in a batch, twice what the system program charges.
+pub const CU_PER_TRANSFER: u32 = 300;
+
+/// Compute units of each registration in a batch, which creates the proof account.
+pub const CU_PER_REGISTER: u32 = 20_000;
+
+/// Compute units of the compute budget instructions in front of a batch.
+const CU_BUDGET_INSTRUCTIONS: u32 = 300;
+
+/// Accounts fetched per `getMultipleAccounts` request, the RPC limit.
+const ACCOUNTS_PER_REQUEST: usize = 100;
+
+/// Transactions in flight at the same time.
+const CONCURRENT_SENDS: usize = 8;
+
+/// Generates `count` keypairs in `dir`, each saved as `<pubkey>.json` like create-account.sh.
+pub fn new_keypairs(dir: &Path, count: usize) -> io::Result<Vec<Pubkey>> {
+    fs::create_dir_all(dir)?;
+    (0..count)
+        .map(|_| {
+            let keypair = Keypair::new();
+            let path = dir.join(format!("{}.json", keypair.pubkey()));
+            write_keypair_file(&keypair, &path)
+                .map_err(|err| io::Error::new(ErrorKind::Other, err.to_string()))?;
+            Ok(keypair.pubkey())
+        })
+        .collect()
+}
+
+/// Reads every `*.json` keypair in `dir`, ordered by file name.
+pub fn read_keypair_dir(dir: &Path, unlocker: &Unlocker) -> io::Result<Vec<Keypair>> {
+    let mut paths = fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<io::Result<Vec<PathBuf>>>()?;
+    paths.retain(|path| path.extension().is_some_and(|extension| extension == "json"));
+    paths.sort();
+
+    paths
+        .iter()
+        .map(|path| unlocker.read_keypair(path))
+        .collect()
+}
+
+/// Lamports moved to or from one miner.
+#[derive(Clone, Copy, Debug)]
+pub struct Transfer {
+    pub miner: Pubkey,
+    pub lamports: u64,
+}
+
+/// Where miner keypairs are loaded from.
+#[derive(Clone)]
+pub enum KeySource {
+    File(PathBuf),
+    /// Every `*.json` keypair in the directory.
+    Dir(PathBuf),
+    /// Keys derived at `m/44'/501'/i'/0'` for every `i` in `range`, from a file holding a BIP39
+    /// mnemonic or a hex encoded seed.
+    Derived {
+        seed_file: PathBuf,
+        passphrase: String,
+        range: Range<u32>,
+    },
+}
+
+impl Miner {
+    /// Loads the miners of a key source, unlocking encrypted keystores with `unlocker`.
+    pub fn load(source: &KeySource, unlocker: &Unlocker) -> io::Result<Vec<Miner>> {
+        let keypairs = match source {
+            KeySource::File(path) => vec![unlocker.read_keypair(path)?],
+            KeySource::Dir(dir) => read_keypair_dir(dir, unlocker)?,
+            KeySource::Derived {
+                seed_file,
+                passphrase,
+                range,
+            } => derive_keypairs(&read_seed_file(seed_file, passphrase)?, range.clone())?,
+        };
+        Ok(keypairs.into_iter().map(Miner::new).collect())
+    }
+}
+
+/// Reads a BIP39 mnemonic, or a 64 byte seed in hex, and returns the seed.
+pub fn read_seed_file(path: &Path, passphrase: &str) -> io::Result<Vec<u8>> {
+    let contents = Zeroizing::new(fs::read_to_string(path)?);
+    let contents = contents.trim();
+
+    if contents.len() == 128 && contents.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+        return hex::decode(contents).map_err(|err| io::Error::new(ErrorKind::InvalidData, err));
+    }
+
+    let phrase = Zeroizing::new(contents.split_whitespace().collect::<Vec<_>>().join(" "));
+    let mnemonic = Mnemonic::from_phrase(&phrase, Language::English).map_err(|err| {
+        io::Error::new(ErrorKind::InvalidData, format!("{}: {}", path.display(), err))
+    })?;
+    Ok(Seed::new(&mnemonic, passphrase).as_bytes().to_vec())
+}
+
+/// The standard Solana derivation path of the miner at `index`, `m/44'/501'/index'/0'`.
+pub fn derivation_path(index: u32) -> DerivationPath {
+    DerivationPath::new_bip44(Some(index), Some(0))
+}
+
+pub fn derive_keypairs(seed: &[u8], range: Range<u32>) -> io::Result<Vec<Keypair>> {
+    range
+        .map(|index| {
+            keypair_from_seed_and_derivation_path(seed, Some(derivation_path(index)))
+                .map_err(|err| io::Error::new(ErrorKind::InvalidInput, err.to_string()))
+        })
+        .collect()
+}
+
+/// A range of derivation indexes such as `0..64`, or a single index.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct DeriveRange(pub Range<u32>);
+
+impl FromStr for DeriveRange {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let parse = |index: &str| {
+            index
+                .trim()
+                .parse::<u32>()
+                .map_err(|err| format!("invalid derivation index {:?}: {}", index.trim(), err))
+        };
+
+        match s.split_once("..") {
+            Some((start, end)) => {
+                let range = parse(start)?..parse(end)?;
+                if range.is_empty() {
+                    return Err(format!("derivation range {} is empty", s.trim()));
+                }
+                Ok(DeriveRange(range))
+            }
+            None => {
+                let index = parse(s)?;
+                let end = index.checked_add(1).ok_or("derivation index is too large")?;
+                Ok(DeriveRange(index..end))
+            }
+        }
+    }
+}
+
+impl TryFrom<String> for DeriveRange {
+    type Error = String;
+
+    fn try_from(value: String) -> std::result::Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<DeriveRange> for String {
+    fn from(value: DeriveRange) -> Self {
+        format!("{}..{}", value.0.start, value.0.end)
+    }
+}
+
+/// A miner's wallet balance and proof, `None` if it is not registered yet.
+#[derive(Clone, Debug)]
+pub struct MinerAccount {
+    pub pubkey: Pubkey,
+    pub lamports: u64,
+    pub proof: Option<Proof>,
+    /// Rent held by the proof account. Ore v1 has no instruction to close a proof, so this is
+    /// what closing it would return once the program supports it.
+    pub proof_lamports: u64,
+}
+
+impl Ore {
+    /// Fetches the balance and proof of every miner, two accounts per miner per request.
+    pub async fn get_miner_accounts(&self) -> Result<Vec<MinerAccount>> {
+        let pubkeys = self.miners.iter().map(|miner| miner.pubkey()).collect::<Vec<_>>();
+        let client = self.get_client(None);
+
+        let mut miner_accounts = Vec::with_capacity(pubkeys.len());
+        for chunk in pubkeys.chunks(ACCOUNTS_PER_REQUEST / 2) {
+            let addresses = chunk
+                .iter()
+                .flat_map(|pubkey| [*pubkey, proof_pubkey(*pubkey)])
+                .collect::<Vec<_>>();
+            let accounts = client.get_multiple_accounts(&addresses).await?;
+
+            for (pubkey, accounts) in chunk.iter().zip(accounts.chunks(2)) {
+                miner_accounts.push(MinerAccount {
+                    pubkey: *pubkey,
+                    lamports: accounts[0].as_ref().map_or(0, |account| account.lamports),
+                    proof: accounts[1]
+                        .as_ref()
+                        .and_then(|account| Proof::try_from_bytes(&account.data).ok())
+                        .copied(),
+                    proof_lamports: accounts[1].as_ref().map_or(0, |account| account.lamports),
+                });
+            }
+        }
+
+        Ok(miner_accounts)
+    }
+
+    /// Fetches the SOL balance of every miner, 100 per request.
+    pub async fn get_miner_balances(&self) -> Result<Vec<(&Miner, u64)>> {
+        let client = self.get_client(None);
+
+        let mut balances = Vec::with_capacity(self.miners.len());
+        for chunk in self.miners.chunks(ACCOUNTS_PER_REQUEST) {
+            let addresses = chunk.iter().map(|miner| miner.pubkey()).collect::<Vec<_>>();
+            let accounts = client.get_multiple_accounts(&addresses).await?;
+            balances.extend(chunk.iter().zip(
+                accounts
+                    .iter()
+                    .map(|account| account.as_ref().map_or(0, |account| account.lamports)),
+            ));
+        }
+
+        Ok(balances)
+    }
+
+    /// The owner's ORE token account, which claims pay into.
+    pub fn owner_token_account(&self) -> Pubkey {
+        spl_associated_token_account::get_associated_token_address(
+            &self.owner.pubkey(),
+            &ore::MINT_ADDRESS,
+        )
+    }
+
+    /// The owner's lamports and the ORE in its token account.
+    pub async fn get_owner_balance(&self) -> Result<(u64, u64)> {
+        let addresses = [self.owner.pubkey(), self.owner_token_account()];
+        let accounts = self.get_client(None).get_multiple_accounts(&addresses).await?;
+        let lamports = accounts[0].as_ref().map_or(0, |account| account.lamports);
+        let ore = accounts[1]
+            .as_ref()
+            .and_then(|account| spl_token::state::Account::unpack(&account.data).ok())
+            .map_or(0, |token_account| token_account.amount);
+        Ok((lamports, ore))
+    }
+
+    /// Claims `amount` of a miner's rewards, everything claimable by default, into the owner's
+    /// token account, which is created first if needed. The miner signs and the owner pays fees.
+    /// Returns the amount claimed.
+    pub async fn claim(
+        &self,
+        miner: &Miner,
+        amount: Option<u64>,
+        policy: &SendPolicy,
+    ) -> Result<(u64, Signature)> {
+        let client = self.get_client(None);
+        let amount = match amount {
+            Some(amount) => amount,
+            None => miner.get_proof(client).await?.claimable_rewards,
+        };
+        if amount == 0 {
+            return Err(Error::CliError(CliError::NothingToClaim(miner.pubkey())));
+        }
+
+        let beneficiary = self.owner_token_account();
+        let mut instructions = vec![];
+        let token_account = client.get_multiple_accounts(&[beneficiary]).await?;
+        if token_account[0].is_none() {
+            instructions.push(
+                spl_associated_token_account::instruction::create_associated_token_account_idempotent(
+                    &self.owner.pubkey(),
+                    &self.owner.pubkey(),
+                    &ore::MINT_ADDRESS,
+                    &spl_token::id(),
+                ),
+            );
+        }
+        instructions.push(ore::instruction::claim(miner.pubkey(), beneficiary, amount));
+
+        let mut transaction = Transaction::new(instructions);
+        if transaction.instructions.len() == 1 {
+            transaction.set_cu_limit(CU_LIMIT_CLAIM);
+        }
+        transaction.set_kind(TransactionKind::Claim);
+        let signature = transaction
+            .send_and_confirm(client, &[&**miner], Some(self.fee_payer()), policy, None)
+            .await?;
+        if let Some(ledger) = &policy.ledger {
+            ledger.record_claim(miner.pubkey(), amount, signature);
+        }
+        if let Some(events) = &policy.events {
+            events.emit(Event::ClaimCompleted {
+                miner: miner.pubkey().to_string(),
+                amount,
+                signature: signature.to_string(),
+            });
+        }
+        Ok((amount, signature))
+    }
+
+    /// Claims from each of `miners`, every miner when empty, up to `amount` or everything
+    /// claimable. Miners with nothing to claim are skipped.
+    pub async fn claim_miners(
+        &self,
+        miners: &[Pubkey],
+        amount: Option<u64>,
+        policy: &SendPolicy,
+    ) -> Result<Vec<(Pubkey, u64, Result<Signature>)>> {
+        let accounts = self.get_miner_accounts().await?;
+        let mut results = vec![];
+        for (miner, account) in self.miners.iter().zip(accounts) {
+            if !miners.is_empty() && !miners.contains(&account.pubkey) {
+                continue;
+            }
+            let claimable = account.proof.map_or(0, |proof| proof.claimable_rewards);
+            let amount = amount.map_or(claimable, |amount| amount.min(claimable));
+            if amount == 0 {
+                continue;
+            }
+            let result = self
+                .claim(miner, Some(amount), policy)
+                .await
+                .map(|(_, signature)| signature);
+            results.push((account.pubkey, amount, result));
+        }
+        Ok(results)
+    }
+
+    /// Registers a proof for every miner that has none, `REGISTER_BATCH` miners per transaction
+    /// with the owner paying fees. Each miner pays its own proof rent.
+    pub async fn register_miners(
+        &self,
+        policy: &SendPolicy,
+    ) -> Result<Vec<(Vec<Pubkey>, Result<Signature>)>> {
+        let unregistered = self
+            .get_miner_accounts()
+            .await?
+            .into_iter()
+            .filter(|account| account.proof.is_none())
+            .map(|account| account.pubkey)
+            .collect::<Vec<_>>();
+        let miners = self
+            .miners
+            .iter()
+            .filter(|miner| unregistered.contains(&miner.pubkey()))
+            .collect::<Vec<&Miner>>();
+
+        let batches = miners
+            .chunks(REGISTER_BATCH)
+            .map(|batch| {
+                let instructions = batch
+                    .iter()
+                    .map(|miner| ore::instruction::register(miner.pubkey()))
+                    .collect();
+                (batch.to_vec(), instructions)
+            })
+            .collect();
+        let results = self
+            .send_batches(TransactionKind::Register, CU_PER_REGISTER, batches, policy)
+            .await;
+
+        Ok(results
+            .into_iter()
+            .map(|(miners, result)| (miners.iter().map(|miner| miner.pubkey()).collect(), result))
+            .collect())
+    }
+
+    /// Every miner with its proof. Miners without one are registered first, as `register_miners`
+    /// does.
+    pub async fn get_or_register_proofs(&self, policy: &SendPolicy) -> Result<Vec<(Miner, Proof)>> {
+        let mut accounts = self.get_miner_accounts().await?;
+        let unregistered = accounts.iter().filter(|account| account.proof.is_none()).count();
+        if unregistered > 0 {
+            info!(count = unregistered, "Registering miners");
+            for (pubkeys, result) in self.register_miners(policy).await? {
+                if let Err(err) = result {
+                    warn!(miners = ?pubkeys, error = %err.to_string(), "Failed to register");
+                }
+            }
+            accounts = self.get_miner_accounts().await?;
+        }
+
+        self.miners
+            .iter()
+            .zip(accounts)
+            .map(|(miner, account)| match account.proof {
+                Some(proof) => Ok((miner.clone(), proof)),
+                None => Err(Error::CliError(CliError::Unregistered(account.pubkey))),
+            })
+            .collect()
+    }
+
+    /// Tops every miner below `target` lamports up to it from the owner.
+    pub async fn fund_miners(
+        &self,
+        target: u64,
+        policy: &SendPolicy,
+    ) -> Result<Vec<(Vec<Transfer>, Result<Signature>)>> {
+        let owner = self.owner.pubkey();
+        let transfers = self
+            .get_miner_balances()
+            .await?
+            .into_iter()
+            .filter(|(_, lamports)| *lamports < target)
+            .map(|(miner, lamports)| Transfer {
+                miner: miner.pubkey(),
+                lamports: target - lamports,
+            })
+            .collect::<Vec<_>>();
+
+        let batches = transfers
+            .chunks(FUND_BATCH)
+            .map(|batch| {
+                let instructions = batch
+                    .iter()
+                    .map(|transfer| system_instruction::transfer(&owner, &transfer.miner, transfer.lamports))
+                    .collect();
+                (vec![], instructions)
+            })
+            .collect();
+        let results = self
+            .send_batches(TransactionKind::Fund, CU_PER_TRANSFER, batches, policy)
+            .await;
+
+        let results = transfers
+            .chunks(FUND_BATCH)
+            .zip(results)
+            .map(|(batch, (_, result))| (batch.to_vec(), result))
+            .collect::<Vec<_>>();
+        record_transfers(TransactionKind::Fund, &results, policy);
+        Ok(results)
+    }
+
+    /// Moves every miner's whole SOL balance back to the owner, who pays the fees.
+    pub async fn sweep_miners(
+        &self,
+        policy: &SendPolicy,
+    ) -> Result<Vec<(Vec<Transfer>, Result<Signature>)>> {
+        let owner = self.owner.pubkey();
+        let balances = self
+            .get_miner_balances()
+            .await?
+            .into_iter()
+            .filter(|(_, lamports)| *lamports > 0)
+            .collect::<Vec<_>>();
+
+        let batches = balances
+            .chunks(SWEEP_BATCH)
+            .map(|batch| {
+                let instructions = batch
+                    .iter()
+                    .map(|(miner, lamports)| system_instruction::transfer(&miner.pubkey(), &owner, *lamports))
+                    .collect();
+                (batch.iter().map(|(miner, _)| *miner).collect(), instructions)
+            })
+            .collect();
+        let results = self
+            .send_batches(TransactionKind::Sweep, CU_PER_TRANSFER, batches, policy)
+            .await;
+
+        let results = balances
+            .chunks(SWEEP_BATCH)
+            .zip(results)
+            .map(|(batch, (_, result))| {
+                let transfers = batch
+                    .iter()
+                    .map(|(miner, lamports)| Transfer {
+                        miner: miner.pubkey(),
+                        lamports: *lamports,
+                    })
+                    .collect();
+                (transfers, result)
+            })
+            .collect::<Vec<_>>();
+        record_transfers(TransactionKind::Sweep, &results, policy);
+        Ok(results)
+    }
+
+    /// Sends every batch signed by its miners with the owner as fee payer, `CONCURRENT_SENDS` at a
+    /// time, and returns the results in batch order. Each instruction gets `units` compute units.
+    async fn send_batches<'a>(
+        &'a self,
+        kind: TransactionKind,
+        units: u32,
+        batches: Vec<(Vec<&'a Miner>, Vec<Instruction>)>,
+        policy: &SendPolicy,
+    ) -> Vec<(Vec<&'a Miner>, Result<Signature>)> {
+        let sends = batches.into_iter().map(|(miners, instructions)| async move {
+            let signers = miners
+                .iter()
+                .map(|miner| &***miner as &(dyn Signer + Sync))
+                .collect::<Vec<_>>();
+            let transaction = batch_transaction(kind, units, instructions);
+            let result = transaction
+                .send_and_confirm(self.get_client(None), &signers, Some(self.fee_payer()), policy, None)
+                .await;
+            (miners, result)
+        });
+
+        stream::iter(sends).buffered(CONCURRENT_SENDS).collect().await
+    }
+}
+
+/// A batch of `instructions` limited to `units` compute units each, so the priority fee is paid
+/// on what the batch uses rather than the runtime's default of 200k units per instruction.
+pub fn batch_transaction(
+    kind: TransactionKind,
+    units: u32,
+    instructions: Vec<Instruction>,
+) -> Transaction {
+    let limit = CU_BUDGET_INSTRUCTIONS + units * instructions.len() as u32;
+    let mut transaction = Transaction::new(instructions);
+    transaction.set_kind(kind);
+    transaction.set_cu_limit(limit);
+    transaction
+}
+
+/// Records the transfers of every batch that landed in the policy's ledger.
+fn record_transfers(
+    kind: TransactionKind,
+    results: &[(Vec<Transfer>, Result<Signature>)],
+    policy: &SendPolicy,
+) {
+    let Some(ledger) = &policy.ledger else {
+        return;
+    };
+    for (transfers, result) in results {
+        if let Ok(signature) = result {
+            for transfer in transfers {
+                ledger.record_transfer(kind, transfer, *signature);
+            }
+        }
+    }
+}
@@ -0,0 +1,85 @@
+use solana_sdk::{
+    keccak::{hashv, Hash as KeccakHash},
+    pubkey::Pubkey,
+};
+
+pub const WORKGROUP_SIZE: u32 = 16 * 16 * 2;
+/// Workgroups per dispatch, shared between the jobs of a batch.
+pub const WORKGROUP_COUNT: u32 = 16 * 16 * 16 * 8;
+
+/// Size of a `Job` in `shaders/mine.wgsl`: prefix at 0, nonce at 64, difficulty at 72.
+pub const JOB_SIZE: usize = 16 * 4 + 2 * 4 + 8 * 4;
+/// Size of a `JobResult` in `shaders/mine.wgsl`: found at 0, nonce at 8.
+pub const RESULT_SIZE: usize = 16;
+
+/// One miner's search on the GPU. `nonce` is where the next dispatch starts.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GpuJob {
+    pub challenge: KeccakHash,
+    pub pubkey: Pubkey,
+    pub difficulty: KeccakHash,
+    pub nonce: u64,
+}
+
+impl GpuJob {
+    pub fn new(challenge: KeccakHash, pubkey: Pubkey, difficulty: KeccakHash) -> Self {
+        GpuJob {
+            challenge,
+            pubkey,
+            difficulty,
+            nonce: 0,
+        }
+    }
+
+    pub fn hash(&self, nonce: u64) -> KeccakHash {
+        hashv(&[self.challenge.as_ref(), self.pubkey.as_ref(), &nonce.to_le_bytes()])
+    }
+
+    pub fn pack(&self, out: &mut [u8]) {
+        out[0..32].copy_from_slice(self.challenge.as_ref());
+        out[32..64].copy_from_slice(self.pubkey.as_ref());
+        out[64..72].copy_from_slice(&self.nonce.to_le_bytes());
+        out[72..104].copy_from_slice(self.difficulty.as_ref());
+    }
+}
+
+/// Workgroups each job gets so a dispatch stays around `WORKGROUP_COUNT` workgroups however
+/// many jobs it carries.
+pub fn workgroups_per_job(jobs: usize) -> u32 {
+    let jobs = u32::try_from(jobs.max(1).next_power_of_two()).unwrap_or(u32::MAX);
+    (WORKGROUP_COUNT / jobs).max(1)
+}
+
+/// Nonces every job advances by in one dispatch of `jobs` jobs.
+pub fn nonces_per_job(jobs: usize) -> u64 {
+    WORKGROUP_SIZE as u64 * workgroups_per_job(jobs) as u64
+}
+
+pub fn pack_jobs(jobs: &[GpuJob]) -> Vec<u8> {
+    let mut data = vec![0u8; jobs.len() * JOB_SIZE];
+    for (job, out) in jobs.iter().zip(data.chunks_exact_mut(JOB_SIZE)) {
+        job.pack(out);
+    }
+    data
+}
+
+/// Reads the found nonce of every job from the results buffer.
+pub fn decode_results(data: &[u8], jobs: usize) -> Vec<Option<u64>> {
+    data.chunks_exact(RESULT_SIZE)
+        .take(jobs)
+        .map(|result| {
+            let found = u32::from_le_bytes(result[0..4].try_into().unwrap());
+            (found > 0).then(|| u64::from_le_bytes(result[8..16].try_into().unwrap()))
+        })
+        .collect()
+}
+
+/// Moves every job that found nothing past the nonces the dispatch covered.
+pub fn advance_jobs(jobs: &mut [GpuJob], results: &[Option<u64>]) {
+    let step = nonces_per_job(jobs.len());
+    for (job, result) in jobs.iter_mut().zip(results) {
+        if result.is_none() {
+            job.nonce = job.nonce.wrapping_add(step);
+        }
+    }
+}
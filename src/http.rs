@@ -0,0 +1,42 @@
+//! The bit of HTTP/1.1 the signer daemon and the metrics server speak.
+
+use std::io::{self, BufRead, ErrorKind};
+
+/// The status or request line of an HTTP message and the headers this crate reads.
+pub(crate) struct HttpHead {
+    pub first: String,
+    pub length: usize,
+    /// The token of an `Authorization: Bearer` header.
+    pub bearer: Option<String>,
+}
+
+/// Reads an HTTP status or request line and headers, refusing a body longer than `max_length`.
+pub(crate) fn read_http_head(reader: &mut impl BufRead, max_length: usize) -> io::Result<HttpHead> {
+    let mut first = String::new();
+    reader.read_line(&mut first)?;
+    let (mut length, mut bearer) = (0, None);
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                length = value
+                    .trim()
+                    .parse()
+                    .map_err(|err| io::Error::new(ErrorKind::InvalidData, err))?;
+            } else if name.trim().eq_ignore_ascii_case("authorization") {
+                bearer = value.trim().strip_prefix("Bearer ").map(str::to_string);
+            }
+        }
+    }
+    if length > max_length {
+        return Err(io::Error::new(ErrorKind::InvalidData, "body too large"));
+    }
+    Ok(HttpHead {
+        first: first.trim_end().to_string(),
+        length,
+        bearer,
+    })
+}
@@ -1,11 +1,21 @@
 use rand::Rng;
-use reqwest::{Client, Result, Url};
+use reqwest::{Client, Url};
 use serde::{Deserialize, Serialize};
+use solana_client::client_error::{ClientErrorKind, Result};
 use solana_program::pubkey::Pubkey;
 use solana_sdk::transaction::Transaction;
 
+/// Lamports tipped with every bundle unless configured otherwise.
+pub const DEFAULT_TIP: u64 = 10_000;
+
+/// A block engine that lands transactions as bundles, each paying a tip to one of its tip
+/// accounts.
+#[derive(Debug)]
 pub struct JitoClient {
     server_url: Url,
+    client: Client,
+    /// Lamports tipped with every bundle.
+    pub tip: u64,
     pub tip_accounts: Vec<Pubkey>,
 }
 
@@ -32,24 +42,40 @@ struct SendJitoBundleResponse {
 }
 
 impl JitoClient {
-    pub async fn new(server_url: &str) -> Result<Self> {
-        let server_url = Url::parse(server_url).unwrap();
-
-        let get_tip_accounts_url = server_url.join("/getTipAccounts").unwrap();
+    /// Connects to the block engine at `server_url`, reading its tip accounts.
+    pub async fn new(server_url: &str, tip: u64) -> Result<Self> {
+        let server_url = Url::parse(server_url).map_err(|err| {
+            ClientErrorKind::Custom(format!("invalid jito url {}: {}", server_url, err))
+        })?;
+        let client = Client::new();
 
-        let response = reqwest::get(get_tip_accounts_url)
+        let response = client
+            .get(join(&server_url, "/getTipAccounts")?)
+            .send()
             .await?
+            .error_for_status()?
             .json::<GetTipAccountsResponse>()
             .await?;
 
         let tip_accounts = response
             .data
             .iter()
-            .map(|key| Pubkey::try_from(bs58::decode(key).into_vec().unwrap()).unwrap())
-            .collect();
+            .map(|key| {
+                key.parse::<Pubkey>().map_err(|err| {
+                    ClientErrorKind::Custom(format!("invalid tip account {}: {}", key, err))
+                })
+            })
+            .collect::<std::result::Result<Vec<Pubkey>, ClientErrorKind>>()?;
+        if tip_accounts.is_empty() {
+            return Err(
+                ClientErrorKind::Custom("the block engine has no tip accounts".into()).into(),
+            );
+        }
 
         Ok(JitoClient {
             server_url,
+            client,
+            tip,
             tip_accounts,
         })
     }
@@ -59,9 +85,8 @@ impl JitoClient {
         self.tip_accounts[index]
     }
 
+    /// Sends the transactions as one bundle, returning its id.
     pub async fn send_jito_bundle(&self, signed_transactions: Vec<&Transaction>) -> Result<String> {
-        let send_jito_bundle_url = self.server_url.join("/sendJitoBundle").unwrap();
-
         let request_transactions = signed_transactions
             .iter()
             .map(|tran| {
@@ -83,15 +108,22 @@ impl JitoClient {
             transactions: request_transactions,
         };
 
-        let client = Client::new();
-        let response = client
-            .post(send_jito_bundle_url)
+        let response = self
+            .client
+            .post(join(&self.server_url, "/sendJitoBundle")?)
             .json(&request)
             .send()
             .await?
+            .error_for_status()?
             .json::<SendJitoBundleResponse>()
             .await?;
 
         Ok(response.data)
     }
 }
+
+fn join(server_url: &Url, path: &str) -> Result<Url> {
+    server_url
+        .join(path)
+        .map_err(|err| ClientErrorKind::Custom(format!("invalid jito url: {}", err)).into())
+}
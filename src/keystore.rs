@@ -0,0 +1,327 @@
+use std::{
+    fmt::Display,
+    fs::{self, File, OpenOptions},
+    io::{self, BufRead, BufReader, ErrorKind, Write},
+    os::{fd::BorrowedFd, unix::fs::OpenOptionsExt},
+    path::Path,
+    str::FromStr,
+    sync::Mutex,
+};
+
+use aes_gcm_siv::{
+    aead::{Aead, NewAead, Payload},
+    Aes256GcmSiv, Key, Nonce,
+};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use solana_sdk::{pubkey::Pubkey, signature::Keypair, signer::Signer};
+use zeroize::Zeroizing;
+
+const VERSION: u32 = 1;
+const SALT_SIZE: usize = 32;
+const NONCE_SIZE: usize = 12;
+
+/// Passphrase descriptors already read, which hold nothing more to read.
+static READ_FDS: Mutex<Vec<i32>> = Mutex::new(Vec::new());
+
+#[derive(Debug)]
+pub enum KeystoreError {
+    Io(io::Error),
+    Format(String),
+    Kdf(String),
+    /// The passphrase is wrong or the file was tampered with.
+    Decrypt,
+}
+
+impl Display for KeystoreError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            KeystoreError::Io(err) => write!(f, "{}", err),
+            KeystoreError::Format(err) => write!(f, "invalid keystore: {}", err),
+            KeystoreError::Kdf(err) => write!(f, "key derivation failed: {}", err),
+            KeystoreError::Decrypt => write!(f, "wrong passphrase or corrupted keystore"),
+        }
+    }
+}
+
+impl From<io::Error> for KeystoreError {
+    fn from(value: io::Error) -> Self {
+        KeystoreError::Io(value)
+    }
+}
+
+impl From<KeystoreError> for io::Error {
+    fn from(value: KeystoreError) -> Self {
+        match value {
+            KeystoreError::Io(err) => err,
+            err => io::Error::new(ErrorKind::InvalidData, err.to_string()),
+        }
+    }
+}
+
+/// scrypt cost parameters, stored with every keystore so they can be raised later.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KdfParams {
+    pub log_n: u8,
+    pub r: u32,
+    pub p: u32,
+}
+
+impl Default for KdfParams {
+    fn default() -> Self {
+        KdfParams {
+            log_n: 15,
+            r: 8,
+            p: 1,
+        }
+    }
+}
+
+/// A keypair encrypted with a passphrase: scrypt derives a key that seals the 64 keypair bytes
+/// with AES-256-GCM-SIV. The pubkey is stored in the clear and authenticated as associated data.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Keystore {
+    pub version: u32,
+    pub pubkey: String,
+    pub kdf: String,
+    pub kdf_params: KdfParams,
+    pub salt: String,
+    pub cipher: String,
+    pub nonce: String,
+    pub ciphertext: String,
+}
+
+impl Keystore {
+    pub fn encrypt(keypair: &Keypair, passphrase: &str) -> Result<Self, KeystoreError> {
+        Self::encrypt_with(keypair, passphrase, KdfParams::default())
+    }
+
+    pub fn encrypt_with(
+        keypair: &Keypair,
+        passphrase: &str,
+        kdf_params: KdfParams,
+    ) -> Result<Self, KeystoreError> {
+        let mut salt = [0u8; SALT_SIZE];
+        let mut nonce = [0u8; NONCE_SIZE];
+        rand::thread_rng().fill_bytes(&mut salt);
+        rand::thread_rng().fill_bytes(&mut nonce);
+
+        let pubkey = keypair.pubkey();
+        let key = derive_key(passphrase, &salt, kdf_params)?;
+        let secret = Zeroizing::new(keypair.to_bytes());
+        let ciphertext = Aes256GcmSiv::new(Key::from_slice(key.as_ref()))
+            .encrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: secret.as_ref(),
+                    aad: pubkey.as_ref(),
+                },
+            )
+            .map_err(|_| KeystoreError::Decrypt)?;
+
+        Ok(Keystore {
+            version: VERSION,
+            pubkey: pubkey.to_string(),
+            kdf: "scrypt".into(),
+            kdf_params,
+            salt: hex::encode(salt),
+            cipher: "aes-256-gcm-siv".into(),
+            nonce: hex::encode(nonce),
+            ciphertext: hex::encode(ciphertext),
+        })
+    }
+
+    pub fn pubkey(&self) -> Result<Pubkey, KeystoreError> {
+        Pubkey::from_str(&self.pubkey).map_err(|err| KeystoreError::Format(err.to_string()))
+    }
+
+    pub fn decrypt(&self, passphrase: &str) -> Result<Keypair, KeystoreError> {
+        if self.version != VERSION || self.kdf != "scrypt" || self.cipher != "aes-256-gcm-siv" {
+            return Err(KeystoreError::Format(format!(
+                "unsupported version {} with {} and {}",
+                self.version, self.kdf, self.cipher
+            )));
+        }
+        let pubkey = self.pubkey()?;
+        let salt = decode_hex(&self.salt)?;
+        let nonce = decode_hex(&self.nonce)?;
+        let ciphertext = decode_hex(&self.ciphertext)?;
+        if nonce.len() != NONCE_SIZE {
+            return Err(KeystoreError::Format("bad nonce length".into()));
+        }
+
+        let key = derive_key(passphrase, &salt, self.kdf_params)?;
+        let secret = Zeroizing::new(
+            Aes256GcmSiv::new(Key::from_slice(key.as_ref()))
+                .decrypt(
+                    Nonce::from_slice(&nonce),
+                    Payload {
+                        msg: &ciphertext,
+                        aad: pubkey.as_ref(),
+                    },
+                )
+                .map_err(|_| KeystoreError::Decrypt)?,
+        );
+        let keypair =
+            Keypair::from_bytes(&secret).map_err(|err| KeystoreError::Format(err.to_string()))?;
+        if keypair.pubkey() != pubkey {
+            return Err(KeystoreError::Decrypt);
+        }
+        Ok(keypair)
+    }
+
+    pub fn load(path: &Path) -> Result<Self, KeystoreError> {
+        serde_json::from_slice(&fs::read(path)?)
+            .map_err(|err| KeystoreError::Format(err.to_string()))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), KeystoreError> {
+        let data = serde_json::to_vec_pretty(self)
+            .map_err(|err| KeystoreError::Format(err.to_string()))?;
+        write_private(path, &data)?;
+        Ok(())
+    }
+}
+
+fn derive_key(
+    passphrase: &str,
+    salt: &[u8],
+    params: KdfParams,
+) -> Result<Zeroizing<[u8; 32]>, KeystoreError> {
+    let params = scrypt::Params::new(params.log_n, params.r, params.p, 32)
+        .map_err(|err| KeystoreError::Kdf(err.to_string()))?;
+    let mut key = Zeroizing::new([0u8; 32]);
+    scrypt::scrypt(passphrase.as_bytes(), salt, &params, key.as_mut())
+        .map_err(|err| KeystoreError::Kdf(err.to_string()))?;
+    Ok(key)
+}
+
+fn decode_hex(value: &str) -> Result<Vec<u8>, KeystoreError> {
+    hex::decode(value).map_err(|err| KeystoreError::Format(err.to_string()))
+}
+
+/// Writes a file only the current user can read.
+pub fn write_private(path: &Path, data: &[u8]) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(path)?
+        .write_all(data)
+}
+
+/// Reads a plaintext `solana-keygen` JSON keypair without leaving copies of the secret behind.
+pub fn read_plain_keypair(path: &Path) -> io::Result<Keypair> {
+    let contents = Zeroizing::new(fs::read_to_string(path)?);
+    let bytes = Zeroizing::new(
+        serde_json::from_str::<Vec<u8>>(&contents)
+            .map_err(|err| io::Error::new(ErrorKind::InvalidData, err))?,
+    );
+    Keypair::from_bytes(&bytes).map_err(|err| io::Error::new(ErrorKind::InvalidData, err))
+}
+
+/// Writes a keypair in the plaintext `solana-keygen` format.
+pub fn write_plain_keypair(keypair: &Keypair, path: &Path) -> io::Result<()> {
+    let bytes = Zeroizing::new(keypair.to_bytes());
+    let contents = Zeroizing::new(serde_json::to_vec(bytes.as_ref())?);
+    write_private(path, &contents)
+}
+
+/// Where keystore passphrases come from.
+#[derive(Clone, Debug, Default)]
+pub enum PassphraseSource {
+    /// Ask on the terminal.
+    #[default]
+    Prompt,
+    /// Read from the named environment variable.
+    Env(String),
+    /// Read the first line of an inherited file descriptor, e.g. `--passphrase-fd 3 3<secret`.
+    /// The descriptor is read once, a second read is an error.
+    Fd(i32),
+}
+
+impl PassphraseSource {
+    pub fn read(&self, prompt: &str) -> io::Result<Zeroizing<String>> {
+        match self {
+            PassphraseSource::Prompt => rpassword::prompt_password(prompt).map(Zeroizing::new),
+            PassphraseSource::Env(name) => std::env::var(name)
+                .map(Zeroizing::new)
+                .map_err(|err| io::Error::new(ErrorKind::NotFound, format!("{}: {}", name, err))),
+            PassphraseSource::Fd(fd) => {
+                let mut read_fds = READ_FDS.lock().unwrap();
+                if read_fds.contains(fd) {
+                    return Err(io::Error::new(
+                        ErrorKind::InvalidInput,
+                        format!("the passphrase on descriptor {} was already read", fd),
+                    ));
+                }
+                read_fds.push(*fd);
+                // Safety: the descriptor is inherited and never closed by this process, so it
+                // stays open while it is duplicated. Only the duplicate is closed.
+                let file = File::from(unsafe { BorrowedFd::borrow_raw(*fd) }.try_clone_to_owned()?);
+                let mut line = Zeroizing::new(String::new());
+                BufReader::new(file).read_line(&mut line)?;
+                Ok(Zeroizing::new(
+                    line.trim_end_matches(['\r', '\n']).to_string(),
+                ))
+            }
+        }
+    }
+
+    /// Reads a new passphrase, asking twice when prompting.
+    pub fn read_new(&self) -> io::Result<Zeroizing<String>> {
+        let passphrase = self.read("New passphrase: ")?;
+        if let PassphraseSource::Prompt = self {
+            if *self.read("Repeat passphrase: ")? != *passphrase {
+                return Err(io::Error::new(
+                    ErrorKind::InvalidInput,
+                    "passphrases do not match",
+                ));
+            }
+        }
+        Ok(passphrase)
+    }
+}
+
+/// Loads keypairs that may be plaintext or encrypted. The passphrase is asked for once, the
+/// first time an encrypted keystore is found, and then reused for every other keystore.
+pub struct Unlocker {
+    source: PassphraseSource,
+    passphrase: Mutex<Option<Zeroizing<String>>>,
+}
+
+impl Unlocker {
+    pub fn new(source: PassphraseSource) -> Self {
+        Unlocker {
+            source,
+            passphrase: Mutex::new(None),
+        }
+    }
+
+    pub fn read_keypair(&self, path: &Path) -> io::Result<Keypair> {
+        let Ok(keystore) = Keystore::load(path) else {
+            return read_plain_keypair(path)
+                .map_err(|err| io::Error::new(err.kind(), format!("{}: {}", path.display(), err)));
+        };
+
+        let mut passphrase = self.passphrase.lock().unwrap();
+        if passphrase.is_none() {
+            *passphrase = Some(self.source.read("Keystore passphrase: ")?);
+        }
+        let result = keystore.decrypt(passphrase.as_ref().unwrap());
+        if let Err(KeystoreError::Decrypt) = result {
+            // Ask again for the next keystore rather than repeating a wrong passphrase.
+            *passphrase = None;
+        }
+        result.map_err(|err| {
+            io::Error::new(
+                ErrorKind::InvalidData,
+                format!("{}: {}", path.display(), err),
+            )
+        })
+    }
+}
@@ -0,0 +1,392 @@
+//! A local SQLite record of everything mining does: found work, every send attempt with its fee,
+//! compute units, endpoint and outcome, proof snapshots, claims and transfers. It outlives
+//! restarts, so `ore stats` can weigh earnings against the SOL spent on fees.
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::{self, ErrorKind},
+    ops::Range,
+    path::Path,
+    sync::Mutex,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use ore::state::Proof;
+use rusqlite::{params, Connection};
+use solana_sdk::{pubkey::Pubkey, signature::Signature, signer::Signer};
+use tracing::warn;
+
+use crate::{
+    fleet::Transfer,
+    metrics::Outcome,
+    mine::{SignedWork, Work},
+};
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS work (
+        time INTEGER NOT NULL,
+        miner TEXT NOT NULL,
+        challenge TEXT NOT NULL,
+        hash TEXT NOT NULL,
+        nonce INTEGER NOT NULL,
+        bus INTEGER NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS attempts (
+        time INTEGER NOT NULL,
+        kind TEXT NOT NULL,
+        miner TEXT,
+        signature TEXT,
+        attempt INTEGER NOT NULL,
+        cu_limit INTEGER,
+        cu_price INTEGER,
+        fee_lamports INTEGER NOT NULL,
+        endpoint TEXT NOT NULL,
+        outcome TEXT NOT NULL,
+        error TEXT
+    );
+    CREATE TABLE IF NOT EXISTS proofs (
+        time INTEGER NOT NULL,
+        miner TEXT NOT NULL,
+        claimable_rewards INTEGER NOT NULL,
+        total_rewards INTEGER NOT NULL,
+        total_hashes INTEGER NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS claims (
+        time INTEGER NOT NULL,
+        miner TEXT NOT NULL,
+        amount INTEGER NOT NULL,
+        signature TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS transfers (
+        time INTEGER NOT NULL,
+        kind TEXT NOT NULL,
+        miner TEXT NOT NULL,
+        lamports INTEGER NOT NULL,
+        signature TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS attempts_time ON attempts (time);
+    CREATE INDEX IF NOT EXISTS proofs_miner_time ON proofs (miner, time);
+";
+
+/// What a transaction was sent for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TransactionKind {
+    Mine,
+    Claim,
+    Register,
+    Fund,
+    Sweep,
+    Admin,
+}
+
+impl TransactionKind {
+    pub const ALL: [TransactionKind; 6] = [
+        TransactionKind::Mine,
+        TransactionKind::Claim,
+        TransactionKind::Register,
+        TransactionKind::Fund,
+        TransactionKind::Sweep,
+        TransactionKind::Admin,
+    ];
+
+    pub fn from_label(label: &str) -> Option<TransactionKind> {
+        TransactionKind::ALL
+            .into_iter()
+            .find(|kind| kind.label() == label)
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            TransactionKind::Mine => "mine",
+            TransactionKind::Claim => "claim",
+            TransactionKind::Register => "register",
+            TransactionKind::Fund => "fund",
+            TransactionKind::Sweep => "sweep",
+            TransactionKind::Admin => "admin",
+        }
+    }
+}
+
+/// One send of a transaction and how it ended.
+#[derive(Clone, Debug)]
+pub struct Attempt<'a> {
+    pub kind: Option<TransactionKind>,
+    /// The miner charged for the fee, `None` when the owner is.
+    pub miner: Option<Pubkey>,
+    /// `None` when sending failed before a signature was returned.
+    pub signature: Option<Signature>,
+    pub attempt: usize,
+    /// Compute units the transaction is limited to, the runtime default without a limit.
+    pub cu_limit: u32,
+    pub cu_price: Option<u64>,
+    /// Base and priority fee the transaction pays if it lands.
+    pub fee_lamports: u64,
+    pub endpoint: &'a str,
+    pub outcome: Outcome,
+    pub error: Option<String>,
+}
+
+/// A transaction the ledger saw land.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LandedTransaction {
+    /// Unix seconds.
+    pub time: i64,
+    pub signature: String,
+    pub kind: Option<TransactionKind>,
+    pub miner: Option<Pubkey>,
+    pub fee_lamports: u64,
+    pub priority_fee_lamports: u64,
+    /// The amount of a claim.
+    pub claimed: Option<u64>,
+}
+
+/// Totals of one miner or one day.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Summary {
+    /// Work found by the hashers.
+    pub found: u64,
+    /// Mine transactions that landed.
+    pub landed: u64,
+    /// Mine transaction attempts that expired or failed.
+    pub failed: u64,
+    /// Rewards in ORE base units, from the growth of `total_rewards` between proof snapshots.
+    pub earned: u64,
+    pub claimed: u64,
+    /// Lamports paid in fees by landed transactions of every kind.
+    pub fees: u64,
+}
+
+/// How `Ledger::summary` groups its totals.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Grouping {
+    /// By miner pubkey. Fees the owner paid for several miners at once are grouped as `owner`.
+    Miner,
+    /// By UTC day, `YYYY-MM-DD`.
+    Day,
+}
+
+impl Grouping {
+    fn key(&self) -> &'static str {
+        match self {
+            Grouping::Miner => "COALESCE(miner, 'owner')",
+            Grouping::Day => "date(time, 'unixepoch')",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Ledger {
+    connection: Mutex<Connection>,
+}
+
+impl Ledger {
+    /// Opens the ledger at `path`, creating it and its tables if needed.
+    pub fn open(path: &Path) -> io::Result<Ledger> {
+        if let Some(parent) = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+        {
+            fs::create_dir_all(parent)?;
+        }
+        let connection = Connection::open(path).map_err(to_io)?;
+        // WAL lets `ore stats` read while a miner writes.
+        connection
+            .pragma_update(None, "journal_mode", "WAL")
+            .map_err(to_io)?;
+        connection
+            .busy_timeout(Duration::from_secs(5))
+            .map_err(to_io)?;
+        connection.execute_batch(SCHEMA).map_err(to_io)?;
+        Ok(Ledger {
+            connection: Mutex::new(connection),
+        })
+    }
+
+    pub fn record_work(&self, signed_work: &SignedWork) {
+        let Work::ToBeProved(hash, nonce) = &signed_work.work else {
+            return;
+        };
+        self.insert(
+            "INSERT INTO work VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                now(),
+                signed_work.signer.pubkey().to_string(),
+                signed_work.challenge.to_string(),
+                hash.to_string(),
+                *nonce as i64,
+                signed_work.bus as i64,
+            ],
+        );
+    }
+
+    pub fn record_attempt(&self, attempt: &Attempt) {
+        self.insert(
+            "INSERT INTO attempts VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+            params![
+                now(),
+                attempt.kind.map_or("other", |kind| kind.label()),
+                attempt.miner.map(|miner| miner.to_string()),
+                attempt.signature.map(|signature| signature.to_string()),
+                attempt.attempt as i64,
+                attempt.cu_limit,
+                attempt.cu_price.map(|price| price as i64),
+                attempt.fee_lamports as i64,
+                attempt.endpoint,
+                attempt.outcome.label(),
+                attempt.error,
+            ],
+        );
+    }
+
+    pub fn record_proof(&self, miner: Pubkey, proof: &Proof) {
+        self.insert(
+            "INSERT INTO proofs VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                now(),
+                miner.to_string(),
+                proof.claimable_rewards as i64,
+                proof.total_rewards as i64,
+                proof.total_hashes as i64,
+            ],
+        );
+    }
+
+    pub fn record_claim(&self, miner: Pubkey, amount: u64, signature: Signature) {
+        self.insert(
+            "INSERT INTO claims VALUES (?1, ?2, ?3, ?4)",
+            params![
+                now(),
+                miner.to_string(),
+                amount as i64,
+                signature.to_string()
+            ],
+        );
+    }
+
+    pub fn record_transfer(
+        &self,
+        kind: TransactionKind,
+        transfer: &Transfer,
+        signature: Signature,
+    ) {
+        self.insert(
+            "INSERT INTO transfers VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                now(),
+                kind.label(),
+                transfer.miner.to_string(),
+                transfer.lamports as i64,
+                signature.to_string(),
+            ],
+        );
+    }
+
+    /// Totals per miner or per day, ordered by key.
+    pub fn summary(&self, grouping: Grouping) -> io::Result<Vec<(String, Summary)>> {
+        let key = grouping.key();
+        let connection = self.connection.lock().unwrap();
+        let mut summaries = BTreeMap::<String, Summary>::new();
+        let mut add = |sql: String, field: fn(&mut Summary) -> &mut u64| -> io::Result<()> {
+            let mut statement = connection.prepare(&sql).map_err(to_io)?;
+            let rows = statement
+                .query_map([], |row| {
+                    Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?))
+                })
+                .map_err(to_io)?;
+            for row in rows {
+                let (key, value) = row.map_err(to_io)?;
+                *field(summaries.entry(key).or_default()) += value as u64;
+            }
+            Ok(())
+        };
+
+        add(
+            format!("SELECT {key}, COUNT(*) FROM work GROUP BY 1"),
+            |summary| &mut summary.found,
+        )?;
+        add(
+            format!(
+                "SELECT {key}, COUNT(*) FROM attempts \
+                 WHERE kind = 'mine' AND outcome = 'landed' GROUP BY 1"
+            ),
+            |summary| &mut summary.landed,
+        )?;
+        add(
+            format!(
+                "SELECT {key}, COUNT(*) FROM attempts \
+                 WHERE kind = 'mine' AND outcome != 'landed' GROUP BY 1"
+            ),
+            |summary| &mut summary.failed,
+        )?;
+        add(
+            format!(
+                "SELECT {key}, SUM(delta) FROM (\
+                     SELECT time, miner, total_rewards - LAG(total_rewards) \
+                     OVER (PARTITION BY miner ORDER BY time, rowid) AS delta FROM proofs\
+                 ) WHERE delta > 0 GROUP BY 1"
+            ),
+            |summary| &mut summary.earned,
+        )?;
+        add(
+            format!("SELECT {key}, SUM(amount) FROM claims GROUP BY 1"),
+            |summary| &mut summary.claimed,
+        )?;
+        add(
+            format!(
+                "SELECT {key}, SUM(fee_lamports) FROM attempts WHERE outcome = 'landed' GROUP BY 1"
+            ),
+            |summary| &mut summary.fees,
+        )?;
+
+        Ok(summaries.into_iter().collect())
+    }
+
+    /// Transactions that landed in `range`, unix seconds, oldest first.
+    pub fn landed(&self, range: Range<i64>) -> io::Result<Vec<LandedTransaction>> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection
+            .prepare(
+                "SELECT attempts.time, attempts.signature, kind, attempts.miner, fee_lamports, \
+                 (COALESCE(cu_price, 0) * COALESCE(cu_limit, 0) + 999999) / 1000000, amount \
+                 FROM attempts LEFT JOIN claims ON claims.signature = attempts.signature \
+                 WHERE outcome = 'landed' AND attempts.signature IS NOT NULL \
+                 AND attempts.time >= ?1 AND attempts.time < ?2 \
+                 ORDER BY attempts.time, attempts.rowid",
+            )
+            .map_err(to_io)?;
+        let rows = statement
+            .query_map(params![range.start, range.end], |row| {
+                Ok(LandedTransaction {
+                    time: row.get(0)?,
+                    signature: row.get(1)?,
+                    kind: TransactionKind::from_label(&row.get::<_, String>(2)?),
+                    miner: row
+                        .get::<_, Option<String>>(3)?
+                        .and_then(|miner| miner.parse().ok()),
+                    fee_lamports: row.get::<_, i64>(4)? as u64,
+                    priority_fee_lamports: row.get::<_, i64>(5)? as u64,
+                    claimed: row.get::<_, Option<i64>>(6)?.map(|amount| amount as u64),
+                })
+            })
+            .map_err(to_io)?;
+        rows.collect::<rusqlite::Result<_>>().map_err(to_io)
+    }
+
+    /// Records are best effort, a full disk must not stop mining.
+    fn insert(&self, sql: &str, params: impl rusqlite::Params) {
+        if let Err(err) = self.connection.lock().unwrap().execute(sql, params) {
+            warn!(error = %err, "Failed to write to the ledger");
+        }
+    }
+}
+
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs() as i64)
+}
+
+fn to_io(err: rusqlite::Error) -> io::Error {
+    io::Error::new(ErrorKind::Other, format!("ledger: {}", err))
+}
@@ -0,0 +1,60 @@
+//! Mining client for the Ore program.
+//!
+//! ```no_run
+//! use std::sync::Arc;
+//!
+//! use ore_cli::{affinity::WorkerConfig, Miner, Ore, Pipeline, PipelineConfig, RpcPool};
+//! use solana_sdk::signature::Keypair;
+//!
+//! # async fn run() -> ore_cli::errors::Result<()> {
+//! let ore = Ore {
+//!     owner: Box::new(Keypair::new()),
+//!     rpc_pool: RpcPool::new(vec!["https://api.mainnet-beta.solana.com".into()]),
+//!     miners: vec![Miner::new(Keypair::new())],
+//!     workers: WorkerConfig::default(),
+//! };
+//! let pipeline = Pipeline::new(Arc::new(ore), PipelineConfig::default());
+//! pipeline.start().await?;
+//! // ...
+//! pipeline.stop().await?;
+//! # Ok(())
+//! # }
+//! ```
+
+#[cfg(feature = "admin")]
+pub mod admin;
+pub mod affinity;
+pub mod balance;
+pub mod config;
+pub mod dynamic_fee;
+pub mod errors;
+pub mod events;
+pub mod factory;
+pub mod fleet;
+pub mod gpu_batch;
+mod http;
+pub mod jito;
+pub mod keystore;
+pub mod ledger;
+pub mod logging;
+pub mod metrics;
+pub mod mine;
+#[cfg(feature = "gpu")]
+pub mod mine_gpu;
+pub mod output;
+pub mod pending;
+pub mod pipeline;
+pub mod profitability;
+pub mod remote_signer;
+pub mod report;
+pub mod rpc;
+pub mod scheduler;
+pub mod transaction;
+pub mod tui;
+pub mod verify;
+
+pub use factory::Ore;
+pub use mine::{Miner, SignedWork, Work};
+pub use pipeline::{Pipeline, PipelineConfig};
+pub use rpc::RpcPool;
+pub use transaction::Transaction;
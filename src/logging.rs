@@ -0,0 +1,157 @@
+//! Log output through `tracing`. Hashing and submission run inside a span carrying the miner's
+//! pubkey and challenge, and events add the nonce, signature or RPC endpoint as fields, so one
+//! miner's lines can be picked out of a fleet's interleaved output.
+
+use std::{
+    collections::VecDeque,
+    io::{self, ErrorKind, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use tracing_appender::{non_blocking::WorkerGuard, rolling};
+use tracing_subscriber::{fmt::writer::BoxMakeWriter, EnvFilter};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    #[default]
+    Human,
+    /// One JSON object per line, with the fields of every enclosing span.
+    Json,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogRotation {
+    #[default]
+    Never,
+    Hourly,
+    Daily,
+}
+
+#[derive(Clone, Debug)]
+pub struct LogConfig {
+    pub format: LogFormat,
+    /// Levels in `RUST_LOG` syntax, e.g. `info,ore_cli::transaction=debug`.
+    pub filter: String,
+    /// Log to this file instead of stderr. Rotated files get a date suffix.
+    pub file: Option<PathBuf>,
+    pub rotation: LogRotation,
+    /// Keep logs in memory instead of writing them to stderr. Ignored with a `file`.
+    pub buffer: Option<LogBuffer>,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        LogConfig {
+            format: LogFormat::Human,
+            filter: "info".into(),
+            file: None,
+            rotation: LogRotation::Never,
+            buffer: None,
+        }
+    }
+}
+
+/// Installs the global subscriber. The returned guard flushes the log file and must be held
+/// until exit.
+pub fn init(config: &LogConfig) -> io::Result<Option<WorkerGuard>> {
+    let filter = EnvFilter::try_new(&config.filter)
+        .map_err(|err| io::Error::new(ErrorKind::InvalidInput, format!("log level: {}", err)))?;
+
+    let (writer, guard) = match (&config.file, &config.buffer) {
+        (Some(path), _) => {
+            let (writer, guard) =
+                tracing_appender::non_blocking(file_appender(path, config.rotation)?);
+            (BoxMakeWriter::new(writer), Some(guard))
+        }
+        (None, Some(buffer)) => {
+            let buffer = buffer.clone();
+            (BoxMakeWriter::new(move || buffer.clone()), None)
+        }
+        (None, None) => (BoxMakeWriter::new(io::stderr), None),
+    };
+
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(writer)
+        .with_ansi(config.file.is_none() && config.buffer.is_none());
+    match config.format {
+        LogFormat::Human => builder.try_init(),
+        LogFormat::Json => builder.json().try_init(),
+    }
+    .map_err(|err| io::Error::new(ErrorKind::Other, err))?;
+
+    Ok(guard)
+}
+
+fn file_appender(path: &Path, rotation: LogRotation) -> io::Result<rolling::RollingFileAppender> {
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(ErrorKind::InvalidInput, "log file has no file name"))?;
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    std::fs::create_dir_all(dir)?;
+    Ok(match rotation {
+        LogRotation::Never => rolling::never(dir, file_name),
+        LogRotation::Hourly => rolling::hourly(dir, file_name),
+        LogRotation::Daily => rolling::daily(dir, file_name),
+    })
+}
+
+/// The last lines of log output, kept for the dashboard to show since it owns the terminal.
+#[derive(Clone, Debug)]
+pub struct LogBuffer {
+    lines: Arc<Mutex<VecDeque<String>>>,
+    capacity: usize,
+    released: Arc<AtomicBool>,
+}
+
+impl LogBuffer {
+    pub fn new(capacity: usize) -> Self {
+        LogBuffer {
+            lines: Arc::new(Mutex::new(VecDeque::with_capacity(capacity))),
+            capacity: capacity.max(1),
+            released: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Sends every later line to stderr, once the dashboard has given the terminal back.
+    pub fn release(&self) {
+        self.released.store(true, Ordering::Relaxed);
+    }
+
+    /// Buffered lines, oldest first.
+    pub fn lines(&self) -> Vec<String> {
+        self.lines.lock().unwrap().iter().cloned().collect()
+    }
+}
+
+impl Write for LogBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.released.load(Ordering::Relaxed) {
+            return io::stderr().write(buf);
+        }
+        let text = String::from_utf8_lossy(buf);
+        let mut lines = self.lines.lock().unwrap();
+        for line in text.lines().filter(|line| !line.is_empty()) {
+            if lines.len() == self.capacity {
+                lines.pop_front();
+            }
+            lines.push_back(line.to_string());
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -1,62 +1,1402 @@
+use std::{
+    net::{SocketAddr, TcpListener},
+    path::{Path, PathBuf},
+    sync::{Arc, OnceLock, Weak},
+    thread,
+};
 
-mod mine;
-mod factory;
-mod pipeline;
-mod rpc;
-mod transaction;
-mod errors;
-mod mine_gpu;
+use chrono::{Days, NaiveDate, NaiveTime, Utc};
+use clap::{command, Args as ClapArgs, Parser, Subcommand};
+#[cfg(feature = "admin")]
+use ore_cli::{
+    admin::Difficulty,
+    output::{AdminAction, AdminOutput},
+};
+use ore_cli::{
+    affinity::{BackendKind, CoreList, WorkerConfig},
+    balance::BalanceConfig,
+    config::{default_config_path, expand_home, ConfigFile, Settings},
+    errors::{CliError, Error, Result},
+    events::{EventBus, EventKind, RateLimit},
+    fleet::{new_keypairs, DeriveRange},
+    jito::{JitoClient, DEFAULT_TIP},
+    keystore::{read_plain_keypair, write_plain_keypair, Keystore, PassphraseSource, Unlocker},
+    ledger::{Grouping, Ledger},
+    logging::{self, LogBuffer, LogFormat, LogRotation},
+    metrics::MetricsServer,
+    output::{
+        self, Amount, BalanceOutput, BussesOutput, ClaimsOutput, CloseOutput, KeyAction,
+        KeyOutput, KeysOutput, MineStatusOutput, MinersOutput, NewMinersOutput, OutputFormat,
+        RegisterOutput, Render, StatsOutput, TransfersOutput, TreasuryOutput,
+    },
+    remote_signer::{
+        RemoteSigner, SignerEndpoint, SignerServer, DEFAULT_MAX_CU_LIMIT, DEFAULT_MAX_CU_PRICE,
+    },
+    report::{write_csv, write_json, ReportFormat},
+    tui::Dashboard,
+    pipeline::PipelineConfig,
+    profitability::GuardAction,
+    transaction::SendPolicy,
+    Miner, Ore, Pipeline, RpcPool,
+};
+use solana_sdk::{
+    native_token::sol_to_lamports,
+    pubkey::Pubkey,
+    signer::{null_signer::NullSigner, Signer},
+};
+use tokio::signal::unix::{signal, SignalKind};
+use tracing::{error, info};
 
-use std::sync::Arc;
+/// Log lines the dashboard keeps.
+const DASHBOARD_LOG_LINES: usize = 500;
 
-use clap::{command, Parser, Subcommand};
-use mine::Miner;
-use solana_sdk::signature::{read_keypair_file, Keypair};
-
-use crate::{factory::Ore, rpc::RpcPool};
+/// The event bus once started, which `fail` closes so queued events are delivered before exiting.
+static EVENTS: OnceLock<Weak<EventBus>> = OnceLock::new();
 
 #[derive(Parser, Debug)]
 #[command(about, version)]
 struct Args {
     #[arg(
         long,
-        help = "Network address of your RPC provider",
+        value_name = "PATH",
+        env = "ORE_CONFIG",
+        help = "Config file [default: ~/.config/ore-cli/config.toml]"
     )]
-    rpc: String,
+    config: Option<PathBuf>,
 
     #[arg(
         long,
-        help = "Filepath to keypair to use"
+        env = "ORE_PROFILE",
+        help = "Config file profile to use, e.g. mainnet or devnet"
+    )]
+    profile: Option<String>,
+
+    #[arg(
+        long,
+        env = "DEFAULT_RPC",
+        value_delimiter = ',',
+        help = "Network address of your RPC provider [default: the Solana CLI config's]",
     )]
-    owner: String,
+    rpc: Vec<String>,
+
+    #[arg(
+        long,
+        env = "WALLET_KEYPAIR",
+        help = "Filepath to keypair to use, or a signer daemon at unix:<path> or http://<host>:<port> [default: the Solana CLI config's]"
+    )]
+    owner: Option<String>,
+
+    #[arg(
+        long,
+        env = "ORE_SIGNER_TOKEN",
+        hide_env_values = true,
+        help = "Bearer token between the signer daemon and miners over HTTP"
+    )]
+    signer_token: Option<String>,
 
     #[arg(
         long,
         help = "Filepath to keypair to use"
     )]
-    miners: Vec<String>,
+    miners: Vec<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "DIR",
+        env = "KEYPAIR_ROOT",
+        help = "Directory of miner keypairs named <pubkey>.json, loaded in addition to --miners"
+    )]
+    miners_dir: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "File holding a BIP39 mnemonic or hex seed to derive miner keypairs from"
+    )]
+    miners_seed: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "RANGE",
+        help = "Derivation indexes of the miners, e.g. 0..64 for m/44'/501'/0'/0' to m/44'/501'/63'/0'"
+    )]
+    miners_derive: Option<DeriveRange>,
+
+    #[arg(
+        long,
+        env = "ORE_SEED_PASSPHRASE",
+        hide_env_values = true,
+        default_value = "",
+        hide_default_value = true,
+        help = "BIP39 passphrase of --miners-seed"
+    )]
+    seed_passphrase: String,
+
+    #[arg(
+        long,
+        value_name = "VAR",
+        conflicts_with = "passphrase_fd",
+        help = "Environment variable holding the keystore passphrase, instead of a prompt"
+    )]
+    passphrase_env: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "FD",
+        help = "File descriptor to read the keystore passphrase from, instead of a prompt"
+    )]
+    passphrase_fd: Option<i32>,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        global = true,
+        env = "ORE_LEDGER_FILE",
+        help = "SQLite database recording work, transactions, proofs, claims and transfers [default: ~/.config/ore-cli/ledger.sqlite3]"
+    )]
+    ledger_file: Option<PathBuf>,
+
+    #[arg(
+        long = "webhook",
+        value_name = "URL",
+        global = true,
+        env = "ORE_WEBHOOKS",
+        value_delimiter = ',',
+        help = "Url every event is POSTed to as JSON"
+    )]
+    webhooks: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "COMMAND",
+        global = true,
+        env = "ORE_EVENT_SCRIPT",
+        help = "Shell command run on every event, with ORE_EVENT and ORE_EVENT_JSON set"
+    )]
+    event_script: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        global = true,
+        env = "ORE_EVENT_LOG",
+        help = "File every event is appended to as a JSON line"
+    )]
+    event_log: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_enum,
+        global = true,
+        value_delimiter = ',',
+        help = "Event kinds delivered [default: all]"
+    )]
+    events: Vec<EventKind>,
+
+    #[arg(
+        long = "event-rate-limit",
+        value_name = "KIND=SECS",
+        global = true,
+        help = "Least seconds between two events of a kind, e.g. submission-failed=300"
+    )]
+    event_rate_limits: Vec<RateLimit>,
+
+    #[arg(
+        long,
+        value_name = "N",
+        global = true,
+        help = "Failed submissions of a miner in a row that raise a submission-failed event [default: 3]"
+    )]
+    submission_failures: Option<usize>,
+
+    #[arg(
+        long = "output",
+        value_name = "FORMAT",
+        value_enum,
+        global = true,
+        env = "ORE_OUTPUT",
+        default_value_t = OutputFormat::Table,
+        help = "Format of command results, json keeps raw integer amounts next to UI amounts"
+    )]
+    output_format: OutputFormat,
+
+    #[arg(
+        long,
+        value_enum,
+        global = true,
+        env = "ORE_LOG_FORMAT",
+        help = "Log output format [default: human]"
+    )]
+    log_format: Option<LogFormat>,
+
+    #[arg(
+        long,
+        value_name = "FILTER",
+        global = true,
+        env = "RUST_LOG",
+        help = "Log levels, per module if needed, e.g. info,ore_cli::transaction=debug [default: info]"
+    )]
+    log_level: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        global = true,
+        env = "ORE_LOG_FILE",
+        help = "Write logs to this file instead of stderr"
+    )]
+    log_file: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_enum,
+        global = true,
+        help = "How often --log-file is rotated [default: never]"
+    )]
+    log_rotation: Option<LogRotation>,
 
     #[command(subcommand)]
     command: Commands,
 }
 
+impl Args {
+    /// The command line and environment layer of the settings.
+    fn settings(&self) -> Settings {
+        let mut settings = Settings {
+            rpc: (!self.rpc.is_empty()).then(|| self.rpc.clone()),
+            owner: self.owner.clone(),
+            miners: (!self.miners.is_empty()).then(|| self.miners.clone()),
+            miners_dir: self.miners_dir.clone(),
+            miners_seed: self.miners_seed.clone(),
+            miners_derive: self.miners_derive.clone(),
+            ledger_file: self.ledger_file.clone(),
+            webhooks: (!self.webhooks.is_empty()).then(|| self.webhooks.clone()),
+            event_script: self.event_script.clone(),
+            event_log: self.event_log.clone(),
+            events: (!self.events.is_empty()).then(|| self.events.clone()),
+            event_rate_limits: (!self.event_rate_limits.is_empty()).then(|| {
+                self.event_rate_limits
+                    .iter()
+                    .map(|RateLimit(kind, secs)| (*kind, *secs))
+                    .collect()
+            }),
+            submission_failures: self.submission_failures,
+            log_format: self.log_format,
+            log_level: self.log_level.clone(),
+            log_file: self.log_file.clone(),
+            log_rotation: self.log_rotation,
+            ..Default::default()
+        };
+        match &self.command {
+            Commands::Mine(MineCli {
+                command: None,
+                args: mine_args,
+            })
+            | Commands::Config(ConfigArgs {
+                command: ConfigCommand::Show(mine_args),
+            }) => mine_args.apply(&mut settings),
+            Commands::Register(send_args)
+            | Commands::Miners(MinersArgs {
+                command: MinersCommand::Register(send_args),
+            })
+            | Commands::Sweep(send_args) => send_args.apply(&mut settings),
+            Commands::Fund(fund_args) => fund_args.send.apply(&mut settings),
+            Commands::Claim(claim_args) => claim_args.send.apply(&mut settings),
+            #[cfg(feature = "admin")]
+            Commands::Initialize(admin_args) => admin_args.send.apply(&mut settings),
+            #[cfg(feature = "admin")]
+            Commands::UpdateAdmin(update_args) => update_args.admin.send.apply(&mut settings),
+            #[cfg(feature = "admin")]
+            Commands::UpdateDifficulty(update_args) => {
+                update_args.admin.send.apply(&mut settings)
+            }
+            _ => {}
+        }
+        settings
+    }
+
+    /// Reads `--config`, or the default config file if there is one.
+    fn config_file(&self) -> std::io::Result<Option<(PathBuf, ConfigFile)>> {
+        let path = match &self.config {
+            Some(path) => path.clone(),
+            None => match default_config_path().filter(|path| path.exists()) {
+                Some(path) => path,
+                None => return Ok(None),
+            },
+        };
+        let file = ConfigFile::load(&path)?;
+        Ok(Some((path, file)))
+    }
+
+    fn passphrase_source(&self) -> PassphraseSource {
+        match (&self.passphrase_env, self.passphrase_fd) {
+            (Some(name), _) => PassphraseSource::Env(name.clone()),
+            (None, Some(fd)) => PassphraseSource::Fd(fd),
+            (None, None) => PassphraseSource::Prompt,
+        }
+    }
+}
+
 #[derive(Subcommand, Debug)]
 enum Commands {
     #[command(about = "Mine Ore using local compute")]
-    Mine,
+    Mine(MineCli),
+
+    #[command(about = "Show the owner's SOL and ORE balance")]
+    Balance,
+
+    #[command(about = "Show each miner's claimable and total ORE rewards")]
+    Rewards,
+
+    #[command(about = "Show the treasury account")]
+    Treasury,
 
+    #[command(about = "Show the ORE left in each bus this epoch")]
+    Busses,
+
+    #[command(about = "Claim miners' rewards into the owner's token account")]
+    Claim(ClaimArgs),
+
+    #[command(about = "Manage the miner keypairs in --miners-dir")]
+    Miners(MinersArgs),
+
+    #[command(about = "Register a proof for every miner that has none, fees paid by the owner")]
+    Register(SendArgs),
+
+    #[command(about = "Close every miner's proof and reclaim its rent, where the program supports it")]
+    Close,
+
+    #[command(about = "Top every miner up to a target SOL balance from the owner")]
+    Fund(FundArgs),
+
+    #[command(about = "Move every miner's SOL balance back to the owner")]
+    Sweep(SendArgs),
+
+    #[command(about = "Summarise ORE earned against SOL spent on fees, per miner and per day")]
+    Stats,
+
+    #[command(about = "Export every transaction's fee and ORE earned or claimed, for accounting")]
+    Report(ReportArgs),
+
+    #[command(about = "Encrypt, decrypt and check passphrase protected keystores")]
+    Keys(KeysArgs),
+
+    #[command(about = "Run a signer daemon that signs as the --owner keypair for remote miners")]
+    Signer(SignerArgs),
+
+    #[command(about = "Inspect the layered configuration")]
+    Config(ConfigArgs),
+
+    #[cfg(feature = "admin")]
+    #[command(about = "Initialize the Ore program with the owner as admin")]
+    Initialize(AdminArgs),
+
+    #[cfg(feature = "admin")]
+    #[command(about = "Hand the treasury admin authority to another key")]
+    UpdateAdmin(UpdateAdminArgs),
+
+    #[cfg(feature = "admin")]
+    #[command(about = "Set the hash difficulty")]
+    UpdateDifficulty(UpdateDifficultyArgs),
+}
+
+impl Commands {
+    /// Commands that only read accounts, so run without the owner's key.
+    fn is_read_only(&self) -> bool {
+        matches!(
+            self,
+            Commands::Rewards
+                | Commands::Treasury
+                | Commands::Busses
+                | Commands::Miners(MinersArgs {
+                    command: MinersCommand::List
+                })
+                | Commands::Mine(MineCli {
+                    command: Some(MineCommand::Status),
+                    ..
+                })
+        )
+    }
+}
+
+#[cfg(feature = "admin")]
+#[derive(ClapArgs, Debug)]
+struct AdminArgs {
+    #[arg(
+        long,
+        help = "Skip the confirmation prompt"
+    )]
+    yes: bool,
+
+    #[command(flatten)]
+    send: SendArgs,
+}
+
+#[cfg(feature = "admin")]
+#[derive(ClapArgs, Debug)]
+struct UpdateAdminArgs {
+    #[arg(help = "Pubkey of the new admin")]
+    new_admin: Pubkey,
+
+    #[command(flatten)]
+    admin: AdminArgs,
+}
+
+#[cfg(feature = "admin")]
+#[derive(ClapArgs, Debug)]
+struct UpdateDifficultyArgs {
+    #[arg(help = "Leading zero bits a hash needs, e.g. 8, or the 32 byte target in hex")]
+    difficulty: Difficulty,
+
+    #[command(flatten)]
+    admin: AdminArgs,
+}
+
+#[derive(ClapArgs, Debug)]
+struct SignerArgs {
+    #[arg(
+        long,
+        value_name = "ENDPOINT",
+        help = "Address to listen on, unix:<path> or http://<host>:<port>"
+    )]
+    listen: SignerEndpoint,
+
+    #[arg(
+        long,
+        value_name = "PROGRAM",
+        help = "Program the daemon may sign for besides Ore, compute budget and system"
+    )]
+    allow_program: Vec<Pubkey>,
+
+    #[arg(
+        long,
+        value_name = "PUBKEY",
+        help = "Wallet the daemon may transfer the owner's SOL to, e.g. each miner it funds"
+    )]
+    allow_recipient: Vec<Pubkey>,
+
+    #[arg(
+        long,
+        value_name = "MICROLAMPORTS",
+        default_value_t = DEFAULT_MAX_CU_PRICE,
+        help = "Highest compute unit price the daemon signs"
+    )]
+    max_cu_price: u64,
+
+    #[arg(
+        long,
+        value_name = "UNITS",
+        default_value_t = DEFAULT_MAX_CU_LIMIT,
+        help = "Largest compute budget the daemon signs"
+    )]
+    max_cu_limit: u32,
+
+    #[arg(long, help = "Also sign the Ore admin instructions, initialize and update-*")]
+    allow_admin: bool,
+}
+
+#[derive(ClapArgs, Debug)]
+struct KeysArgs {
+    #[command(subcommand)]
+    command: KeysCommand,
 }
-#[tokio::main]
-async fn main() {
+
+#[derive(Subcommand, Debug)]
+enum KeysCommand {
+    #[command(about = "Encrypt a plaintext keypair file into a keystore")]
+    Import {
+        #[arg(help = "Plaintext keypair file")]
+        input: PathBuf,
+
+        #[arg(help = "Keystore file to write")]
+        output: PathBuf,
+    },
+
+    #[command(about = "Decrypt a keystore into a plaintext keypair file")]
+    Export {
+        #[arg(help = "Keystore file")]
+        keystore: PathBuf,
+
+        #[arg(help = "Plaintext keypair file to write")]
+        output: PathBuf,
+    },
+
+    #[command(about = "Check the passphrase of keystores and print their pubkeys")]
+    Unlock {
+        #[arg(required = true, help = "Keystore files")]
+        keystores: Vec<PathBuf>,
+    },
+}
+
+#[derive(ClapArgs, Debug)]
+struct ReportArgs {
+    #[arg(
+        long,
+        value_name = "DATE",
+        help = "First day of the report, e.g. 2024-05-01, in UTC"
+    )]
+    from: NaiveDate,
+
+    #[arg(
+        long,
+        value_name = "DATE",
+        help = "Last day of the report, included, in UTC [default: today]"
+    )]
+    to: Option<NaiveDate>,
+
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = ReportFormat::Csv,
+        help = "Output format"
+    )]
+    format: ReportFormat,
+}
+
+#[derive(ClapArgs, Debug)]
+struct ClaimArgs {
+    #[arg(
+        long,
+        value_name = "PUBKEY",
+        help = "Miner to claim from, every miner if not given"
+    )]
+    miner: Vec<Pubkey>,
+
+    #[arg(
+        long,
+        value_name = "ORE",
+        help = "ORE to claim from each miner, everything claimable if not given"
+    )]
+    amount: Option<f64>,
+
+    #[command(flatten)]
+    send: SendArgs,
+}
+
+#[derive(ClapArgs, Debug)]
+struct FundArgs {
+    #[arg(
+        long,
+        value_name = "SOL",
+        help = "Balance every miner is topped up to"
+    )]
+    target: f64,
+
+    #[command(flatten)]
+    send: SendArgs,
+}
+
+#[derive(ClapArgs, Debug)]
+struct MinersArgs {
+    #[command(subcommand)]
+    command: MinersCommand,
+}
+
+#[derive(Subcommand, Debug)]
+enum MinersCommand {
+    #[command(about = "Generate new miner keypairs in --miners-dir")]
+    New {
+        #[arg(help = "Number of keypairs to generate")]
+        count: usize,
+    },
+
+    #[command(about = "Show each miner's SOL balance, proof and claimable ORE")]
+    List,
+
+    #[command(about = "Same as ore register")]
+    Register(SendArgs),
+}
+
+#[derive(ClapArgs, Debug)]
+struct SendArgs {
+    #[arg(
+        long,
+        value_name = "MICROLAMPORTS",
+        env = "DEFAULT_PRIORITY_FEE",
+        help = "Priority fee per compute unit"
+    )]
+    priority_fee: Option<u64>,
+
+    #[arg(
+        long,
+        alias = "submit-retries",
+        env = "SUBMIT_RETRIES",
+        help = "Number of times to resend a transaction before giving up [default: 10]"
+    )]
+    retries: Option<usize>,
+
+    #[arg(
+        long,
+        env = "CONFIRM_WAIT_MS",
+        help = "Milliseconds between signature status polls [default: 1000]"
+    )]
+    confirm_wait_ms: Option<u64>,
+
+    #[arg(
+        long,
+        value_name = "BOOL",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true",
+        help = "Skip preflight simulation, --skip-preflight=false turns it back on"
+    )]
+    skip_preflight: Option<bool>,
+
+    #[arg(
+        long,
+        alias = "dynamic-priority-fee",
+        value_name = "BOOL",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true",
+        help = "Follow the recent priority fees of the busses instead of --priority-fee"
+    )]
+    dynamic_fee: Option<bool>,
+
+    #[arg(
+        long,
+        alias = "dynamic-priority-fee-min",
+        value_name = "MICROLAMPORTS",
+        env = "DYNAMIC_FEE_MIN",
+        help = "Lowest dynamic priority fee"
+    )]
+    dynamic_fee_min: Option<u64>,
+
+    #[arg(
+        long,
+        alias = "dynamic-priority-fee-max",
+        value_name = "MICROLAMPORTS",
+        env = "DYNAMIC_FEE_MAX",
+        help = "Highest dynamic priority fee"
+    )]
+    dynamic_fee_max: Option<u64>,
+
+    #[arg(
+        long,
+        alias = "dynamic-priority-fee-percentile",
+        value_name = "PERCENTILE",
+        env = "DYNAMIC_FEE_PERCENTILE",
+        value_parser = clap::value_parser!(u8).range(0..=100),
+        help = "Percentile of the recent priority fees the dynamic fee starts from [default: 70]"
+    )]
+    dynamic_fee_percentile: Option<u8>,
+
+    #[arg(
+        long,
+        alias = "dynamic-priority-fee-uplift",
+        value_name = "MICROLAMPORTS",
+        env = "DYNAMIC_FEE_UPLIFT",
+        help = "Added to the percentile of the recent priority fees [default: 0]"
+    )]
+    dynamic_fee_uplift: Option<u64>,
+
+    #[arg(
+        long,
+        value_name = "URL",
+        env = "ORE_JITO_URL",
+        help = "Send transactions as Jito bundles through this block engine"
+    )]
+    jito_url: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "LAMPORTS",
+        env = "ORE_JITO_TIP",
+        help = "Tip paid by the fee payer with every bundle [default: 10000]"
+    )]
+    jito_tip: Option<u64>,
+}
+
+impl SendArgs {
+    fn apply(&self, settings: &mut Settings) {
+        settings.priority_fee = self.priority_fee;
+        settings.retries = self.retries;
+        settings.confirm_wait_ms = self.confirm_wait_ms;
+        settings.skip_preflight = self.skip_preflight;
+        settings.dynamic_fee = self.dynamic_fee;
+        settings.dynamic_fee_min = self.dynamic_fee_min;
+        settings.dynamic_fee_max = self.dynamic_fee_max;
+        settings.dynamic_fee_percentile = self.dynamic_fee_percentile;
+        settings.dynamic_fee_uplift = self.dynamic_fee_uplift;
+        settings.jito_url = self.jito_url.clone();
+        settings.jito_tip = self.jito_tip;
+    }
+}
+
+#[derive(ClapArgs, Debug)]
+#[command(args_conflicts_with_subcommands = true)]
+struct MineCli {
+    #[command(subcommand)]
+    command: Option<MineCommand>,
+
+    #[command(flatten)]
+    args: MineArgs,
+}
+
+#[derive(Subcommand, Debug)]
+enum MineCommand {
+    #[command(about = "Show the epoch, bus rewards and every miner's proof without mining")]
+    Status,
+}
+
+#[derive(ClapArgs, Debug)]
+struct MineArgs {
+    #[arg(long, help = "Show a live dashboard of the miners instead of log lines")]
+    tui: bool,
+
+    #[arg(
+        long,
+        env = "MINE_THREADS",
+        help = "Number of hashing threads, defaults to one per hashing core"
+    )]
+    threads: Option<usize>,
+
+    #[arg(
+        long,
+        value_name = "CORES",
+        help = "CPU cores to pin hashing threads to, e.g. 0-6,8"
+    )]
+    cores: Option<CoreList>,
+
+    #[arg(
+        long,
+        value_name = "CORE",
+        help = "CPU core reserved for the RPC runtime, excluded from hashing"
+    )]
+    rpc_core: Option<usize>,
+
+    #[arg(
+        long,
+        help = "Number of miners hashed at the same time, the rest are queued and each sent to the bus with the most left. On the gpu backend this is the number of jobs per dispatch [default: 1]"
+    )]
+    slots: Option<usize>,
+
+    #[arg(
+        long,
+        value_enum,
+        help = "Hashing backend, gpu falls back to cpu if no adapter is available [default: cpu]"
+    )]
+    backend: Option<BackendKind>,
+
+    #[command(flatten)]
+    send: SendArgs,
+
+    #[arg(
+        long,
+        help = "Seconds to wait for in-flight confirmations on shutdown [default: 30]"
+    )]
+    drain_timeout_secs: Option<u64>,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "File unconfirmed work is saved to on shutdown and resumed from on start [default: ~/.config/ore-cli/pending.json]"
+    )]
+    pending_file: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "ADDR",
+        env = "ORE_METRICS_ADDR",
+        help = "Serve Prometheus metrics on http://ADDR/metrics, e.g. 127.0.0.1:9100"
+    )]
+    metrics_addr: Option<SocketAddr>,
+
+    #[arg(
+        long,
+        value_name = "SOL",
+        help = "Price of one ORE in SOL, turns on the profitability guard"
+    )]
+    ore_price: Option<f64>,
+
+    #[arg(
+        long,
+        value_name = "URL",
+        env = "ORE_PRICE_URL",
+        conflicts_with = "ore_price",
+        help = "JSON endpoint of the ORE price in SOL, turns on the profitability guard"
+    )]
+    price_url: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "POINTER",
+        help = "JSON pointer to the price in the --price-url response, e.g. /data/ORE/price [default: the whole response]"
+    )]
+    price_pointer: Option<String>,
+
+    #[arg(
+        long,
+        help = "Seconds between --price-url fetches [default: 60]"
+    )]
+    price_refresh_secs: Option<u64>,
+
+    #[arg(
+        long,
+        value_name = "RATIO",
+        allow_negative_numbers = true,
+        help = "Least margin of ORE earned over fees spent to mine at, 0.25 for a quarter more than spent [default: 0]"
+    )]
+    min_margin: Option<f64>,
+
+    #[arg(
+        long,
+        value_enum,
+        help = "What to do while mining is below --min-margin [default: pause]"
+    )]
+    unprofitable: Option<GuardAction>,
+
+    #[arg(
+        long,
+        help = "Seconds every submission waits while mining is below --min-margin with --unprofitable slow [default: 60]"
+    )]
+    slow_down_secs: Option<u64>,
+
+    #[arg(
+        long,
+        value_name = "SOL",
+        help = "Fee payer balance mining stops at [default: 0.001]"
+    )]
+    balance_floor: Option<f64>,
+
+    #[arg(
+        long,
+        value_name = "SOL",
+        help = "Fee payer balance under which alerts and top-ups start [default: 0.01]"
+    )]
+    balance_warning: Option<f64>,
+
+    #[arg(
+        long,
+        value_name = "COMMAND",
+        env = "ORE_BALANCE_ALERT",
+        help = "Shell command run on every balance alert, with ORE_ALERT, ORE_WALLET and ORE_BALANCE_LAMPORTS set"
+    )]
+    balance_alert: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "Keypair or keystore that tops the fee payer up once it is under --balance-warning"
+    )]
+    top_up_keypair: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "SOL",
+        help = "Balance the fee payer is topped up to [default: 0.1]"
+    )]
+    top_up_target: Option<f64>,
+}
+
+impl MineArgs {
+    fn apply(&self, settings: &mut Settings) {
+        self.send.apply(settings);
+        settings.threads = self.threads;
+        settings.cores = self.cores.clone();
+        settings.rpc_core = self.rpc_core;
+        settings.slots = self.slots;
+        settings.backend = self.backend;
+        settings.drain_timeout_secs = self.drain_timeout_secs;
+        settings.pending_file = self.pending_file.clone();
+        settings.metrics_addr = self.metrics_addr;
+        settings.ore_price = self.ore_price;
+        settings.price_url = self.price_url.clone();
+        settings.price_pointer = self.price_pointer.clone();
+        settings.price_refresh_secs = self.price_refresh_secs;
+        settings.min_margin = self.min_margin;
+        settings.unprofitable = self.unprofitable;
+        settings.slow_down_secs = self.slow_down_secs;
+        settings.balance_floor = self.balance_floor;
+        settings.balance_warning = self.balance_warning;
+        settings.balance_alert = self.balance_alert.clone();
+        settings.top_up_keypair = self.top_up_keypair.clone();
+        settings.top_up_target = self.top_up_target;
+    }
+}
+
+#[derive(ClapArgs, Debug)]
+struct ConfigArgs {
+    #[command(subcommand)]
+    command: ConfigCommand,
+}
+
+#[derive(Subcommand, Debug)]
+enum ConfigCommand {
+    #[command(about = "Print the settings `ore mine` would resolve from flags, environment, config file and defaults")]
+    Show(MineArgs),
+}
+
+fn main() {
     let args = Args::parse();
+    let format = args.output_format;
+    let config = check(args.config_file(), format);
+    let profile = args
+        .profile
+        .clone()
+        .or_else(|| config.as_ref().and_then(|(_, file)| file.profile.clone()));
+    let settings = Settings::resolve(
+        args.settings(),
+        config.as_ref().map(|(_, file)| file),
+        profile.as_deref(),
+    );
+    let settings = check(settings, format);
+
+    if let Commands::Config(ConfigArgs {
+        command: ConfigCommand::Show(_),
+    }) = &args.command
+    {
+        let path = config.as_ref().map(|(path, _)| path.display().to_string());
+        println!("# config file: {}", path.as_deref().unwrap_or("none"));
+        println!("# profile: {}", profile.as_deref().unwrap_or("none"));
+        print!("{}", toml::to_string(&settings).unwrap());
+        return;
+    }
+
+    // The dashboard owns the terminal, so unless logs go to a file it shows them itself.
+    let mut log_config = settings.log_config();
+    let logs = match &args.command {
+        Commands::Mine(MineCli {
+            command: None,
+            args,
+        }) if args.tui && log_config.file.is_none() => {
+            Some(LogBuffer::new(DASHBOARD_LOG_LINES))
+        }
+        _ => None,
+    };
+    log_config.buffer = logs.clone();
+    let _log_guard = check(logging::init(&log_config), format);
+
+    let workers = match &args.command {
+        Commands::Mine(MineCli { command: None, .. }) => settings.worker_config(),
+        _ => WorkerConfig::default(),
+    };
+    check(workers.hasher_cores().map_err(config_error), format);
+
+    workers.build_runtime().block_on(run(args, settings, workers, logs));
+}
+
+async fn run(args: Args, settings: Settings, workers: WorkerConfig, logs: Option<LogBuffer>) {
+    let format = args.output_format;
+    if let Commands::Miners(MinersArgs {
+        command: MinersCommand::New { count },
+    }) = &args.command
+    {
+        let dir = settings
+            .miners_dir
+            .as_ref()
+            .ok_or_else(|| config_error("--miners-dir is required to create miners"));
+        let dir = expand_home(check(dir, format).as_path());
+        let miners = new_keypairs(&dir, *count).map(|pubkeys| NewMinersOutput {
+            dir: dir.display().to_string(),
+            miners: pubkeys.iter().map(ToString::to_string).collect(),
+        });
+        emit(miners.map_err(Error::from), format);
+        return;
+    }
+
+    if let Commands::Stats = &args.command {
+        let stats = || -> Result<StatsOutput> {
+            let path = settings
+                .ledger_path()
+                .ok_or_else(|| config_error("No ledger file configured"))?;
+            let ledger = Ledger::open(&path)?;
+            Ok(StatsOutput::new(
+                ledger.summary(Grouping::Miner)?,
+                ledger.summary(Grouping::Day)?,
+            ))
+        };
+        emit(stats(), format);
+        return;
+    }
+
+    let unlocker = Unlocker::new(args.passphrase_source());
+    if let Commands::Keys(keys_args) = &args.command {
+        let keys = run_keys(&keys_args.command, &args.passphrase_source(), &unlocker);
+        emit(keys.map_err(Error::from), format);
+        return;
+    }
+
+    let owner_path = settings
+        .owner
+        .clone()
+        .ok_or_else(|| config_error("No owner keypair configured"));
+    if let Commands::Signer(signer_args) = &args.command {
+        let owner_path = check(owner_path, format);
+        let keypair = check(unlocker.read_keypair(&expand_home(Path::new(&owner_path))), format);
+        let mut server = SignerServer::new(keypair);
+        server.allowed_programs.extend(&signer_args.allow_program);
+        server.allowed_recipients.extend(&signer_args.allow_recipient);
+        server.max_cu_price = signer_args.max_cu_price;
+        server.max_cu_limit = signer_args.max_cu_limit;
+        server.allow_admin = signer_args.allow_admin;
+        server.token = args.signer_token.clone();
+        info!(signer = %server.keypair.pubkey(), endpoint = %signer_args.listen, "Signing");
+        check(server.serve(&signer_args.listen), format);
+        return;
+    }
+
+    if settings.miners_seed.is_some() != settings.miners_derive.is_some() {
+        fail(config_error("--miners-seed and --miners-derive must be set together"), format);
+    }
+    let owner: Box<dyn Signer + Send + Sync> = if args.command.is_read_only() {
+        Box::new(NullSigner::new(&Pubkey::default()))
+    } else {
+        let owner_path = check(owner_path, format);
+        if SignerEndpoint::is_endpoint(&owner_path) {
+            let endpoint = check(owner_path.parse().map_err(config_error), format);
+            Box::new(check(RemoteSigner::connect(endpoint, args.signer_token.clone()), format))
+        } else {
+            Box::new(check(unlocker.read_keypair(&expand_home(Path::new(&owner_path))), format))
+        }
+    };
+    let rpc = check(settings.rpc.clone().ok_or_else(|| config_error("No RPC configured")), format);
+    let rpc_pool = RpcPool::new(rpc);
+    let miners = settings
+        .key_sources(&args.seed_passphrase)
+        .iter()
+        .flat_map(|source| check(Miner::load(source, &unlocker), format))
+        .collect();
+    let funder = match (&args.command, &settings.top_up_keypair) {
+        (Commands::Mine(MineCli { command: None, .. }), Some(path)) => {
+            Some(Arc::new(check(unlocker.read_keypair(&expand_home(path)), format)))
+        }
+        _ => None,
+    };
+    drop(unlocker);
+
+    let ore = Ore { owner, rpc_pool, miners, workers };
+    let ledger = settings
+        .ledger_path()
+        .map(|path| Arc::new(check(Ledger::open(&path), format)));
+    let event_config = settings.event_config();
+    let events = event_config.has_sinks().then(|| {
+        let events = Arc::new(check(EventBus::start(event_config), format));
+        let _ = EVENTS.set(Arc::downgrade(&events));
+        events
+    });
+    let jito = match &settings.jito_url {
+        Some(url) if !args.command.is_read_only() => {
+            let tip = settings.jito_tip.unwrap_or(DEFAULT_TIP);
+            Some(Arc::new(check(JitoClient::new(url, tip).await, format)))
+        }
+        _ => None,
+    };
+    let policy = SendPolicy {
+        jito,
+        ledger,
+        events: events.clone(),
+        ..settings.send_policy()
+    };
+
+    match args.command {
+        Commands::Mine(MineCli {
+            command: Some(MineCommand::Status),
+            ..
+        }) => {
+            let status = async {
+                let window = ore.get_epoch_window().await?;
+                let accounts = ore.get_miner_accounts().await?;
+                Ok(MineStatusOutput::new(&window, &accounts))
+            };
+            emit(status.await, format);
+        }
+        Commands::Mine(MineCli {
+            command: None,
+            args: mine_args,
+        }) => {
+            let pipeline = Arc::new(Pipeline::new(
+                Arc::new(ore),
+                PipelineConfig {
+                    send: policy,
+                    balance: Some(BalanceConfig {
+                        funder,
+                        ..settings.balance_config()
+                    }),
+                    ..settings.pipeline_config()
+                },
+            ));
+            if let Some(addr) = settings.metrics_addr {
+                let listener = check(TcpListener::bind(addr), format);
+                let server = MetricsServer {
+                    metrics: pipeline.metrics().clone(),
+                    ore: pipeline.ore().clone(),
+                };
+                info!("Serving metrics on http://{}/metrics", addr);
+                thread::spawn(move || server.serve(listener));
+            }
+            if let Err(err) = pipeline.start().await {
+                exit_with(err, events.as_deref());
+            }
+            if mine_args.tui {
+                tokio::select! {
+                    result = Dashboard::new(pipeline.clone(), logs.clone()).run() => {
+                        check(result, format)
+                    }
+                    _ = shutdown_signal() => {}
+                }
+                if let Some(logs) = &logs {
+                    logs.release();
+                }
+                info!("Shutting down, waiting for in-flight submissions...");
+                if let Err(err) = pipeline.stop().await {
+                    exit_with(err, events.as_deref());
+                }
+                return;
+            }
+            tokio::select! {
+                result = pipeline.wait() => {
+                    if let Err(err) = result {
+                        exit_with(err, events.as_deref());
+                    }
+                }
+                _ = shutdown_signal() => {
+                    info!("Shutting down, waiting for in-flight submissions...");
+                    if let Err(err) = pipeline.stop().await {
+                        exit_with(err, events.as_deref());
+                    }
+                }
+            }
+        }
+        Commands::Balance => {
+            let balance = ore.get_owner_balance().await.map(|(lamports, ore_amount)| {
+                BalanceOutput {
+                    owner: ore.owner.pubkey().to_string(),
+                    token_account: ore.owner_token_account().to_string(),
+                    sol: Amount::sol(lamports),
+                    ore: Amount::ore(ore_amount),
+                }
+            });
+            emit(balance, format);
+        }
+        Commands::Register(_)
+        | Commands::Miners(MinersArgs {
+            command: MinersCommand::Register(_),
+        }) => {
+            let register = async {
+                let results = ore.register_miners(&policy).await?;
+                let accounts = ore.get_miner_accounts().await?;
+                Ok(RegisterOutput::new(results, &accounts))
+            };
+            emit(register.await, format);
+        }
+        Commands::Miners(miners_args) => match miners_args.command {
+            MinersCommand::New { .. } | MinersCommand::Register(_) => unreachable!(),
+            MinersCommand::List => emit(
+                ore.get_miner_accounts().await.map(|accounts| MinersOutput::new(&accounts)),
+                format,
+            ),
+        },
+        Commands::Rewards => emit(
+            ore.get_miner_accounts().await.map(|accounts| MinersOutput::new(&accounts)),
+            format,
+        ),
+        Commands::Treasury => emit(
+            ore.get_treasury().await.map(|treasury| TreasuryOutput::from(&treasury)),
+            format,
+        ),
+        Commands::Busses => emit(
+            ore.get_epoch_window().await.map(|window| BussesOutput::from(&window)),
+            format,
+        ),
+        Commands::Claim(claim_args) => {
+            let amount = claim_args
+                .amount
+                .map(|amount| (amount * 10f64.powi(ore::TOKEN_DECIMALS as i32)) as u64);
+            let claims = ore
+                .claim_miners(&claim_args.miner, amount, &policy)
+                .await
+                .map(|claims| ClaimsOutput::new(ore.owner_token_account(), claims));
+            emit(claims, format);
+        }
+        Commands::Close => emit(
+            ore.get_miner_accounts().await.map(|accounts| CloseOutput::new(&accounts)),
+            format,
+        ),
+        Commands::Fund(fund_args) => emit(
+            ore.fund_miners(sol_to_lamports(fund_args.target), &policy)
+                .await
+                .map(|results| TransfersOutput::new("Funded", results)),
+            format,
+        ),
+        Commands::Sweep(_) => emit(
+            ore.sweep_miners(&policy)
+                .await
+                .map(|results| TransfersOutput::new("Swept", results)),
+            format,
+        ),
+        Commands::Report(report_args) => {
+            let to = report_args.to.unwrap_or_else(|| Utc::now().date_naive());
+            let start = report_args.from.and_time(NaiveTime::MIN).and_utc().timestamp();
+            let end = (to + Days::new(1)).and_time(NaiveTime::MIN).and_utc().timestamp();
+            let report = async {
+                let landed = match &policy.ledger {
+                    Some(ledger) => ledger.landed(start..end)?,
+                    None => vec![],
+                };
+                let rows = ore.report(start..end, landed).await?;
+                let stdout = std::io::stdout().lock();
+                match report_args.format {
+                    ReportFormat::Csv => write_csv(&rows, stdout)?,
+                    ReportFormat::Json => write_json(&rows, stdout)?,
+                }
+                Ok::<(), Error>(())
+            };
+            check(report.await, format);
+        }
+        Commands::Stats | Commands::Keys(_) | Commands::Signer(_) | Commands::Config(_) => {
+            unreachable!()
+        }
+        #[cfg(feature = "admin")]
+        Commands::Initialize(admin_args) => {
+            let treasury = check(ore.get_treasury_if_initialized().await, format);
+            let mut output = AdminOutput {
+                action: AdminAction::Initialize,
+                value: ore.owner.pubkey().to_string(),
+                treasury: treasury.as_ref().map(TreasuryOutput::from),
+                signature: None,
+            };
+            if treasury.is_none() {
+                eprintln!("Initializing Ore {} with admin {}", ore::ID, output.value);
+                if admin_args.yes || check(confirm(), format) {
+                    let signature = check(ore.initialize(&policy).await, format);
+                    output.signature = Some(signature.to_string());
+                }
+            }
+            output::print(&output, format);
+        }
+        #[cfg(feature = "admin")]
+        Commands::UpdateAdmin(update_args) => {
+            let treasury = check(ore.get_treasury().await, format);
+            print_treasury(&treasury);
+            eprintln!("New admin: {}", update_args.new_admin);
+            let mut output = AdminOutput {
+                action: AdminAction::UpdateAdmin,
+                value: update_args.new_admin.to_string(),
+                treasury: Some(TreasuryOutput::from(&treasury)),
+                signature: None,
+            };
+            if update_args.admin.yes || check(confirm(), format) {
+                let signature =
+                    check(ore.update_admin(update_args.new_admin, &policy).await, format);
+                output.signature = Some(signature.to_string());
+            }
+            output::print(&output, format);
+        }
+        #[cfg(feature = "admin")]
+        Commands::UpdateDifficulty(update_args) => {
+            let treasury = check(ore.get_treasury().await, format);
+            print_treasury(&treasury);
+            eprintln!("New difficulty: {}", update_args.difficulty);
+            let mut output = AdminOutput {
+                action: AdminAction::UpdateDifficulty,
+                value: update_args.difficulty.to_string(),
+                treasury: Some(TreasuryOutput::from(&treasury)),
+                signature: None,
+            };
+            if update_args.admin.yes || check(confirm(), format) {
+                let signature =
+                    check(ore.update_difficulty(update_args.difficulty, &policy).await, format);
+                output.signature = Some(signature.to_string());
+            }
+            output::print(&output, format);
+        }
+    }
+}
+
+fn run_keys(
+    command: &KeysCommand,
+    source: &PassphraseSource,
+    unlocker: &Unlocker,
+) -> std::io::Result<KeysOutput> {
+    let key = |path: &Path, result: std::io::Result<Pubkey>| KeyOutput {
+        path: path.display().to_string(),
+        pubkey: result.as_ref().ok().map(ToString::to_string),
+        error: result.err().map(|err| err.to_string()),
+    };
+    let (action, keys) = match command {
+        KeysCommand::Import { input, output } => {
+            let keypair = read_plain_keypair(input)?;
+            let passphrase = source.read_new()?;
+            Keystore::encrypt(&keypair, &passphrase)?.save(output)?;
+            (KeyAction::Encrypted, vec![key(output, Ok(keypair.pubkey()))])
+        }
+        KeysCommand::Export { keystore, output } => {
+            let keypair = unlocker.read_keypair(keystore)?;
+            write_plain_keypair(&keypair, output)?;
+            (KeyAction::Decrypted, vec![key(output, Ok(keypair.pubkey()))])
+        }
+        KeysCommand::Unlock { keystores } => {
+            let keys = keystores
+                .iter()
+                .map(|path| key(path, unlocker.read_keypair(path).map(|keypair| keypair.pubkey())))
+                .collect();
+            (KeyAction::Unlocked, keys)
+        }
+    };
+    Ok(KeysOutput { action, keys })
+}
 
-    let owner = read_keypair_file(args.owner.clone()).unwrap();
-    let rpc_pool = RpcPool::new(vec![args.rpc.clone()]);
-    let miners = args.miners.iter().map(|miner| Miner::new(read_keypair_file(miner.clone()).unwrap())).collect();
+/// Shows the treasury an admin command is about to change on stderr, next to its prompt.
+#[cfg(feature = "admin")]
+fn print_treasury(treasury: &ore::state::Treasury) {
+    eprintln!("Treasury {}", ore::TREASURY_ADDRESS);
+    eprintln!("  Admin: {}", treasury.admin);
+    eprintln!("  Difficulty: {}", Difficulty::from(treasury.difficulty));
+    eprintln!("  Last reset at: {}", treasury.last_reset_at);
+    eprintln!("  Reward rate: {}", treasury.reward_rate);
+    eprintln!("  Total claimed rewards: {}", treasury.total_claimed_rewards);
+}
+
+/// Asks on stderr, so stdout only carries the command's output. A closed stdin answers no.
+#[cfg(feature = "admin")]
+fn confirm() -> std::io::Result<bool> {
+    eprint!("Proceed? [y/N] ");
+    std::io::Write::flush(&mut std::io::stderr())?;
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
 
-    let ore = Ore { owner, rpc_pool, miners };
+/// Logs why mining stopped, delivers the queued events and exits with status 1.
+fn exit_with(err: Error, events: Option<&EventBus>) -> ! {
+    error!(kind = err.kind(), error = %err.to_string(), "Mining stopped");
+    if let Some(events) = events {
+        events.close();
+    }
+    std::process::exit(1);
+}
+
+/// Prints a command's result in `format`, or its error and exits with status 1.
+fn emit(result: Result<impl Render>, format: OutputFormat) {
+    match result {
+        Ok(value) => output::print(&value, format),
+        Err(err) => fail(err, format),
+    }
+}
 
-    ore.mine().await.unwrap();
+/// The value of a step a command needs, or its error printed in `format` and exit status 1.
+fn check<T>(result: std::result::Result<T, impl Into<Error>>, format: OutputFormat) -> T {
+    result.unwrap_or_else(|err| fail(err.into(), format))
+}
+
+/// Prints the error in `format` and exits with status 1 once the queued events are delivered.
+fn fail(err: Error, format: OutputFormat) -> ! {
+    output::print_error(&err, format);
+    if let Some(events) = EVENTS.get().and_then(Weak::upgrade) {
+        events.close();
+    }
+    std::process::exit(1);
+}
+
+fn config_error(message: impl ToString) -> Error {
+    Error::CliError(CliError::Config(message.to_string()))
+}
 
-    println!("{:?}", args);
+async fn shutdown_signal() {
+    let mut terminate = signal(SignalKind::terminate()).expect("Failed to listen for SIGTERM");
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => {}
+        _ = terminate.recv() => {}
+    }
 }
@@ -0,0 +1,545 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Write as _,
+    io::{self, BufReader, Read, Write},
+    net::{TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, RwLock,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use ore::{state::Proof, ONE_ORE};
+use solana_sdk::{keccak::Hash as KeccakHash, pubkey::Pubkey, signature::Signature};
+use tracing::warn;
+
+use crate::{
+    errors::Error,
+    factory::Ore,
+    http::read_http_head,
+    rpc::{endpoint_host, RpcTransportStats},
+    scheduler::EpochWindow,
+    verify::Rejection,
+};
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+/// Largest request body read, and thrown away, from a scraper.
+const MAX_BODY: usize = 4 * 1024;
+
+/// Per-miner counters. Hashes are estimated from the winning nonce, since every backend searches
+/// nonces upwards from zero.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MinerStats {
+    pub hashes: u64,
+    pub solutions: u64,
+    /// Total time spent finding `solutions`.
+    pub solution_time: Duration,
+    /// Hash rate of the last solution.
+    pub hashes_per_second: f64,
+    pub submissions: u64,
+    pub landed: u64,
+    pub claimable_rewards: u64,
+    pub state: MinerState,
+    /// The challenge being hashed and when the miner got it.
+    pub challenge: Option<(KeccakHash, Instant)>,
+    pub last_signature: Option<Signature>,
+    /// Lifetime rewards of the proof, when first seen and now.
+    pub rewards: Option<(u64, u64)>,
+}
+
+impl MinerStats {
+    /// Rewards the proof earned since the session started.
+    pub fn session_rewards(&self) -> u64 {
+        self.rewards
+            .map_or(0, |(first, last)| last.saturating_sub(first))
+    }
+}
+
+/// Where a miner is in the pipeline.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MinerState {
+    /// Waiting in the scheduler for a hashing slot.
+    #[default]
+    Queued,
+    Hashing,
+    /// Verifying found work against the on-chain proof.
+    Submitting,
+    /// The mine transaction is being sent and confirmed.
+    Confirming,
+    /// Found work waits for mining to be profitable again.
+    Held,
+}
+
+impl std::fmt::Display for MinerState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let label = match self {
+            MinerState::Queued => "queued",
+            MinerState::Hashing => "hashing",
+            MinerState::Submitting => "submitting",
+            MinerState::Confirming => "confirming",
+            MinerState::Held => "held",
+        };
+        write!(f, "{}", label)
+    }
+}
+
+/// How a transaction, or one attempt to send it, ended.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Outcome {
+    Landed,
+    /// Expired before confirming, every attempt of it for a whole transaction.
+    Expired,
+    Failed,
+}
+
+impl Outcome {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Outcome::Landed => "landed",
+            Outcome::Expired => "expired",
+            Outcome::Failed => "failed",
+        }
+    }
+}
+
+/// Counters shared by every stage of the pipeline.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    verified: AtomicU64,
+    rejected: [AtomicU64; Rejection::ALL.len()],
+    miners: Mutex<BTreeMap<Pubkey, MinerStats>>,
+    /// Mine transactions by outcome and `errors::Error` kind, `"none"` when landed.
+    outcomes: Mutex<BTreeMap<(Outcome, &'static str), u64>>,
+    priority_fee_lamports: AtomicU64,
+    window: RwLock<Option<EpochWindow>>,
+}
+
+impl Metrics {
+    pub fn record_verified(&self) {
+        self.verified.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_rejected(&self, rejection: Rejection) {
+        self.rejected[rejection.index()].fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn verified(&self) -> u64 {
+        self.verified.load(Ordering::Relaxed)
+    }
+
+    pub fn rejected(&self, rejection: Rejection) -> u64 {
+        self.rejected[rejection.index()].load(Ordering::Relaxed)
+    }
+
+    pub fn record_solution(&self, miner: Pubkey, nonce: u64, elapsed: Duration) {
+        let hashes = nonce.saturating_add(1);
+        self.update_miner(miner, |stats| {
+            stats.hashes += hashes;
+            stats.solutions += 1;
+            stats.solution_time += elapsed;
+            stats.hashes_per_second = hashes as f64 / elapsed.as_secs_f64().max(f64::EPSILON);
+        });
+    }
+
+    pub fn record_state(&self, miner: Pubkey, state: MinerState) {
+        self.update_miner(miner, |stats| stats.state = state);
+    }
+
+    /// Records that the miner started hashing `challenge`, keeping the time it was first seen.
+    pub fn record_hashing(&self, miner: Pubkey, challenge: KeccakHash) {
+        self.update_miner(miner, |stats| {
+            stats.state = MinerState::Hashing;
+            if !stats
+                .challenge
+                .is_some_and(|(current, _)| current == challenge)
+            {
+                stats.challenge = Some((challenge, Instant::now()));
+            }
+        });
+    }
+
+    pub fn record_submission(&self, miner: Pubkey) {
+        self.update_miner(miner, |stats| stats.submissions += 1);
+    }
+
+    /// Records a landed transaction and the priority fee it paid.
+    pub fn record_landed(&self, miner: Pubkey, signature: Signature, priority_fee_lamports: u64) {
+        self.update_miner(miner, |stats| {
+            stats.landed += 1;
+            stats.last_signature = Some(signature);
+        });
+        self.priority_fee_lamports
+            .fetch_add(priority_fee_lamports, Ordering::Relaxed);
+        self.count_outcome(Outcome::Landed, "none");
+    }
+
+    /// Records a transaction that did not land, as expired when every attempt timed out.
+    pub fn record_failed(&self, error: &Error) {
+        let outcome = match error.kind() {
+            "TransactionNotLanded" => Outcome::Expired,
+            _ => Outcome::Failed,
+        };
+        self.count_outcome(outcome, error.kind());
+    }
+
+    pub fn record_proof(&self, miner: Pubkey, proof: &Proof) {
+        self.update_miner(miner, |stats| {
+            stats.claimable_rewards = proof.claimable_rewards;
+            let first = stats
+                .rewards
+                .map_or(proof.total_rewards, |(first, _)| first);
+            stats.rewards = Some((first, proof.total_rewards));
+        });
+    }
+
+    pub fn record_window(&self, window: &EpochWindow) {
+        *self.window.write().unwrap() = Some(window.clone());
+    }
+
+    pub fn window(&self) -> Option<EpochWindow> {
+        self.window.read().unwrap().clone()
+    }
+
+    pub fn miners(&self) -> BTreeMap<Pubkey, MinerStats> {
+        self.miners.lock().unwrap().clone()
+    }
+
+    pub fn miner(&self, miner: &Pubkey) -> MinerStats {
+        self.miners
+            .lock()
+            .unwrap()
+            .get(miner)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    pub fn outcomes(&self, outcome: Outcome) -> u64 {
+        self.outcomes
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|((counted, _), _)| *counted == outcome)
+            .map(|(_, count)| count)
+            .sum()
+    }
+
+    pub fn priority_fee_lamports(&self) -> u64 {
+        self.priority_fee_lamports.load(Ordering::Relaxed)
+    }
+
+    fn update_miner(&self, miner: Pubkey, update: impl FnOnce(&mut MinerStats)) {
+        update(self.miners.lock().unwrap().entry(miner).or_default());
+    }
+
+    fn count_outcome(&self, outcome: Outcome, kind: &'static str) {
+        *self
+            .outcomes
+            .lock()
+            .unwrap()
+            .entry((outcome, kind))
+            .or_default() += 1;
+    }
+
+    /// Renders every metric, plus the transport stats of each RPC endpoint, in the Prometheus
+    /// text format.
+    pub fn render(&self, rpc: &[(String, RpcTransportStats)]) -> String {
+        let mut out = String::new();
+
+        header(
+            &mut out,
+            "ore_work_verified_total",
+            "counter",
+            "Work that passed local verification",
+        );
+        sample(&mut out, "ore_work_verified_total", "", self.verified());
+        header(
+            &mut out,
+            "ore_work_rejected_total",
+            "counter",
+            "Work rejected by local verification",
+        );
+        for rejection in Rejection::ALL {
+            let labels = format!("reason=\"{}\"", rejection);
+            sample(
+                &mut out,
+                "ore_work_rejected_total",
+                &labels,
+                self.rejected(rejection),
+            );
+        }
+
+        let miners = self.miners.lock().unwrap().clone();
+        header(
+            &mut out,
+            "ore_miner_hashes_total",
+            "counter",
+            "Estimated hashes computed",
+        );
+        for (miner, stats) in &miners {
+            sample(
+                &mut out,
+                "ore_miner_hashes_total",
+                &miner_label(miner),
+                stats.hashes,
+            );
+        }
+        header(
+            &mut out,
+            "ore_miner_hashes_per_second",
+            "gauge",
+            "Hash rate of the last solution",
+        );
+        for (miner, stats) in &miners {
+            let labels = miner_label(miner);
+            sample(
+                &mut out,
+                "ore_miner_hashes_per_second",
+                &labels,
+                stats.hashes_per_second,
+            );
+        }
+        header(
+            &mut out,
+            "ore_miner_solution_seconds",
+            "summary",
+            "Time to find a solution",
+        );
+        for (miner, stats) in &miners {
+            let labels = miner_label(miner);
+            let seconds = stats.solution_time.as_secs_f64();
+            sample(&mut out, "ore_miner_solution_seconds_sum", &labels, seconds);
+            sample(
+                &mut out,
+                "ore_miner_solution_seconds_count",
+                &labels,
+                stats.solutions,
+            );
+        }
+        header(
+            &mut out,
+            "ore_miner_submissions_total",
+            "counter",
+            "Mine transactions submitted",
+        );
+        for (miner, stats) in &miners {
+            let labels = miner_label(miner);
+            sample(
+                &mut out,
+                "ore_miner_submissions_total",
+                &labels,
+                stats.submissions,
+            );
+        }
+        header(
+            &mut out,
+            "ore_miner_landed_total",
+            "counter",
+            "Mine transactions landed",
+        );
+        for (miner, stats) in &miners {
+            sample(
+                &mut out,
+                "ore_miner_landed_total",
+                &miner_label(miner),
+                stats.landed,
+            );
+        }
+        header(
+            &mut out,
+            "ore_miner_claimable_ore",
+            "gauge",
+            "Rewards the miner can claim",
+        );
+        for (miner, stats) in &miners {
+            let claimable = stats.claimable_rewards as f64 / ONE_ORE as f64;
+            sample(
+                &mut out,
+                "ore_miner_claimable_ore",
+                &miner_label(miner),
+                claimable,
+            );
+        }
+
+        header(
+            &mut out,
+            "ore_transactions_total",
+            "counter",
+            "Mine transactions by outcome and error kind",
+        );
+        for ((outcome, kind), count) in self.outcomes.lock().unwrap().iter() {
+            let labels = format!("outcome=\"{}\",kind=\"{}\"", outcome.label(), kind);
+            sample(&mut out, "ore_transactions_total", &labels, count);
+        }
+        header(
+            &mut out,
+            "ore_priority_fee_lamports_total",
+            "counter",
+            "Priority fees paid by landed transactions",
+        );
+        sample(
+            &mut out,
+            "ore_priority_fee_lamports_total",
+            "",
+            self.priority_fee_lamports(),
+        );
+
+        header(
+            &mut out,
+            "ore_rpc_request_seconds",
+            "summary",
+            "Time spent on RPC requests",
+        );
+        for (index, (url, stats)) in rpc.iter().enumerate() {
+            let labels = endpoint_label(index, url);
+            let seconds = stats.elapsed_time.as_secs_f64();
+            sample(&mut out, "ore_rpc_request_seconds_sum", &labels, seconds);
+            sample(
+                &mut out,
+                "ore_rpc_request_seconds_count",
+                &labels,
+                stats.request_count,
+            );
+        }
+        header(
+            &mut out,
+            "ore_rpc_rate_limited_seconds_total",
+            "counter",
+            "Time spent waiting on RPC rate limits",
+        );
+        for (index, (url, stats)) in rpc.iter().enumerate() {
+            let labels = endpoint_label(index, url);
+            let seconds = stats.rate_limited_time.as_secs_f64();
+            sample(
+                &mut out,
+                "ore_rpc_rate_limited_seconds_total",
+                &labels,
+                seconds,
+            );
+        }
+
+        if let Some(window) = self.window.read().unwrap().as_ref() {
+            header(
+                &mut out,
+                "ore_epoch_last_reset_timestamp_seconds",
+                "gauge",
+                "When the current epoch started",
+            );
+            sample(
+                &mut out,
+                "ore_epoch_last_reset_timestamp_seconds",
+                "",
+                window.last_reset_at,
+            );
+            header(
+                &mut out,
+                "ore_epoch_seconds_left",
+                "gauge",
+                "Seconds until the epoch can be reset",
+            );
+            sample(
+                &mut out,
+                "ore_epoch_seconds_left",
+                "",
+                window.seconds_left(),
+            );
+            header(
+                &mut out,
+                "ore_reward_rate",
+                "gauge",
+                "Reward per valid hash, in the smallest ORE unit",
+            );
+            sample(&mut out, "ore_reward_rate", "", window.reward_rate);
+            header(
+                &mut out,
+                "ore_bus_rewards",
+                "gauge",
+                "Rewards left on each bus, in the smallest ORE unit",
+            );
+            for (bus, rewards) in window.bus_rewards.iter().enumerate() {
+                sample(
+                    &mut out,
+                    "ore_bus_rewards",
+                    &format!("bus=\"{}\"", bus),
+                    rewards,
+                );
+            }
+        }
+
+        out
+    }
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+fn sample(out: &mut String, name: &str, labels: &str, value: impl std::fmt::Display) {
+    if labels.is_empty() {
+        let _ = writeln!(out, "{} {}", name, value);
+    } else {
+        let _ = writeln!(out, "{}{{{}}} {}", name, labels, value);
+    }
+}
+
+fn miner_label(miner: &Pubkey) -> String {
+    format!("miner=\"{}\"", miner)
+}
+
+fn endpoint_label(index: usize, url: &str) -> String {
+    format!(
+        "endpoint=\"{}\",host=\"{}\"",
+        index,
+        endpoint_host(url).replace(['"', '\\'], "")
+    )
+}
+
+/// Serves `GET /metrics` for a running pipeline. Every connection is handled on its own thread,
+/// so a stalled client does not hold up scraping.
+#[derive(Clone)]
+pub struct MetricsServer {
+    pub metrics: Arc<Metrics>,
+    pub ore: Arc<Ore>,
+}
+
+impl MetricsServer {
+    pub fn serve(&self, listener: TcpListener) -> io::Result<()> {
+        for stream in listener.incoming() {
+            let stream = stream?;
+            let server = self.clone();
+            thread::spawn(move || {
+                if let Err(err) = server.respond(stream) {
+                    warn!(error = %err, "Metrics connection failed");
+                }
+            });
+        }
+        Ok(())
+    }
+
+    fn respond(&self, stream: TcpStream) -> io::Result<()> {
+        stream.set_read_timeout(Some(TIMEOUT))?;
+        stream.set_write_timeout(Some(TIMEOUT))?;
+        let mut reader = BufReader::new(&stream);
+        let head = read_http_head(&mut reader, MAX_BODY)?;
+        reader.take(head.length as u64).read_to_end(&mut vec![])?;
+
+        let mut parts = head.first.split_whitespace();
+        let (status, body) = match (parts.next(), parts.next()) {
+            (Some("GET"), Some("/metrics")) => (
+                "200 OK",
+                self.metrics.render(&self.ore.rpc_pool.transport_stats()),
+            ),
+            (Some("GET"), _) => ("404 Not Found", "not found\n".to_string()),
+            _ => ("405 Method Not Allowed", "use GET\n".to_string()),
+        };
+        write!(
+            &stream,
+            "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            status,
+            body.len()
+        )?;
+        (&stream).write_all(body.as_bytes())
+    }
+}
@@ -1,47 +1,45 @@
+#[cfg(feature = "gpu")]
+use crate::{gpu_batch::GpuJob, mine_gpu::GpuSearcher};
 use crate::{
-    errors::{CliError, Error, Result},
+    errors::Result,
+    factory::invalid_account,
+    metrics::{Metrics, MinerState},
     rpc::RpcClient,
-    transaction::Transaction,
+    scheduler::Scheduler,
 };
 use cached::proc_macro::cached;
-use futures::{
-    future::{self, try_join_all},
-    Future, SinkExt,
-};
-use ore::{instruction, state::Proof, utils::AccountDeserialize, PROOF};
-use rayon::{prelude::*, Scope};
+use flume::Sender;
+use ore::{state::Proof, utils::AccountDeserialize, PROOF};
+use rayon::{prelude::*, ThreadPool};
 use solana_sdk::{
-    commitment_config::CommitmentConfig,
     keccak::{hashv, Hash as KeccakHash},
     pubkey::Pubkey,
     signer::{keypair::Keypair, Signer},
 };
 use std::{
-    borrow::{Borrow, BorrowMut},
-    cell::{Cell, RefCell},
-    collections::{HashMap, VecDeque},
     fmt::Debug,
     hash::{Hash, Hasher},
-    io::{stdout, Write},
-    num,
-    ops::{Deref, DerefMut},
-    pin::Pin,
+    ops::Deref,
     sync::{
-        atomic::{AtomicBool, AtomicUsize, Ordering},
-        mpsc::{channel, Receiver},
-        Arc, Mutex, RwLock,
+        atomic::{AtomicBool, Ordering},
+        Arc,
     },
-    time::{Duration, Instant},
+    thread::{self, JoinHandle},
+    time::Instant,
 };
+use tracing::{debug, info, info_span};
 
-#[derive(PartialEq)]
+/// A cheaply cloneable handle to a miner's keypair.
+#[derive(Clone, PartialEq)]
 pub struct Miner {
-    keypair: Keypair,
+    keypair: Arc<Keypair>,
 }
 
 impl Miner {
     pub fn new(keypair: Keypair) -> Self {
-        Miner { keypair }
+        Miner {
+            keypair: Arc::new(keypair),
+        }
     }
 
     pub fn mine(&self, last_work: &Work, difficulty: &KeccakHash) -> Work {
@@ -61,7 +59,8 @@ impl Miner {
         Work::ToBeProved(next_hash, nonce)
     }
 
-    pub fn mine_par(&self, last_work: &Work, difficulty: &KeccakHash) -> Work {
+    /// Searches the nonce space on the current rayon pool. Returns `None` once `stop` is set.
+    pub fn mine_par(&self, last_work: &Work, difficulty: &KeccakHash, stop: &AtomicBool) -> Option<Work> {
         let pubkey = self.pubkey().to_bytes();
         let last_hash = last_work.hash().to_bytes();
 
@@ -69,56 +68,27 @@ impl Miner {
             .into_par_iter()
             .by_exponential_blocks()
             .find_map_any(|nonce| {
+                if stop.load(Ordering::Relaxed) {
+                    return Some(None);
+                }
                 let hash = hashv(&[&last_hash, &pubkey, &nonce.to_le_bytes()]);
-                if hash.le(&difficulty) {
-                    return Some((hash, nonce));
+                if hash.le(difficulty) {
+                    Some(Some((hash, nonce)))
                 } else {
-                    return None;
+                    None
                 }
             })
-            .unwrap();
+            .flatten()?;
 
-        Work::ToBeProved(hash, nonce as u64)
+        Some(Work::ToBeProved(hash, nonce as u64))
     }
 
     pub async fn get_proof(&self, client: &RpcClient) -> Result<Proof> {
         let proof_address = proof_pubkey(self.keypair.pubkey());
         let data = client.get_account_data(&proof_address).await?;
-        Ok(*Proof::try_from_bytes(&data).expect("Failed to parse miner's proof account"))
-    }
-
-    pub async fn get_proof_or_register(
-        &self,
-        client: &RpcClient,
-        fee_payer: Option<&dyn Signer>,
-    ) -> Result<Proof> {
-        let proof_result = self.get_proof(client).await;
-
-        if proof_result
-            .as_ref()
-            .is_err_and(|err| err.to_string().contains("AccountNotFound"))
-        {
-            let instruction = instruction::register(self.keypair.pubkey());
-            let transaction = Transaction::new(vec![instruction]);
-            let sent_tx = transaction
-                .send(client, &self.keypair, fee_payer, false)
-                .await?;
-            let confirmed = sent_tx
-                .confirm(
-                    client,
-                    CommitmentConfig::confirmed(),
-                    Duration::from_millis(1000),
-                )
-                .await?;
-
-            if confirmed {
-                return self.get_proof(client).await;
-            } else {
-                return Err(Error::CliError(CliError::TransactionNotLanded));
-            }
-        }
-
-        proof_result
+        let proof = Proof::try_from_bytes(&data)
+            .map_err(|err| invalid_account(format!("failed to parse proof account: {}", err)))?;
+        Ok(*proof)
     }
 }
 
@@ -162,72 +132,224 @@ impl Work {
         }
     }
 
-    pub fn to_signed(self, miner: &Miner) -> SignedWork {
+    pub fn into_signed(self, miner: &Miner, log: &MinerLog) -> SignedWork {
         SignedWork {
-            signer: miner,
+            signer: miner.clone(),
+            challenge: *log.last_work.hash(),
+            bus: log.bus,
             work: self,
         }
     }
 }
 
-#[derive(Debug)]
-pub struct SignedWork<'a> {
-    signer: &'a Miner,
-    work: Work,
+/// Work found by a miner together with the challenge it answers and the bus to submit it on.
+#[derive(Clone, Debug)]
+pub struct SignedWork {
+    pub signer: Miner,
+    pub challenge: KeccakHash,
+    pub bus: usize,
+    pub work: Work,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct MinerLog {
-    last_work: Work,
+    pub last_work: Work,
+    pub bus: usize,
+}
+
+impl MinerLog {
+    pub fn new(proof: &Proof, bus: usize) -> Self {
+        MinerLog {
+            last_work: Work::Proved(proof.hash.into()),
+            bus,
+        }
+    }
+}
+
+/// Where nonces are searched. Every backend stops searching once the shared `stop` flag is set.
+#[derive(Clone)]
+pub enum Backend {
+    Cpu(Arc<ThreadPool>),
+    #[cfg(feature = "gpu")]
+    Gpu(Arc<GpuSearcher>),
+}
+
+impl Backend {
+    pub fn search(
+        &self,
+        miner: &Miner,
+        last_work: &Work,
+        difficulty: &KeccakHash,
+        stop: &AtomicBool,
+    ) -> Option<Work> {
+        match self {
+            Backend::Cpu(pool) => pool.install(|| miner.mine_par(last_work, difficulty, stop)),
+            #[cfg(feature = "gpu")]
+            Backend::Gpu(gpu) => gpu
+                .search(last_work.hash(), &miner.pubkey(), difficulty, stop)
+                .map(|(hash, nonce)| Work::ToBeProved(hash, nonce)),
+        }
+    }
 }
 
-#[derive(Debug)]
-pub struct MineLine<'a> {
-    miner_logs: HashMap<&'a Miner, MinerLog>,
+impl Debug for Backend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Backend::Cpu(pool) => write!(f, "CPU ({} threads)", pool.current_num_threads()),
+            #[cfg(feature = "gpu")]
+            Backend::Gpu(gpu) => write!(f, "GPU ({})", gpu.adapter_name()),
+        }
+    }
 }
 
-impl<'a> MineLine<'a> {
-    pub async fn init(
-        miners: &'a [Miner],
-        client: &RpcClient,
-        fee_payer: Option<&dyn Signer>,
-    ) -> Result<Self> {
-        let set_last_work_futures = miners.iter().map(|miner| async move {
-            let proof = miner.get_proof_or_register(client, fee_payer).await?;
-            let log = MinerLog {
-                last_work: Work::Proved(proof.hash.into()),
-            };
-            Result::Ok((miner, log))
-        });
+/// The hashing front of the pipeline: every slot takes the next miner from the scheduler, hashes
+/// its challenge on the backend and hands the work to the submitter. On the GPU the slots are the
+/// jobs of one batched dispatch instead of threads.
+pub struct MineLine {
+    scheduler: Arc<Scheduler>,
+    backend: Backend,
+    metrics: Arc<Metrics>,
+}
 
-        let miner_logs = try_join_all(set_last_work_futures).await?;
-        let miner_logs = HashMap::from_iter(miner_logs);
+impl MineLine {
+    /// Queues every registered miner on the scheduler, starting from its proof. Busses are
+    /// picked as miners are dequeued.
+    pub fn init(
+        miners: Vec<(Miner, Proof)>,
+        scheduler: Arc<Scheduler>,
+        backend: Backend,
+        metrics: Arc<Metrics>,
+    ) -> Self {
+        for (miner, proof) in miners {
+            metrics.record_proof(miner.pubkey(), &proof);
+            scheduler.push(miner, MinerLog::new(&proof, 0));
+        }
 
-        return Ok(MineLine { miner_logs });
+        MineLine {
+            scheduler,
+            backend,
+            metrics,
+        }
     }
 
-    pub fn mine_in_scope(
-        &'a mut self,
-        scope: &Scope<'a>,
+    pub fn spawn(
+        &self,
         difficulty: KeccakHash,
-    ) -> Receiver<SignedWork<'a>> {
-        let (sender, receiver) = channel::<SignedWork<'a>>();
-
-        for (miner, miner_log) in self.miner_logs.iter_mut() {
-            let sender = sender.clone();
-            scope.spawn(move |_| loop {
-                println!("Mining new...");
-                let now = Instant::now();
-                let last_work = miner_log.last_work.clone();
-                let new_work = miner.mine_par(&last_work, &difficulty.clone());
-                miner_log.last_work = new_work.clone();
-                let signed_work = new_work.to_signed(miner);
-                println!("Mined: {:?}", signed_work);
-                println!("Duration: {:?}", now.elapsed());
-                sender.send(signed_work).unwrap();
-            });
+        sender: Sender<SignedWork>,
+        stop: Arc<AtomicBool>,
+    ) -> Vec<JoinHandle<()>> {
+        #[cfg(feature = "gpu")]
+        if let Backend::Gpu(gpu) = &self.backend {
+            return vec![self.spawn_gpu(gpu.clone(), difficulty, sender, stop)];
         }
 
-        receiver
+        (0..self.scheduler.slots())
+            .map(|slot| {
+                let scheduler = self.scheduler.clone();
+                let backend = self.backend.clone();
+                let metrics = self.metrics.clone();
+                let sender = sender.clone();
+                let stop = stop.clone();
+                thread::Builder::new()
+                    .name(format!("ore-slot-{}", slot))
+                    .spawn(move || {
+                        while let Some((miner, miner_log)) = scheduler.pop() {
+                            let _span = info_span!(
+                                "mine",
+                                miner = %miner.pubkey(),
+                                challenge = %miner_log.last_work.hash()
+                            )
+                            .entered();
+                            debug!("Mining");
+                            metrics.record_hashing(miner.pubkey(), *miner_log.last_work.hash());
+                            let now = Instant::now();
+                            let Some(new_work) =
+                                backend.search(&miner, &miner_log.last_work, &difficulty, &stop)
+                            else {
+                                metrics.record_state(miner.pubkey(), MinerState::Queued);
+                                scheduler.push(miner, miner_log);
+                                break;
+                            };
+                            if let Work::ToBeProved(hash, nonce) = new_work {
+                                metrics.record_solution(miner.pubkey(), nonce, now.elapsed());
+                                info!(nonce, %hash, elapsed = ?now.elapsed(), "Mined");
+                            }
+                            let signed_work = new_work.into_signed(&miner, &miner_log);
+                            if sender.send(signed_work).is_err() {
+                                break;
+                            }
+                        }
+                    })
+                    .expect("Failed to spawn hashing slot")
+            })
+            .collect()
+    }
+
+    /// Keeps up to `slots` miners in the GPU batch, topping it up from the scheduler after every
+    /// dispatch so each miner that finds a nonce is replaced by the next one in the queue.
+    #[cfg(feature = "gpu")]
+    fn spawn_gpu(
+        &self,
+        gpu: Arc<GpuSearcher>,
+        difficulty: KeccakHash,
+        sender: Sender<SignedWork>,
+        stop: Arc<AtomicBool>,
+    ) -> JoinHandle<()> {
+        let scheduler = self.scheduler.clone();
+        let metrics = self.metrics.clone();
+        thread::Builder::new()
+            .name("ore-gpu".into())
+            .spawn(move || {
+                let mut active: Vec<(Miner, MinerLog, Instant)> = vec![];
+                let mut jobs: Vec<GpuJob> = vec![];
+
+                'dispatch: while !stop.load(Ordering::Relaxed) {
+                    while active.len() < scheduler.slots() {
+                        let next = if active.is_empty() {
+                            scheduler.pop()
+                        } else {
+                            scheduler.try_pop()
+                        };
+                        let Some((miner, miner_log)) = next else {
+                            break;
+                        };
+                        metrics.record_hashing(miner.pubkey(), *miner_log.last_work.hash());
+                        jobs.push(GpuJob::new(*miner_log.last_work.hash(), miner.pubkey(), difficulty));
+                        active.push((miner, miner_log, Instant::now()));
+                    }
+                    if active.is_empty() {
+                        break;
+                    }
+
+                    let results = gpu.dispatch(&mut jobs);
+                    for (index, result) in results.into_iter().enumerate().rev() {
+                        let Some(nonce) = result else {
+                            continue;
+                        };
+                        let job = jobs.remove(index);
+                        let (miner, miner_log, since) = active.remove(index);
+                        metrics.record_solution(miner.pubkey(), nonce, since.elapsed());
+                        let hash = job.hash(nonce);
+                        info!(
+                            miner = %miner.pubkey(),
+                            challenge = %miner_log.last_work.hash(),
+                            nonce,
+                            %hash,
+                            elapsed = ?since.elapsed(),
+                            "Mined"
+                        );
+                        let signed_work = Work::ToBeProved(hash, nonce).into_signed(&miner, &miner_log);
+                        if sender.send(signed_work).is_err() {
+                            break 'dispatch;
+                        }
+                    }
+                }
+
+                for (miner, miner_log, _) in active {
+                    metrics.record_state(miner.pubkey(), MinerState::Queued);
+                    scheduler.push(miner, miner_log);
+                }
+            })
+            .expect("Failed to spawn GPU batch thread")
     }
 }
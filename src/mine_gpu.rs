@@ -1,7 +1,238 @@
-use crate::mine::Miner;
+use std::{
+    borrow::Cow,
+    fmt::Display,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+};
 
-impl Miner {
-    pub fn mine_gpu() {
-        
+use solana_sdk::{keccak::Hash as KeccakHash, pubkey::Pubkey};
+use tracing::error;
+use wgpu::util::DeviceExt;
+
+use crate::gpu_batch::{
+    advance_jobs, decode_results, pack_jobs, workgroups_per_job, GpuJob, JOB_SIZE, RESULT_SIZE,
+    WORKGROUP_SIZE,
+};
+
+#[derive(Debug)]
+pub enum GpuError {
+    AdapterNotFound,
+    RequestDevice(wgpu::RequestDeviceError),
+}
+
+impl Display for GpuError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GpuError::AdapterNotFound => write!(f, "no compatible GPU adapter found"),
+            GpuError::RequestDevice(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+/// Buffers sized for `capacity` jobs, reallocated when a bigger batch comes in.
+struct Buffers {
+    capacity: usize,
+    bind_group: wgpu::BindGroup,
+    jobs: wgpu::Buffer,
+    results: wgpu::Buffer,
+    staging: wgpu::Buffer,
+}
+
+struct Kernel {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    pipeline: wgpu::ComputePipeline,
+    buffers: Option<Buffers>,
+}
+
+/// Runs the keccak search of `shaders/mine.wgsl` on a single adapter. Every dispatch advances a
+/// whole batch of jobs, one per miner, so a fleet shares the device instead of queueing on it.
+pub struct GpuSearcher {
+    adapter_name: String,
+    kernel: Mutex<Kernel>,
+}
+
+impl GpuSearcher {
+    pub fn new() -> Result<Self, GpuError> {
+        pollster::block_on(Self::init())
+    }
+
+    async fn init() -> Result<Self, GpuError> {
+        let instance = wgpu::Instance::default();
+        let adapter = instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::HighPerformance,
+                ..Default::default()
+            })
+            .await
+            .ok_or(GpuError::AdapterNotFound)?;
+        let adapter_name = adapter.get_info().name;
+        let (device, queue) = adapter
+            .request_device(
+                &wgpu::DeviceDescriptor {
+                    label: None,
+                    required_features: wgpu::Features::default(),
+                    required_limits: wgpu::Limits {
+                        max_compute_workgroup_size_x: WORKGROUP_SIZE,
+                        max_compute_workgroup_size_y: WORKGROUP_SIZE,
+                        max_compute_invocations_per_workgroup: WORKGROUP_SIZE,
+                        ..Default::default()
+                    },
+                },
+                None,
+            )
+            .await
+            .map_err(GpuError::RequestDevice)?;
+
+        device.on_uncaptured_error(Box::new(|err| {
+            error!(error = ?err, "GPU error");
+        }));
+
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("mine"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("shaders/mine.wgsl"))),
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("mine"),
+            layout: None,
+            module: &module,
+            entry_point: "main",
+        });
+
+        Ok(GpuSearcher {
+            adapter_name,
+            kernel: Mutex::new(Kernel {
+                device,
+                queue,
+                pipeline,
+                buffers: None,
+            }),
+        })
+    }
+
+    pub fn adapter_name(&self) -> &str {
+        &self.adapter_name
+    }
+
+    /// Runs one dispatch over every job and returns the nonce each one found, if any. Jobs that
+    /// found nothing are advanced past the nonces that were searched.
+    pub fn dispatch(&self, jobs: &mut [GpuJob]) -> Vec<Option<u64>> {
+        if jobs.is_empty() {
+            return vec![];
+        }
+        let mut kernel = self.kernel.lock().unwrap();
+        let results = kernel.dispatch(jobs);
+        advance_jobs(jobs, &results);
+        results
+    }
+
+    /// Searches for a nonce whose hash satisfies `difficulty` as a batch of one. Returns `None`
+    /// once `stop` is set, like the CPU path.
+    pub fn search(
+        &self,
+        challenge: &KeccakHash,
+        pubkey: &Pubkey,
+        difficulty: &KeccakHash,
+        stop: &AtomicBool,
+    ) -> Option<(KeccakHash, u64)> {
+        let mut jobs = [GpuJob::new(*challenge, *pubkey, *difficulty)];
+        while !stop.load(Ordering::Relaxed) {
+            if let [Some(nonce)] = self.dispatch(&mut jobs)[..] {
+                return Some((jobs[0].hash(nonce), nonce));
+            }
+        }
+
+        None
+    }
+}
+
+impl Kernel {
+    fn dispatch(&mut self, jobs: &[GpuJob]) -> Vec<Option<u64>> {
+        if self.buffers.as_ref().map_or(true, |buffers| buffers.capacity < jobs.len()) {
+            self.buffers = Some(self.create_buffers(jobs.len().next_power_of_two()));
+        }
+        let buffers = self.buffers.as_ref().unwrap();
+        let results_size = (jobs.len() * RESULT_SIZE) as u64;
+
+        self.queue.write_buffer(&buffers.jobs, 0, &pack_jobs(jobs));
+        self.queue
+            .write_buffer(&buffers.results, 0, &vec![0u8; jobs.len() * RESULT_SIZE]);
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        {
+            let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: None,
+                timestamp_writes: None,
+            });
+            cpass.set_pipeline(&self.pipeline);
+            cpass.set_bind_group(0, &buffers.bind_group, &[]);
+            cpass.dispatch_workgroups(workgroups_per_job(jobs.len()), jobs.len() as u32, 1);
+        }
+        encoder.copy_buffer_to_buffer(&buffers.results, 0, &buffers.staging, 0, results_size);
+        self.queue.submit(Some(encoder.finish()));
+
+        let (sender, receiver) = flume::bounded(1);
+        let staging_slice = buffers.staging.slice(..results_size);
+        staging_slice.map_async(wgpu::MapMode::Read, move |result| {
+            sender.send(result).ok();
+        });
+        self.device.poll(wgpu::Maintain::Wait);
+
+        let mut results = vec![None; jobs.len()];
+        if let Ok(Ok(())) = receiver.recv() {
+            results = decode_results(&staging_slice.get_mapped_range(), jobs.len());
+        }
+        buffers.staging.unmap();
+
+        results
+    }
+
+    fn create_buffers(&self, capacity: usize) -> Buffers {
+        let jobs = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("jobs"),
+            size: (capacity * JOB_SIZE) as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let results = self.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("results"),
+            contents: &vec![0u8; capacity * RESULT_SIZE],
+            usage: wgpu::BufferUsages::STORAGE
+                | wgpu::BufferUsages::COPY_SRC
+                | wgpu::BufferUsages::COPY_DST,
+        });
+        let staging = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("staging"),
+            size: (capacity * RESULT_SIZE) as u64,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &self.pipeline.get_bind_group_layout(0),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: jobs.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: results.as_entire_binding(),
+                },
+            ],
+        });
+
+        Buffers {
+            capacity,
+            bind_group,
+            jobs,
+            results,
+            staging,
+        }
     }
-}
\ No newline at end of file
+}
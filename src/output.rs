@@ -0,0 +1,964 @@
+//! What commands print. Every response is a plain struct that serializes to a stable JSON schema,
+//! with amounts as both the raw integer and the UI string, and renders as a table for people or
+//! as tab separated lines for shell scripts.
+
+use std::fmt::{self, Display, Write};
+
+use clap::ValueEnum;
+use ore::{state::Treasury, BUS_ADDRESSES, TOKEN_DECIMALS, TREASURY_ADDRESS};
+use serde::Serialize;
+use solana_sdk::{native_token::LAMPORTS_PER_SOL, pubkey::Pubkey, signature::Signature};
+
+use crate::{
+    errors::{Error, Result},
+    fleet::{MinerAccount, Transfer},
+    ledger::Summary,
+    scheduler::EpochWindow,
+};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Aligned columns with headers and totals.
+    #[default]
+    Table,
+    /// One JSON document on stdout, and errors as `{"error": {"kind", "message"}}`.
+    Json,
+    /// One record per line, tab separated, without headers or totals.
+    Plain,
+}
+
+/// An amount in base units, lamports or ORE's smallest unit, next to its decimal form.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Amount {
+    pub raw: u64,
+    pub ui: String,
+}
+
+impl Amount {
+    pub fn sol(lamports: u64) -> Self {
+        Amount::new(lamports, LAMPORTS_PER_SOL)
+    }
+
+    pub fn ore(amount: u64) -> Self {
+        Amount::new(amount, 10u64.pow(TOKEN_DECIMALS as u32))
+    }
+
+    fn new(raw: u64, one: u64) -> Self {
+        let decimals = one.ilog10() as usize;
+        Amount {
+            raw,
+            ui: format!("{}.{:0decimals$}", raw / one, raw % one),
+        }
+    }
+}
+
+impl Display for Amount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(&self.ui)
+    }
+}
+
+/// An error as JSON, `kind` being the `errors::Error` variant.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct ErrorOutput {
+    pub kind: &'static str,
+    pub message: String,
+}
+
+impl From<&Error> for ErrorOutput {
+    fn from(error: &Error) -> Self {
+        ErrorOutput {
+            kind: error.kind(),
+            message: error.to_string(),
+        }
+    }
+}
+
+/// How a transaction of an action ended, flattened into the action's rows.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct TransactionOutput {
+    pub signature: Option<String>,
+    pub error: Option<ErrorOutput>,
+}
+
+impl TransactionOutput {
+    pub fn new(result: &Result<Signature>) -> Self {
+        match result {
+            Ok(signature) => TransactionOutput {
+                signature: Some(signature.to_string()),
+                error: None,
+            },
+            Err(err) => TransactionOutput {
+                signature: None,
+                error: Some(err.into()),
+            },
+        }
+    }
+
+    /// The signature and error kind, `-` for whichever is missing.
+    fn plain(&self) -> String {
+        let signature = self.signature.as_deref().unwrap_or("-");
+        let kind = self.error.as_ref().map_or("-", |error| error.kind);
+        format!("{}\t{}", signature, kind)
+    }
+}
+
+impl Display for TransactionOutput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.signature, &self.error) {
+            (Some(signature), _) => write!(f, "{}", signature),
+            (None, Some(error)) => write!(f, "{}", error.message),
+            (None, None) => Ok(()),
+        }
+    }
+}
+
+pub trait Render: Serialize {
+    fn table(&self) -> String;
+    fn plain(&self) -> String;
+}
+
+pub fn print(value: &impl Render, format: OutputFormat) {
+    match format {
+        OutputFormat::Table => print!("{}", value.table()),
+        OutputFormat::Plain => print!("{}", value.plain()),
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(value).unwrap()),
+    }
+}
+
+/// Prints an error as JSON on stdout in the JSON format, otherwise on stderr.
+pub fn print_error(error: &Error, format: OutputFormat) {
+    #[derive(Serialize)]
+    struct ErrorDocument {
+        error: ErrorOutput,
+    }
+
+    match format {
+        OutputFormat::Json => {
+            let document = ErrorDocument {
+                error: error.into(),
+            };
+            println!("{}", serde_json::to_string_pretty(&document).unwrap());
+        }
+        OutputFormat::Table | OutputFormat::Plain => eprintln!("Error: {}", error.to_string()),
+    }
+}
+
+/// The owner's SOL and ORE.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct BalanceOutput {
+    pub owner: String,
+    pub token_account: String,
+    pub sol: Amount,
+    pub ore: Amount,
+}
+
+impl Render for BalanceOutput {
+    fn table(&self) -> String {
+        let mut out = String::new();
+        writeln!(out, "{:<14} {}", "Owner", self.owner).unwrap();
+        writeln!(out, "{:<14} {}", "Token account", self.token_account).unwrap();
+        writeln!(out, "{:<14} {}", "SOL", self.sol).unwrap();
+        writeln!(out, "{:<14} {}", "ORE", self.ore).unwrap();
+        out
+    }
+
+    fn plain(&self) -> String {
+        format!("{}\t{}\t{}\n", self.owner, self.sol, self.ore)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct MinerOutput {
+    pub miner: String,
+    pub registered: bool,
+    pub sol: Amount,
+    pub claimable: Amount,
+    pub total_rewards: Amount,
+    pub total_hashes: u64,
+    /// Rent held by the proof account.
+    pub proof_rent: Amount,
+}
+
+impl From<&MinerAccount> for MinerOutput {
+    fn from(account: &MinerAccount) -> Self {
+        let proof = account.proof.as_ref();
+        MinerOutput {
+            miner: account.pubkey.to_string(),
+            registered: proof.is_some(),
+            sol: Amount::sol(account.lamports),
+            claimable: Amount::ore(proof.map_or(0, |proof| proof.claimable_rewards)),
+            total_rewards: Amount::ore(proof.map_or(0, |proof| proof.total_rewards)),
+            total_hashes: proof.map_or(0, |proof| proof.total_hashes),
+            proof_rent: Amount::sol(account.proof_lamports),
+        }
+    }
+}
+
+/// Every miner's balance and proof, for `miners list` and `rewards`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct MinersOutput {
+    pub miners: Vec<MinerOutput>,
+    pub total_sol: Amount,
+    pub total_claimable: Amount,
+}
+
+impl MinersOutput {
+    pub fn new(accounts: &[MinerAccount]) -> Self {
+        let miners = accounts.iter().map(MinerOutput::from).collect::<Vec<_>>();
+        MinersOutput {
+            total_sol: Amount::sol(miners.iter().map(|miner| miner.sol.raw).sum()),
+            total_claimable: Amount::ore(miners.iter().map(|miner| miner.claimable.raw).sum()),
+            miners,
+        }
+    }
+}
+
+impl Render for MinersOutput {
+    fn table(&self) -> String {
+        let mut out = String::new();
+        writeln!(
+            out,
+            "{:<44} {:>14} {:<12} {:>16}",
+            "Miner", "SOL", "Proof", "Claimable ORE"
+        )
+        .unwrap();
+        for miner in &self.miners {
+            let status = if miner.registered {
+                "registered"
+            } else {
+                "unregistered"
+            };
+            writeln!(
+                out,
+                "{:<44} {:>14} {:<12} {:>16}",
+                miner.miner, miner.sol, status, miner.claimable
+            )
+            .unwrap();
+        }
+        writeln!(
+            out,
+            "{:<44} {:>14} {:<12} {:>16}",
+            format!("Total ({} miners)", self.miners.len()),
+            self.total_sol,
+            "",
+            self.total_claimable
+        )
+        .unwrap();
+        out
+    }
+
+    fn plain(&self) -> String {
+        let mut out = String::new();
+        for miner in &self.miners {
+            writeln!(
+                out,
+                "{}\t{}\t{}\t{}",
+                miner.miner, miner.sol, miner.registered, miner.claimable
+            )
+            .unwrap();
+        }
+        out
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct TreasuryOutput {
+    pub address: String,
+    pub admin: String,
+    /// The 32 byte target in hex.
+    pub difficulty: String,
+    pub last_reset_at: i64,
+    pub reward_rate: Amount,
+    pub total_claimed_rewards: Amount,
+}
+
+impl From<&Treasury> for TreasuryOutput {
+    fn from(treasury: &Treasury) -> Self {
+        TreasuryOutput {
+            address: TREASURY_ADDRESS.to_string(),
+            admin: treasury.admin.to_string(),
+            difficulty: hex::encode(treasury.difficulty.0),
+            last_reset_at: treasury.last_reset_at,
+            reward_rate: Amount::ore(treasury.reward_rate),
+            total_claimed_rewards: Amount::ore(treasury.total_claimed_rewards),
+        }
+    }
+}
+
+impl Render for TreasuryOutput {
+    fn table(&self) -> String {
+        let mut out = String::new();
+        writeln!(out, "{:<22} {}", "Treasury", self.address).unwrap();
+        writeln!(out, "{:<22} {}", "Admin", self.admin).unwrap();
+        writeln!(out, "{:<22} {}", "Difficulty", self.difficulty).unwrap();
+        writeln!(out, "{:<22} {}", "Last reset at", self.last_reset_at).unwrap();
+        writeln!(out, "{:<22} {}", "Reward rate ORE", self.reward_rate).unwrap();
+        writeln!(
+            out,
+            "{:<22} {}",
+            "Total claimed ORE", self.total_claimed_rewards
+        )
+        .unwrap();
+        out
+    }
+
+    fn plain(&self) -> String {
+        format!(
+            "{}\t{}\t{}\t{}\t{}\n",
+            self.admin,
+            self.difficulty,
+            self.last_reset_at,
+            self.reward_rate,
+            self.total_claimed_rewards
+        )
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct BusOutput {
+    pub id: usize,
+    pub address: String,
+    pub rewards: Amount,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct BussesOutput {
+    pub busses: Vec<BusOutput>,
+    pub total: Amount,
+}
+
+impl From<&EpochWindow> for BussesOutput {
+    fn from(window: &EpochWindow) -> Self {
+        BussesOutput {
+            busses: window
+                .bus_rewards
+                .iter()
+                .enumerate()
+                .map(|(id, rewards)| BusOutput {
+                    id,
+                    address: BUS_ADDRESSES[id].to_string(),
+                    rewards: Amount::ore(*rewards),
+                })
+                .collect(),
+            total: Amount::ore(window.bus_rewards.iter().sum()),
+        }
+    }
+}
+
+impl Render for BussesOutput {
+    fn table(&self) -> String {
+        let mut out = String::new();
+        writeln!(out, "{:<4} {:<44} {:>14}", "Bus", "Address", "Rewards ORE").unwrap();
+        for bus in &self.busses {
+            writeln!(out, "{:<4} {:<44} {:>14}", bus.id, bus.address, bus.rewards).unwrap();
+        }
+        writeln!(out, "{:<49} {:>14}", "Total", self.total).unwrap();
+        out
+    }
+
+    fn plain(&self) -> String {
+        let mut out = String::new();
+        for bus in &self.busses {
+            writeln!(out, "{}\t{}\t{}", bus.id, bus.address, bus.rewards).unwrap();
+        }
+        out
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct EpochOutput {
+    pub last_reset_at: i64,
+    /// Until the treasury can be reset and the busses refilled.
+    pub seconds_left: i64,
+    pub reward_rate: Amount,
+}
+
+/// The epoch and every miner's proof, for `mine status`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct MineStatusOutput {
+    pub epoch: EpochOutput,
+    pub busses: BussesOutput,
+    pub miners: MinersOutput,
+}
+
+impl MineStatusOutput {
+    pub fn new(window: &EpochWindow, accounts: &[MinerAccount]) -> Self {
+        MineStatusOutput {
+            epoch: EpochOutput {
+                last_reset_at: window.last_reset_at,
+                seconds_left: window.seconds_left(),
+                reward_rate: Amount::ore(window.reward_rate),
+            },
+            busses: window.into(),
+            miners: MinersOutput::new(accounts),
+        }
+    }
+}
+
+impl Render for MineStatusOutput {
+    fn table(&self) -> String {
+        let mut out = String::new();
+        writeln!(
+            out,
+            "{:<16} {}s",
+            "Epoch resets in", self.epoch.seconds_left
+        )
+        .unwrap();
+        writeln!(out, "{:<16} {}", "Reward rate ORE", self.epoch.reward_rate).unwrap();
+        writeln!(out, "{:<16} {}", "Bus rewards ORE", self.busses.total).unwrap();
+        writeln!(out).unwrap();
+        writeln!(
+            out,
+            "{:<44} {:>14} {:>16} {:>16} {:>12}",
+            "Miner", "SOL", "Claimable ORE", "Total ORE", "Hashes"
+        )
+        .unwrap();
+        for miner in &self.miners.miners {
+            writeln!(
+                out,
+                "{:<44} {:>14} {:>16} {:>16} {:>12}",
+                miner.miner, miner.sol, miner.claimable, miner.total_rewards, miner.total_hashes
+            )
+            .unwrap();
+        }
+        out
+    }
+
+    fn plain(&self) -> String {
+        let mut out = String::new();
+        for miner in &self.miners.miners {
+            writeln!(
+                out,
+                "{}\t{}\t{}\t{}\t{}",
+                miner.miner, miner.sol, miner.claimable, miner.total_rewards, miner.total_hashes
+            )
+            .unwrap();
+        }
+        out
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct ClaimOutput {
+    pub miner: String,
+    pub amount: Amount,
+    #[serde(flatten)]
+    pub transaction: TransactionOutput,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct ClaimsOutput {
+    pub beneficiary: String,
+    pub claims: Vec<ClaimOutput>,
+    /// Of the claims that landed.
+    pub total: Amount,
+}
+
+impl ClaimsOutput {
+    pub fn new(beneficiary: Pubkey, claims: Vec<(Pubkey, u64, Result<Signature>)>) -> Self {
+        let total = claims
+            .iter()
+            .filter(|(_, _, result)| result.is_ok())
+            .map(|(_, amount, _)| amount)
+            .sum();
+        ClaimsOutput {
+            beneficiary: beneficiary.to_string(),
+            claims: claims
+                .iter()
+                .map(|(miner, amount, result)| ClaimOutput {
+                    miner: miner.to_string(),
+                    amount: Amount::ore(*amount),
+                    transaction: TransactionOutput::new(result),
+                })
+                .collect(),
+            total: Amount::ore(total),
+        }
+    }
+}
+
+impl Render for ClaimsOutput {
+    fn table(&self) -> String {
+        let mut out = String::new();
+        for claim in &self.claims {
+            let action = match claim.transaction.error {
+                Some(_) => "Failed",
+                None => "Claimed",
+            };
+            writeln!(
+                out,
+                "{} {} {} ORE: {}",
+                action, claim.miner, claim.amount, claim.transaction
+            )
+            .unwrap();
+        }
+        writeln!(out, "Claimed {} ORE to {}", self.total, self.beneficiary).unwrap();
+        out
+    }
+
+    fn plain(&self) -> String {
+        let mut out = String::new();
+        for claim in &self.claims {
+            writeln!(
+                out,
+                "{}\t{}\t{}",
+                claim.miner,
+                claim.amount,
+                claim.transaction.plain()
+            )
+            .unwrap();
+        }
+        out
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct TransferOutput {
+    pub miner: String,
+    pub amount: Amount,
+    #[serde(flatten)]
+    pub transaction: TransactionOutput,
+}
+
+/// The transfers of `fund` or `sweep`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct TransfersOutput {
+    #[serde(skip)]
+    pub action: &'static str,
+    pub transfers: Vec<TransferOutput>,
+    /// Of the transfers that landed.
+    pub total: Amount,
+    pub transactions: usize,
+}
+
+impl TransfersOutput {
+    pub fn new(action: &'static str, results: Vec<(Vec<Transfer>, Result<Signature>)>) -> Self {
+        let mut transfers = vec![];
+        let mut total = 0;
+        for (batch, result) in &results {
+            for transfer in batch {
+                if result.is_ok() {
+                    total += transfer.lamports;
+                }
+                transfers.push(TransferOutput {
+                    miner: transfer.miner.to_string(),
+                    amount: Amount::sol(transfer.lamports),
+                    transaction: TransactionOutput::new(result),
+                });
+            }
+        }
+        TransfersOutput {
+            action,
+            transfers,
+            total: Amount::sol(total),
+            transactions: results.len(),
+        }
+    }
+}
+
+impl Render for TransfersOutput {
+    fn table(&self) -> String {
+        let mut out = String::new();
+        for transfer in &self.transfers {
+            let action = match transfer.transaction.error {
+                Some(_) => "Failed",
+                None => self.action,
+            };
+            writeln!(
+                out,
+                "{} {} {} SOL: {}",
+                action, transfer.miner, transfer.amount, transfer.transaction
+            )
+            .unwrap();
+        }
+        writeln!(
+            out,
+            "{} {} SOL in {} transactions",
+            self.action, self.total, self.transactions
+        )
+        .unwrap();
+        out
+    }
+
+    fn plain(&self) -> String {
+        let mut out = String::new();
+        for transfer in &self.transfers {
+            writeln!(
+                out,
+                "{}\t{}\t{}",
+                transfer.miner,
+                transfer.amount,
+                transfer.transaction.plain()
+            )
+            .unwrap();
+        }
+        out
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct RegistrationOutput {
+    pub miner: String,
+    #[serde(flatten)]
+    pub transaction: TransactionOutput,
+}
+
+/// The registrations of `register` and every miner afterwards.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct RegisterOutput {
+    pub registrations: Vec<RegistrationOutput>,
+    pub miners: MinersOutput,
+}
+
+impl RegisterOutput {
+    pub fn new(results: Vec<(Vec<Pubkey>, Result<Signature>)>, accounts: &[MinerAccount]) -> Self {
+        let registrations = results
+            .iter()
+            .flat_map(|(pubkeys, result)| {
+                pubkeys.iter().map(|pubkey| RegistrationOutput {
+                    miner: pubkey.to_string(),
+                    transaction: TransactionOutput::new(result),
+                })
+            })
+            .collect();
+        RegisterOutput {
+            registrations,
+            miners: MinersOutput::new(accounts),
+        }
+    }
+}
+
+impl Render for RegisterOutput {
+    fn table(&self) -> String {
+        let mut out = String::new();
+        if self.registrations.is_empty() {
+            writeln!(out, "Every miner is registered").unwrap();
+        }
+        for registration in &self.registrations {
+            let action = match registration.transaction.error {
+                Some(_) => "failed",
+                None => "registered",
+            };
+            writeln!(
+                out,
+                "{} {}: {}",
+                registration.miner, action, registration.transaction
+            )
+            .unwrap();
+        }
+        out.push_str(&self.miners.table());
+        out
+    }
+
+    fn plain(&self) -> String {
+        let mut out = String::new();
+        for registration in &self.registrations {
+            writeln!(
+                out,
+                "{}\t{}",
+                registration.miner,
+                registration.transaction.plain()
+            )
+            .unwrap();
+        }
+        out
+    }
+}
+
+/// The rent `close` would reclaim. Ore v1 cannot close proofs, so nothing is closed yet.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct CloseOutput {
+    pub miners: MinersOutput,
+    pub proofs: usize,
+    pub reclaimable: Amount,
+    pub closed: usize,
+}
+
+impl CloseOutput {
+    pub fn new(accounts: &[MinerAccount]) -> Self {
+        let registered = accounts.iter().filter(|account| account.proof.is_some());
+        CloseOutput {
+            miners: MinersOutput::new(accounts),
+            proofs: registered.clone().count(),
+            reclaimable: Amount::sol(registered.map(|account| account.proof_lamports).sum()),
+            closed: 0,
+        }
+    }
+}
+
+impl Render for CloseOutput {
+    fn table(&self) -> String {
+        let mut out = self.miners.table();
+        for miner in self.miners.miners.iter().filter(|miner| miner.registered) {
+            writeln!(
+                out,
+                "{} proof holds {} SOL of rent",
+                miner.miner, miner.proof_rent
+            )
+            .unwrap();
+        }
+        writeln!(
+            out,
+            "{} SOL of rent is reclaimable from {} proofs, but Ore v1 has no instruction to close a proof, so nothing was closed",
+            self.reclaimable, self.proofs
+        )
+        .unwrap();
+        out
+    }
+
+    fn plain(&self) -> String {
+        let mut out = String::new();
+        for miner in self.miners.miners.iter().filter(|miner| miner.registered) {
+            writeln!(out, "{}\t{}", miner.miner, miner.proof_rent).unwrap();
+        }
+        out
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct SummaryOutput {
+    /// The miner, `owner`, or the UTC day.
+    pub key: String,
+    pub found: u64,
+    pub landed: u64,
+    pub failed: u64,
+    pub earned: Amount,
+    pub claimed: Amount,
+    pub fees: Amount,
+}
+
+impl SummaryOutput {
+    fn new(key: String, summary: &Summary) -> Self {
+        SummaryOutput {
+            key,
+            found: summary.found,
+            landed: summary.landed,
+            failed: summary.failed,
+            earned: Amount::ore(summary.earned),
+            claimed: Amount::ore(summary.claimed),
+            fees: Amount::sol(summary.fees),
+        }
+    }
+
+    /// ORE earned per SOL of fees.
+    fn ore_per_sol(&self) -> String {
+        match self.fees.raw {
+            0 => String::from("-"),
+            fees => format!(
+                "{:.4}",
+                self.earned.raw as f64
+                    / 10f64.powi(TOKEN_DECIMALS as i32)
+                    / (fees as f64 / LAMPORTS_PER_SOL as f64)
+            ),
+        }
+    }
+}
+
+/// The ledger's totals for `stats`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct StatsOutput {
+    pub miners: Vec<SummaryOutput>,
+    pub days: Vec<SummaryOutput>,
+}
+
+impl StatsOutput {
+    pub fn new(miners: Vec<(String, Summary)>, days: Vec<(String, Summary)>) -> Self {
+        let summaries = |rows: Vec<(String, Summary)>| {
+            rows.into_iter()
+                .map(|(key, summary)| SummaryOutput::new(key, &summary))
+                .collect()
+        };
+        StatsOutput {
+            miners: summaries(miners),
+            days: summaries(days),
+        }
+    }
+}
+
+impl Render for StatsOutput {
+    fn table(&self) -> String {
+        let mut out = String::new();
+        for (key, rows) in [("Miner", &self.miners), ("Day", &self.days)] {
+            if key == "Day" {
+                writeln!(out).unwrap();
+            }
+            writeln!(
+                out,
+                "{:<44} {:>8} {:>8} {:>8} {:>14} {:>14} {:>14} {:>10}",
+                key,
+                "Found",
+                "Landed",
+                "Failed",
+                "Earned ORE",
+                "Claimed ORE",
+                "Fees SOL",
+                "ORE/SOL"
+            )
+            .unwrap();
+            let mut total = Summary::default();
+            for row in rows.iter() {
+                total.found += row.found;
+                total.landed += row.landed;
+                total.failed += row.failed;
+                total.earned += row.earned.raw;
+                total.claimed += row.claimed.raw;
+                total.fees += row.fees.raw;
+                write_summary(&mut out, row);
+            }
+            write_summary(&mut out, &SummaryOutput::new("Total".into(), &total));
+        }
+        out
+    }
+
+    fn plain(&self) -> String {
+        let mut out = String::new();
+        let rows = self.miners.iter().map(|row| ("miner", row));
+        for (grouping, row) in rows.chain(self.days.iter().map(|row| ("day", row))) {
+            writeln!(
+                out,
+                "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                grouping,
+                row.key,
+                row.found,
+                row.landed,
+                row.failed,
+                row.earned,
+                row.claimed,
+                row.fees
+            )
+            .unwrap();
+        }
+        out
+    }
+}
+
+fn write_summary(out: &mut String, row: &SummaryOutput) {
+    writeln!(
+        out,
+        "{:<44} {:>8} {:>8} {:>8} {:>14} {:>14} {:>14} {:>10}",
+        row.key,
+        row.found,
+        row.landed,
+        row.failed,
+        row.earned,
+        row.claimed,
+        row.fees,
+        row.ore_per_sol()
+    )
+    .unwrap();
+}
+
+/// The keypairs `miners new` wrote.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct NewMinersOutput {
+    pub dir: String,
+    pub miners: Vec<String>,
+}
+
+impl Render for NewMinersOutput {
+    fn table(&self) -> String {
+        self.plain()
+    }
+
+    fn plain(&self) -> String {
+        let mut out = String::new();
+        for miner in &self.miners {
+            writeln!(out, "{}", miner).unwrap();
+        }
+        out
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyAction {
+    Encrypted,
+    Decrypted,
+    Unlocked,
+}
+
+/// A keystore or keypair file `keys` read or wrote, with its pubkey or why it failed.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct KeyOutput {
+    pub path: String,
+    pub pubkey: Option<String>,
+    pub error: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct KeysOutput {
+    pub action: KeyAction,
+    pub keys: Vec<KeyOutput>,
+}
+
+impl Render for KeysOutput {
+    fn table(&self) -> String {
+        let mut out = String::new();
+        for key in &self.keys {
+            match (&key.pubkey, &key.error, self.action) {
+                (_, Some(error), _) => writeln!(out, "{} failed: {}", key.path, error),
+                (Some(pubkey), None, KeyAction::Encrypted) => {
+                    writeln!(out, "{} encrypted to {}", pubkey, key.path)
+                }
+                (Some(pubkey), None, KeyAction::Decrypted) => {
+                    writeln!(out, "{} decrypted to {}", pubkey, key.path)
+                }
+                (pubkey, None, _) => {
+                    writeln!(out, "{} {}", key.path, pubkey.as_deref().unwrap_or("-"))
+                }
+            }
+            .unwrap();
+        }
+        out
+    }
+
+    fn plain(&self) -> String {
+        let mut out = String::new();
+        for key in &self.keys {
+            let pubkey = key.pubkey.as_deref().unwrap_or("-");
+            writeln!(out, "{}\t{}", key.path, pubkey).unwrap();
+        }
+        out
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AdminAction {
+    Initialize,
+    UpdateAdmin,
+    UpdateDifficulty,
+}
+
+/// An admin instruction and the treasury it was checked against. Without a signature it was
+/// declined, or `initialize` found the program already initialized.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct AdminOutput {
+    pub action: AdminAction,
+    /// The admin initialized with, the new admin or the new difficulty.
+    pub value: String,
+    /// The treasury before the instruction, `None` before the program is initialized.
+    pub treasury: Option<TreasuryOutput>,
+    pub signature: Option<String>,
+}
+
+impl Render for AdminOutput {
+    fn table(&self) -> String {
+        let done = match self.action {
+            AdminAction::Initialize => "Initialized",
+            AdminAction::UpdateAdmin => "Admin updated",
+            AdminAction::UpdateDifficulty => "Difficulty updated",
+        };
+        match (&self.signature, self.action, &self.treasury) {
+            (Some(signature), _, _) => format!("{}: {}\n", done, signature),
+            (None, AdminAction::Initialize, Some(_)) => {
+                "The program is already initialized\n".into()
+            }
+            (None, _, _) => "Declined\n".into(),
+        }
+    }
+
+    fn plain(&self) -> String {
+        format!("{}\n", self.signature.as_deref().unwrap_or("-"))
+    }
+}
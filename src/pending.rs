@@ -0,0 +1,128 @@
+use std::{
+    collections::HashMap,
+    fs,
+    io::ErrorKind,
+    path::Path,
+    str::FromStr,
+    sync::Mutex,
+};
+
+use serde::{Deserialize, Serialize};
+use solana_sdk::{keccak::Hash as KeccakHash, pubkey::Pubkey, signer::Signer};
+use tracing::info;
+
+use crate::{
+    mine::{SignedWork, Work},
+    scheduler::Scheduler,
+};
+
+/// On-disk form of a `SignedWork` that was found but never confirmed. Only public data is stored,
+/// the miner's keypair is looked up again on resume.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PendingWork {
+    pub miner: String,
+    pub challenge: String,
+    pub hash: String,
+    pub nonce: u64,
+    pub bus: usize,
+}
+
+impl PendingWork {
+    pub fn miner(&self) -> Option<Pubkey> {
+        Pubkey::from_str(&self.miner).ok()
+    }
+
+    pub fn challenge(&self) -> Option<KeccakHash> {
+        KeccakHash::from_str(&self.challenge).ok()
+    }
+
+    pub fn work(&self) -> Option<Work> {
+        let hash = KeccakHash::from_str(&self.hash).ok()?;
+        Some(Work::ToBeProved(hash, self.nonce))
+    }
+
+    /// Takes the miner out of the scheduler to submit the work again. Work for a challenge the
+    /// miner has moved past is discarded and the miner left queued.
+    pub fn resume(&self, scheduler: &Scheduler) -> Option<SignedWork> {
+        let (miner, challenge, work) = (self.miner()?, self.challenge()?, self.work()?);
+        let (miner, log) = scheduler.take(&miner)?;
+        if log.last_work.hash().ne(&challenge) {
+            info!(miner = %miner.pubkey(), %challenge, "Discarding stale pending work");
+            scheduler.push(miner, log);
+            return None;
+        }
+
+        info!(miner = %miner.pubkey(), %challenge, "Resuming pending work");
+        Some(SignedWork {
+            signer: miner,
+            challenge,
+            bus: self.bus,
+            work,
+        })
+    }
+}
+
+impl From<&SignedWork> for PendingWork {
+    fn from(signed_work: &SignedWork) -> Self {
+        let (hash, nonce) = match &signed_work.work {
+            Work::ToBeProved(hash, nonce) => (hash, *nonce),
+            Work::Proved(hash) => (hash, 0),
+        };
+        PendingWork {
+            miner: signed_work.signer.pubkey().to_string(),
+            challenge: signed_work.challenge.to_string(),
+            hash: hash.to_string(),
+            nonce,
+            bus: signed_work.bus,
+        }
+    }
+}
+
+/// Work that has left the hashers but has not been confirmed yet, keyed by miner.
+#[derive(Default)]
+pub struct PendingWorks {
+    works: Mutex<HashMap<Pubkey, SignedWork>>,
+}
+
+impl PendingWorks {
+    pub fn insert(&self, signed_work: &SignedWork) {
+        self.works
+            .lock()
+            .unwrap()
+            .insert(signed_work.signer.pubkey(), signed_work.clone());
+    }
+
+    pub fn remove(&self, miner: &Pubkey) {
+        self.works.lock().unwrap().remove(miner);
+    }
+
+    pub fn len(&self) -> usize {
+        self.works.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        let works = self
+            .works
+            .lock()
+            .unwrap()
+            .values()
+            .map(PendingWork::from)
+            .collect::<Vec<_>>();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_vec_pretty(&works)?)
+    }
+
+    pub fn load(path: &Path) -> std::io::Result<Vec<PendingWork>> {
+        match fs::read(path) {
+            Ok(data) => Ok(serde_json::from_slice(&data)?),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(vec![]),
+            Err(err) => Err(err),
+        }
+    }
+}
@@ -1,33 +1,589 @@
-use std::{collections::VecDeque, sync::mpsc};
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 
-use solana_sdk::keccak::{hashv, Hash};
-use threadpool::ThreadPool;
+use flume::{Receiver, SendError, Sender};
+use ore::{state::Proof, BUS_ADDRESSES, BUS_COUNT};
+use solana_sdk::{keccak::Hash as KeccakHash, pubkey::Pubkey, signer::Signer};
+use tokio::{
+    sync::Mutex,
+    task::{JoinHandle, JoinSet},
+    time::{sleep, timeout},
+};
+use tracing::{debug, error, info, info_span, warn, Instrument};
 
-use crate::mine::{Miner, Work};
+use crate::{
+    balance::{BalanceConfig, BalanceGuard, CHECK_INTERVAL},
+    errors::{CliError, Error, Result},
+    events::{Event, EventBus},
+    factory::Ore,
+    ledger::TransactionKind,
+    metrics::{Metrics, MinerState},
+    mine::{MineLine, Miner, MinerLog, SignedWork, Work},
+    pending::PendingWorks,
+    profitability::{ProfitConfig, ProfitGuard, Verdict},
+    rpc::endpoint_host,
+    scheduler::{EpochWindow, Scheduler},
+    transaction::{SendPolicy, Transaction},
+    verify::verify_work,
+};
 
-pub struct Pipeline<'a> {
-    miners: &'a Vec<Miner>,
-    mine_threads: ThreadPool,
-    mine_deque: VecDeque<Work>,
+pub const CU_LIMIT_MINE: u32 = 3200;
+
+const WINDOW_REFRESH: Duration = Duration::from_secs(5);
+
+#[derive(Clone, Debug)]
+pub struct PipelineConfig {
+    /// Priority fee, resends and confirmation polling of mine transactions.
+    pub send: SendPolicy,
+    /// How long `stop` waits for in-flight confirmations before giving up on them.
+    pub drain_timeout: Duration,
+    /// Where unconfirmed work is saved on stop and resumed from on start.
+    pub pending_path: Option<PathBuf>,
+    /// Holds, cheapens or slows mine transactions that cost more than they earn, off when unset.
+    pub profit: Option<ProfitConfig>,
+    /// Alerts on, tops up and stops at a low fee payer balance, off when unset.
+    pub balance: Option<BalanceConfig>,
 }
 
-impl<'a> Pipeline<'a> {
-    pub fn new(mine_workers: usize, miners: &'a Vec<Miner>) -> Self {
-        let mine_threads = ThreadPool::new(mine_workers);
-        let mine_deque: VecDeque<Work> = VecDeque::new();
+impl Default for PipelineConfig {
+    fn default() -> Self {
+        PipelineConfig {
+            send: SendPolicy::default(),
+            drain_timeout: Duration::from_secs(30),
+            pending_path: None,
+            profit: None,
+            balance: None,
+        }
+    }
+}
 
+/// Owns a running mining session: hashing slots feed found work to a submitter task which lands
+/// it on-chain and requeues each miner with its next challenge.
+pub struct Pipeline {
+    ore: Arc<Ore>,
+    config: PipelineConfig,
+    stop: Arc<AtomicBool>,
+    pending: Arc<PendingWorks>,
+    metrics: Arc<Metrics>,
+    scheduler: Mutex<Option<Arc<Scheduler>>>,
+    task: Mutex<Option<JoinHandle<Result<()>>>>,
+}
+
+impl Pipeline {
+    pub fn new(ore: Arc<Ore>, config: PipelineConfig) -> Self {
         Pipeline {
-            miners,
-            mine_threads,
-            mine_deque,
+            ore,
+            config,
+            stop: Arc::new(AtomicBool::new(false)),
+            pending: Arc::new(PendingWorks::default()),
+            metrics: Arc::new(Metrics::default()),
+            scheduler: Mutex::new(None),
+            task: Mutex::new(None),
+        }
+    }
+
+    pub fn ore(&self) -> &Arc<Ore> {
+        &self.ore
+    }
+
+    pub fn metrics(&self) -> &Arc<Metrics> {
+        &self.metrics
+    }
+
+    pub fn config(&self) -> &PipelineConfig {
+        &self.config
+    }
+
+    /// Pauses or resumes hashing for a miner of the running session.
+    pub async fn set_paused(&self, miner: Pubkey, paused: bool) {
+        if let Some(scheduler) = self.scheduler.lock().await.as_ref() {
+            match paused {
+                true => scheduler.pause(miner),
+                false => scheduler.resume(&miner),
+            }
         }
     }
 
-    pub fn run(&mut self) {
-        let (sender, receiver) = mpsc::channel::<Work>();
+    pub async fn is_paused(&self, miner: &Pubkey) -> bool {
+        self.scheduler
+            .lock()
+            .await
+            .as_ref()
+            .is_some_and(|scheduler| scheduler.is_paused(miner))
+    }
+
+    pub async fn is_running(&self) -> bool {
+        self.task
+            .lock()
+            .await
+            .as_ref()
+            .is_some_and(|task| !task.is_finished())
+    }
+
+    /// Registers missing proofs, queues every miner, resubmits saved work whose challenge is
+    /// still current and starts hashing and submitting.
+    pub async fn start(&self) -> Result<()> {
+        let mut task = self.task.lock().await;
+        if task.as_ref().is_some_and(|task| !task.is_finished()) {
+            return Err(Error::CliError(CliError::PipelineRunning));
+        }
+        self.stop.store(false, Ordering::SeqCst);
+
+        let ore = self.ore.clone();
+        // Every transaction is paid by the fee payer, miners whose keypair is the owner included.
+        let balance = self.config.balance.clone().map(|config| {
+            Arc::new(BalanceGuard::new(config, vec![ore.fee_payer().pubkey()]))
+        });
+        if let Some(balance) = &balance {
+            if let Some(wallet) = balance.check(&ore, &self.config.send).await? {
+                return Err(Error::CliError(CliError::BalanceBelowFloor(wallet)));
+            }
+        }
+        let treasury = ore.get_treasury().await?;
+        let window = ore.get_epoch_window().await?;
+        self.metrics.record_window(&window);
+        let scheduler = Arc::new(Scheduler::new(ore.workers.slots, window));
+        let backend = ore.workers.build_backend()?;
+        info!(?backend, "Hashing");
+
+        let miners = ore.get_or_register_proofs(&self.config.send).await?;
+        let mineline = MineLine::init(miners, scheduler.clone(), backend, self.metrics.clone());
+        info!(miners = ore.miners.len(), "Miners initialized");
+
+        let guard = self
+            .config
+            .profit
+            .clone()
+            .map(|config| Arc::new(ProfitGuard::new(config)));
+        if let Some(guard) = &guard {
+            guard.refresh().await;
+        }
+
+        let (sender, receiver) = flume::unbounded();
+        self.resume(&scheduler, &sender);
+        let difficulty: KeccakHash = treasury.difficulty.into();
+        let slots = mineline.spawn(difficulty, sender, self.stop.clone());
+
+        let submitter = Arc::new(Submitter {
+            ore: ore.clone(),
+            config: self.config.clone(),
+            scheduler: scheduler.clone(),
+            pending: self.pending.clone(),
+            metrics: self.metrics.clone(),
+            stop: self.stop.clone(),
+            difficulty,
+            guard: guard.clone(),
+            balance: balance.clone(),
+            failures: std::sync::Mutex::new(HashMap::new()),
+        });
+        let refresher = tokio::spawn(refresh_window(
+            ore.clone(),
+            scheduler.clone(),
+            self.metrics.clone(),
+            self.config.send.events.clone(),
+            self.stop.clone(),
+        ));
+        let pricer = guard.map(|guard| tokio::spawn(refresh_price(guard, self.stop.clone())));
+        let watcher = balance.clone().map(|balance| {
+            tokio::spawn(watch_balance(
+                ore.clone(),
+                balance,
+                self.config.send.clone(),
+                scheduler.clone(),
+                self.stop.clone(),
+            ))
+        });
+        *task = Some(tokio::spawn(async move {
+            let result = submitter.run(receiver).await;
+            refresher.abort();
+            for task in pricer.into_iter().chain(watcher) {
+                task.abort();
+            }
+            for slot in slots {
+                slot.join().map_err(|_| Error::CliError(CliError::TaskFailed))?;
+            }
+            match balance.and_then(|balance| balance.depleted()) {
+                Some(wallet) => Err(Error::CliError(CliError::BalanceBelowFloor(wallet))),
+                None => result,
+            }
+        }));
+        *self.scheduler.lock().await = Some(scheduler);
+
+        Ok(())
+    }
+
+    /// Waits until the pipeline stops on its own or through `stop`.
+    pub async fn wait(&self) -> Result<()> {
+        let mut task = self.task.lock().await;
+        let result = match task.as_mut() {
+            Some(handle) => {
+                let result = handle.await;
+                *task = None;
+                result.map_err(|_| Error::CliError(CliError::TaskFailed))?
+            }
+            None => Ok(()),
+        };
+        self.save_pending();
+        result
+    }
+
+    /// Stops hashing and waits up to `drain_timeout` for in-flight confirmations. Whatever has
+    /// not landed by then is saved to `pending_path`.
+    pub async fn stop(&self) -> Result<()> {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(scheduler) = self.scheduler.lock().await.take() {
+            scheduler.close();
+        }
+
+        match timeout(self.config.drain_timeout, self.wait()).await {
+            Ok(result) => result,
+            Err(_) => {
+                warn!(
+                    timeout = ?self.config.drain_timeout,
+                    abandoned = self.pending.len(),
+                    "Drain timed out, abandoning submissions"
+                );
+                if let Some(handle) = self.task.lock().await.take() {
+                    handle.abort();
+                }
+                self.save_pending();
+                Ok(())
+            }
+        }
+    }
+
+    fn resume(&self, scheduler: &Scheduler, sender: &Sender<SignedWork>) {
+        let Some(path) = &self.config.pending_path else {
+            return;
+        };
+        let saved = match PendingWorks::load(path) {
+            Ok(saved) => saved,
+            Err(err) => {
+                warn!(?path, error = %err, "Failed to load pending work");
+                return;
+            }
+        };
+
+        for signed_work in saved.iter().filter_map(|saved_work| saved_work.resume(scheduler)) {
+            if let Err(SendError(signed_work)) = sender.send(signed_work) {
+                warn!(miner = %signed_work.signer.pubkey(), "Submitter is gone, keeping work pending");
+                self.pending.insert(&signed_work);
+            }
+        }
+    }
+
+    fn save_pending(&self) {
+        let Some(path) = &self.config.pending_path else {
+            return;
+        };
+        match self.pending.save(path) {
+            Ok(()) if !self.pending.is_empty() => {
+                info!(count = self.pending.len(), ?path, "Saved pending work")
+            }
+            Ok(()) => {}
+            Err(err) => warn!(?path, error = %err, "Failed to save pending work"),
+        }
+    }
+}
+
+/// Refreshes the epoch window, announcing resets, drained busses and an unreachable RPC once
+/// each until they change again. An unreachable endpoint is swapped for the next in the pool.
+async fn refresh_window(
+    ore: Arc<Ore>,
+    scheduler: Arc<Scheduler>,
+    metrics: Arc<Metrics>,
+    events: Option<Arc<EventBus>>,
+    stop: Arc<AtomicBool>,
+) {
+    let mut previous = scheduler.window();
+    let mut drained = drained_busses(&previous);
+    let mut down = false;
+    while !stop.load(Ordering::Relaxed) {
+        sleep(WINDOW_REFRESH).await;
+        match ore.get_epoch_window().await {
+            Ok(window) => {
+                down = false;
+                metrics.record_window(&window);
+                if let Some(events) = &events {
+                    if window.last_reset_at != previous.last_reset_at {
+                        events.emit(Event::EpochReset {
+                            last_reset_at: window.last_reset_at,
+                            reward_rate: window.reward_rate,
+                        });
+                    }
+                    let now_drained = drained_busses(&window);
+                    for bus in 0..BUS_COUNT {
+                        if now_drained[bus] && !drained[bus] {
+                            events.emit(Event::BusDrained {
+                                bus,
+                                rewards: window.bus_rewards[bus],
+                            });
+                        }
+                    }
+                    drained = now_drained;
+                }
+                previous = window.clone();
+                scheduler.update_window(window);
+            }
+            Err(err) => {
+                warn!(error = ?err, "Failed to refresh epoch window");
+                let url = ore.get_client(None).url();
+                if let (Some(events), false) = (&events, down) {
+                    events.emit(Event::RpcDown {
+                        endpoint: endpoint_host(&url).to_string(),
+                        error: err.to_string(),
+                    });
+                }
+                down = true;
+                if let Some(next) = ore.rpc_pool.fail_over(&url) {
+                    warn!(endpoint = endpoint_host(&next), "Switching RPC endpoint");
+                }
+            }
+        }
+    }
+}
+
+/// Busses with less left than one proof's reward, before the epoch ends.
+fn drained_busses(window: &EpochWindow) -> [bool; BUS_COUNT] {
+    let live = window.seconds_left() > 0;
+    std::array::from_fn(|bus| live && window.bus_rewards[bus] < window.reward_rate)
+}
+
+async fn refresh_price(guard: Arc<ProfitGuard>, stop: Arc<AtomicBool>) {
+    while !stop.load(Ordering::Relaxed) {
+        sleep(guard.config.refresh).await;
+        guard.refresh().await;
+    }
+}
+
+/// Checks the fee payers every `CHECK_INTERVAL` and stops the pipeline once one is at the floor.
+async fn watch_balance(
+    ore: Arc<Ore>,
+    balance: Arc<BalanceGuard>,
+    policy: SendPolicy,
+    scheduler: Arc<Scheduler>,
+    stop: Arc<AtomicBool>,
+) {
+    while !stop.load(Ordering::Relaxed) {
+        sleep(CHECK_INTERVAL).await;
+        match balance.check(&ore, &policy).await {
+            Ok(Some(wallet)) => {
+                error!(%wallet, "Stopping, the fee payer cannot pay for more transactions");
+                stop.store(true, Ordering::SeqCst);
+                scheduler.close();
+            }
+            Ok(None) => {}
+            Err(err) => warn!(error = ?err, "Failed to check fee payer balances"),
+        }
+    }
+}
+
+/// State shared by every in-flight submission.
+struct Submitter {
+    ore: Arc<Ore>,
+    config: PipelineConfig,
+    scheduler: Arc<Scheduler>,
+    pending: Arc<PendingWorks>,
+    metrics: Arc<Metrics>,
+    stop: Arc<AtomicBool>,
+    difficulty: KeccakHash,
+    guard: Option<Arc<ProfitGuard>>,
+    balance: Option<Arc<BalanceGuard>>,
+    /// Failed submissions in a row per miner.
+    failures: std::sync::Mutex<HashMap<Pubkey, usize>>,
+}
+
+impl Submitter {
+    async fn run(self: Arc<Self>, receiver: Receiver<SignedWork>) -> Result<()> {
+        let mut submissions = JoinSet::new();
+
+        while let Ok(signed_work) = receiver.recv_async().await {
+            self.pending.insert(&signed_work);
+            if let Some(ledger) = &self.config.send.ledger {
+                ledger.record_work(&signed_work);
+            }
+            let submitter = self.clone();
+            let span = info_span!(
+                "submit",
+                miner = %signed_work.signer.pubkey(),
+                challenge = %signed_work.challenge,
+                bus = signed_work.bus
+            );
+            submissions.spawn(
+                async move {
+                    if let Some(log) = submitter.submit(&signed_work).await {
+                        submitter.pending.remove(&signed_work.signer.pubkey());
+                        if !submitter.scheduler.is_closed() {
+                            let miner = signed_work.signer.pubkey();
+                            submitter.metrics.record_state(miner, MinerState::Queued);
+                            submitter.scheduler.push(signed_work.signer, log);
+                        }
+                    }
+                }
+                .instrument(span),
+            );
+        }
+
+        while submissions.join_next().await.is_some() {}
+
+        Ok(())
+    }
+
+    fn is_stopped(&self) -> bool {
+        self.stop.load(Ordering::Relaxed)
+    }
+
+    /// Lands the work and returns the miner's log for its next challenge. Returns `None` if the
+    /// pipeline stopped before the work was settled, leaving it pending.
+    async fn submit(&self, signed_work: &SignedWork) -> Option<MinerLog> {
+        let Work::ToBeProved(hash, nonce) = signed_work.work else {
+            return Some(MinerLog {
+                last_work: signed_work.work.clone(),
+                bus: signed_work.bus,
+            });
+        };
+
+        let miner = &signed_work.signer;
+        self.metrics.record_state(miner.pubkey(), MinerState::Submitting);
+
+        let proof = self.get_proof(signed_work).await?;
+        if let Err(rejection) = verify_work(signed_work, &proof.hash.into(), &self.difficulty) {
+            warn!(%rejection, "Rejected work");
+            self.metrics.record_rejected(rejection);
+            return Some(MinerLog::new(&proof, signed_work.bus));
+        }
+        self.metrics.record_verified();
+
+        let policy = self.admit(miner).await?;
+        debug!(nonce, %hash, "Submitting");
+        let instruction =
+            ore::instruction::mine(miner.pubkey(), BUS_ADDRESSES[signed_work.bus], hash.into(), nonce);
+        let mut transaction = Transaction::new(vec![instruction]);
+        transaction.set_cu_limit(CU_LIMIT_MINE);
+        transaction.set_kind(TransactionKind::Mine);
+
+        self.metrics.record_submission(miner.pubkey());
+        self.metrics.record_state(miner.pubkey(), MinerState::Confirming);
+        match transaction
+            .send_and_confirm(
+                self.ore.get_client(None),
+                &[&**miner],
+                Some(self.ore.fee_payer()),
+                &policy,
+                Some(&self.stop),
+            )
+            .await
+        {
+            Ok(signature) => {
+                info!(%signature, "Landed");
+                let fee = policy.priority_fee_lamports(CU_LIMIT_MINE);
+                self.metrics.record_landed(miner.pubkey(), signature, fee);
+                self.failures.lock().unwrap().remove(&miner.pubkey());
+                if let Some(events) = &self.config.send.events {
+                    events.emit(Event::ProofLanded {
+                        miner: miner.pubkey().to_string(),
+                        signature: signature.to_string(),
+                    });
+                }
+            }
+            Err(Error::CliError(CliError::Interrupted)) => return None,
+            Err(err) => {
+                warn!(kind = err.kind(), error = %err.to_string(), "Failed to land work");
+                self.metrics.record_failed(&err);
+                self.record_failure(miner.pubkey(), &err);
+            }
+        }
+
+        let proof = self.get_proof(signed_work).await?;
+        Some(MinerLog::new(&proof, signed_work.bus))
+    }
+
+    /// The policy to send the miner's work with once the profitability guard lets it through,
+    /// priced at the dynamic fee if there is one. Returns `None` if a fee payer is at the floor or
+    /// the pipeline stopped while the work was held back.
+    async fn admit(&self, miner: &Miner) -> Option<SendPolicy> {
+        if self.balance.as_ref().is_some_and(|balance| balance.depleted().is_some()) {
+            return None;
+        }
+        let send = self.config.send.with_current_fee(self.ore.get_client(None)).await;
+        let Some(guard) = &self.guard else {
+            return Some(send);
+        };
+        let signatures = match miner.pubkey() == self.ore.fee_payer().pubkey() {
+            true => 1,
+            false => 2,
+        };
         loop {
-            if self.mine_deque.len() < 10 {}
+            let reward_rate = self.scheduler.window().reward_rate;
+            match guard.judge(reward_rate, &send, signatures, CU_LIMIT_MINE) {
+                Verdict::Submit(priority_fee) => {
+                    return Some(SendPolicy {
+                        priority_fee,
+                        ..send
+                    })
+                }
+                Verdict::Delay(delay) => {
+                    sleep(delay).await;
+                    return (!self.is_stopped()).then_some(send);
+                }
+                Verdict::Hold => {
+                    self.metrics.record_state(miner.pubkey(), MinerState::Held);
+                    if self.is_stopped() {
+                        return None;
+                    }
+                    sleep(self.config.send.confirm_interval).await;
+                }
+            }
+        }
+    }
+
+    /// Counts a failed submission, announcing every `submission_failures` in a row.
+    fn record_failure(&self, miner: Pubkey, err: &Error) {
+        let failures = {
+            let mut failures = self.failures.lock().unwrap();
+            let count = failures.entry(miner).or_default();
+            *count += 1;
+            *count
+        };
+        let Some(events) = &self.config.send.events else {
+            return;
+        };
+        if failures % events.config.submission_failures.max(1) == 0 {
+            events.emit(Event::SubmissionFailed {
+                miner: miner.to_string(),
+                failures,
+                error_kind: err.kind(),
+                error: err.to_string(),
+            });
         }
     }
 
+    /// Fetches the miner's proof, retrying until it succeeds or the pipeline stops.
+    async fn get_proof(&self, signed_work: &SignedWork) -> Option<Proof> {
+        loop {
+            match signed_work.signer.get_proof(self.ore.get_client(None)).await {
+                Ok(proof) => {
+                    self.metrics.record_proof(signed_work.signer.pubkey(), &proof);
+                    if let Some(ledger) = &self.config.send.ledger {
+                        ledger.record_proof(signed_work.signer.pubkey(), &proof);
+                    }
+                    return Some(proof);
+                }
+                Err(err) => {
+                    warn!(error = ?err, "Failed to fetch proof");
+                    if self.is_stopped() {
+                        return None;
+                    }
+                    sleep(self.config.send.confirm_interval).await;
+                }
+            }
+        }
+    }
 }
@@ -0,0 +1,240 @@
+//! Keeps `ore mine` from spending more on fees than its ORE is worth. A proof earns the
+//! treasury's reward rate and costs the base fee of its signatures plus the priority fee of its
+//! compute units; an ORE price in SOL, static or fetched over HTTP, puts both in lamports.
+
+use std::{
+    io::{self, ErrorKind},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        RwLock,
+    },
+    time::Duration,
+};
+
+use clap::ValueEnum;
+use ore::ONE_ORE;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use solana_sdk::native_token::LAMPORTS_PER_SOL;
+use tracing::{info, warn};
+
+use crate::transaction::{SendPolicy, LAMPORTS_PER_SIGNATURE};
+
+pub const DEFAULT_PRICE_REFRESH: Duration = Duration::from_secs(60);
+pub const DEFAULT_SLOW_DOWN: Duration = Duration::from_secs(60);
+
+const PRICE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// What `ore mine` does while a proof earns less than the margin.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum GuardAction {
+    /// Hold found work until the margin recovers.
+    #[default]
+    Pause,
+    /// Lower the priority fee until the margin is met, holding work if the base fee alone is
+    /// too much.
+    ReduceFee,
+    /// Wait before every submission.
+    Slow,
+}
+
+/// Where the price of one ORE in SOL comes from.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PriceFeed {
+    Static(f64),
+    /// A JSON document with the price at `pointer`, e.g. `/data/ORE/price`, or the whole
+    /// document when the pointer is empty.
+    Http {
+        url: String,
+        pointer: String,
+    },
+}
+
+impl PriceFeed {
+    pub async fn fetch(&self, client: &reqwest::Client) -> io::Result<f64> {
+        let (url, pointer) = match self {
+            PriceFeed::Static(price) => return Ok(*price),
+            PriceFeed::Http { url, pointer } => (url, pointer),
+        };
+        let to_io = |err: reqwest::Error| io::Error::new(ErrorKind::Other, err);
+        let document = client
+            .get(url)
+            .timeout(PRICE_TIMEOUT)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(to_io)?
+            .json::<Value>()
+            .await
+            .map_err(to_io)?;
+        parse_price(&document, pointer).ok_or_else(|| {
+            io::Error::new(
+                ErrorKind::InvalidData,
+                format!("no positive price at {:?} of {}", pointer, url),
+            )
+        })
+    }
+}
+
+/// The positive number, or numeric string, at `pointer`.
+pub fn parse_price(document: &Value, pointer: &str) -> Option<f64> {
+    let price = match document.pointer(pointer)? {
+        Value::Number(number) => number.as_f64()?,
+        Value::String(string) => string.trim().parse().ok()?,
+        _ => return None,
+    };
+    (price.is_finite() && price > 0.0).then_some(price)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProfitConfig {
+    pub feed: PriceFeed,
+    /// Least margin mining goes on at: 0 breaks even, 0.25 earns a quarter more than it spends.
+    pub min_margin: f64,
+    pub action: GuardAction,
+    /// How long `GuardAction::Slow` waits before every submission.
+    pub slow_down: Duration,
+    /// How often an HTTP feed is fetched.
+    pub refresh: Duration,
+}
+
+/// Expected earnings and cost of one proof.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Margin {
+    /// The reward rate at the ORE price.
+    pub value_lamports: f64,
+    pub cost_lamports: u64,
+}
+
+impl Margin {
+    pub fn new(reward_rate: u64, ore_price: f64, cost_lamports: u64) -> Self {
+        Margin {
+            value_lamports: reward_rate as f64
+                * ore_price
+                * (LAMPORTS_PER_SOL as f64 / ONE_ORE as f64),
+            cost_lamports,
+        }
+    }
+
+    /// Earnings over cost, minus one.
+    pub fn ratio(&self) -> f64 {
+        match self.cost_lamports {
+            0 => f64::INFINITY,
+            cost => self.value_lamports / cost as f64 - 1.0,
+        }
+    }
+}
+
+/// What to do with a proof about to be submitted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Verdict {
+    /// Submit at this priority fee, microlamports per compute unit.
+    Submit(Option<u64>),
+    /// Submit at the configured priority fee after waiting.
+    Delay(Duration),
+    Hold,
+}
+
+/// Judges every proof against the margin with the latest price, logging when mining falls below
+/// the margin and when it recovers.
+#[derive(Debug)]
+pub struct ProfitGuard {
+    pub config: ProfitConfig,
+    price: RwLock<Option<f64>>,
+    below: AtomicBool,
+    client: reqwest::Client,
+}
+
+impl ProfitGuard {
+    pub fn new(config: ProfitConfig) -> Self {
+        let price = match config.feed {
+            PriceFeed::Static(price) => Some(price),
+            PriceFeed::Http { .. } => None,
+        };
+        ProfitGuard {
+            config,
+            price: RwLock::new(price),
+            below: AtomicBool::new(false),
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// The latest ORE price in SOL, `None` until an HTTP feed answered once.
+    pub fn price(&self) -> Option<f64> {
+        *self.price.read().unwrap()
+    }
+
+    /// Fetches the price, keeping the last one if the feed fails.
+    pub async fn refresh(&self) {
+        match self.config.feed.fetch(&self.client).await {
+            Ok(price) => *self.price.write().unwrap() = Some(price),
+            Err(err) => warn!(error = %err, "Failed to fetch the ORE price"),
+        }
+    }
+
+    /// Judges a transaction with `signatures` signatures and `cu_limit` compute units, priced by
+    /// `policy`, that earns `reward_rate`. Without a price yet every proof is submitted.
+    pub fn judge(
+        &self,
+        reward_rate: u64,
+        policy: &SendPolicy,
+        signatures: u64,
+        cu_limit: u32,
+    ) -> Verdict {
+        let Some(price) = self.price() else {
+            return Verdict::Submit(policy.priority_fee);
+        };
+        let base_fee = LAMPORTS_PER_SIGNATURE * signatures;
+        let margin = Margin::new(
+            reward_rate,
+            price,
+            base_fee + policy.priority_fee_lamports(cu_limit),
+        );
+        if margin.ratio() >= self.config.min_margin {
+            if self.below.swap(false, Ordering::Relaxed) {
+                info!(
+                    margin = margin.ratio(),
+                    ore_price = price,
+                    "Mining is above the margin again"
+                );
+            }
+            return Verdict::Submit(policy.priority_fee);
+        }
+
+        let (verdict, action) = match self.config.action {
+            GuardAction::Pause => (Verdict::Hold, "holding found work"),
+            GuardAction::Slow => (Verdict::Delay(self.config.slow_down), "slowing submissions"),
+            GuardAction::ReduceFee => match self.max_priority_fee(&margin, base_fee, cu_limit) {
+                Some(fee) => (Verdict::Submit(Some(fee)), "reducing the priority fee"),
+                None => (
+                    Verdict::Hold,
+                    "holding found work, the base fee alone is too much",
+                ),
+            },
+        };
+        if !self.below.swap(true, Ordering::Relaxed) {
+            warn!(
+                margin = margin.ratio(),
+                min_margin = self.config.min_margin,
+                ore_price = price,
+                reward_rate,
+                value_lamports = margin.value_lamports as u64,
+                cost_lamports = margin.cost_lamports,
+                "Mining is below the margin, {}",
+                action
+            );
+        }
+        verdict
+    }
+
+    /// The highest priority fee, in microlamports per compute unit, that still meets the margin.
+    fn max_priority_fee(&self, margin: &Margin, base_fee: u64, cu_limit: u32) -> Option<u64> {
+        let allowed = margin.value_lamports / (1.0 + self.config.min_margin);
+        if allowed < base_fee as f64 {
+            return None;
+        }
+        let priority_lamports = (allowed - base_fee as f64) as u64;
+        Some(priority_lamports * 1_000_000 / cu_limit.max(1) as u64)
+    }
+}
@@ -0,0 +1,477 @@
+use std::{
+    fmt::Display,
+    io::{self, BufRead, BufReader, ErrorKind, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    os::unix::net::{UnixListener, UnixStream},
+    path::PathBuf,
+    str::FromStr,
+    time::Duration,
+};
+
+use ore::instruction::OreInstruction;
+use serde::{Deserialize, Serialize};
+use solana_sdk::{
+    compute_budget, instruction::CompiledInstruction, message::Message, pubkey::Pubkey,
+    signature::Keypair, signature::Signature, signer::Signer, signer::SignerError,
+    system_instruction::SystemInstruction, system_program,
+};
+use tracing::warn;
+
+use crate::http::read_http_head;
+
+/// How long either side waits on a signing connection before giving up.
+const TIMEOUT: Duration = Duration::from_secs(10);
+/// Largest request the daemon reads, well above the size of a transaction message.
+const MAX_REQUEST: usize = 64 * 1024;
+/// Highest priority fee the daemon pays by default, in micro-lamports per compute unit.
+pub const DEFAULT_MAX_CU_PRICE: u64 = 1_000_000;
+/// Largest compute budget the daemon pays for by default.
+pub const DEFAULT_MAX_CU_LIMIT: u32 = 200_000;
+/// Compute units the runtime grants each instruction of a transaction without a limit.
+const DEFAULT_INSTRUCTION_CU: u32 = 200_000;
+/// Largest compute budget of a transaction.
+const MAX_TRANSACTION_CU: u32 = 1_400_000;
+
+/// Where a signer daemon listens: `unix:/path/to/socket` or `http://host:port`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SignerEndpoint {
+    Unix(PathBuf),
+    Http(String),
+}
+
+impl SignerEndpoint {
+    /// Whether an `--owner` value names a signer daemon rather than a keypair file.
+    pub fn is_endpoint(value: &str) -> bool {
+        value.starts_with("unix:") || value.starts_with("http://")
+    }
+}
+
+impl FromStr for SignerEndpoint {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(path) = s.strip_prefix("unix:") {
+            Ok(SignerEndpoint::Unix(path.into()))
+        } else if let Some(address) = s.strip_prefix("http://") {
+            Ok(SignerEndpoint::Http(address.trim_end_matches('/').into()))
+        } else {
+            Err(format!("expected unix:<path> or http://<host>:<port>, got {}", s))
+        }
+    }
+}
+
+impl Display for SignerEndpoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SignerEndpoint::Unix(path) => write!(f, "unix:{}", path.display()),
+            SignerEndpoint::Http(address) => write!(f, "http://{}", address),
+        }
+    }
+}
+
+/// A request to a signer daemon. Over a Unix socket every request and response is one line of
+/// JSON; over HTTP it is the body of a `POST /`, with `Authorization: Bearer <token>` when the
+/// daemon has a token.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "method", rename_all = "snake_case")]
+pub enum SignRequest {
+    Pubkey,
+    /// `message` is the hex encoded serialized transaction message.
+    Sign { message: String },
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct SignResponse {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pubkey: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl SignResponse {
+    fn error(error: impl Display) -> Self {
+        SignResponse {
+            error: Some(error.to_string()),
+            ..Default::default()
+        }
+    }
+}
+
+/// A fee payer whose key lives in a signer daemon. Signing blocks on a round trip to the daemon,
+/// so it is only meant for a daemon on the same host or network.
+#[derive(Debug)]
+pub struct RemoteSigner {
+    endpoint: SignerEndpoint,
+    pubkey: Pubkey,
+    /// Sent as a bearer token over HTTP.
+    token: Option<String>,
+}
+
+impl RemoteSigner {
+    /// Asks the daemon for its pubkey, which is then cached.
+    pub fn connect(endpoint: SignerEndpoint, token: Option<String>) -> io::Result<Self> {
+        let response = call(&endpoint, token.as_deref(), &SignRequest::Pubkey)?;
+        let pubkey = response
+            .pubkey
+            .ok_or_else(|| invalid_response(response.error))?
+            .parse()
+            .map_err(|err| io::Error::new(ErrorKind::InvalidData, format!("{:?}", err)))?;
+        Ok(RemoteSigner {
+            endpoint,
+            pubkey,
+            token,
+        })
+    }
+
+    pub fn endpoint(&self) -> &SignerEndpoint {
+        &self.endpoint
+    }
+}
+
+impl Signer for RemoteSigner {
+    fn try_pubkey(&self) -> Result<Pubkey, SignerError> {
+        Ok(self.pubkey)
+    }
+
+    fn try_sign_message(&self, message: &[u8]) -> Result<Signature, SignerError> {
+        let request = SignRequest::Sign {
+            message: hex::encode(message),
+        };
+        let response = call(&self.endpoint, self.token.as_deref(), &request)
+            .map_err(|err| SignerError::Connection(format!("{}: {}", self.endpoint, err)))?;
+        let signature = match (response.signature, response.error) {
+            (Some(signature), _) => Signature::from_str(&signature)
+                .map_err(|err| SignerError::Protocol(err.to_string()))?,
+            (None, error) => return Err(SignerError::Custom(error.unwrap_or_default())),
+        };
+        if !signature.verify(self.pubkey.as_ref(), message) {
+            return Err(SignerError::Protocol("signature does not verify".into()));
+        }
+        Ok(signature)
+    }
+
+    fn is_interactive(&self) -> bool {
+        false
+    }
+}
+
+fn invalid_response(error: Option<String>) -> io::Error {
+    io::Error::new(
+        ErrorKind::InvalidData,
+        error.unwrap_or_else(|| "invalid signer response".into()),
+    )
+}
+
+fn call(
+    endpoint: &SignerEndpoint,
+    token: Option<&str>,
+    request: &SignRequest,
+) -> io::Result<SignResponse> {
+    let body = serde_json::to_vec(request)?;
+    let response = match endpoint {
+        SignerEndpoint::Unix(path) => {
+            let mut stream = UnixStream::connect(path)?;
+            stream.set_read_timeout(Some(TIMEOUT))?;
+            stream.set_write_timeout(Some(TIMEOUT))?;
+            stream.write_all(&body)?;
+            stream.write_all(b"\n")?;
+            let mut line = String::new();
+            BufReader::new(stream).read_line(&mut line)?;
+            line.into_bytes()
+        }
+        SignerEndpoint::Http(address) => {
+            let mut stream = TcpStream::connect(address)?;
+            stream.set_read_timeout(Some(TIMEOUT))?;
+            stream.set_write_timeout(Some(TIMEOUT))?;
+            let authorization = token
+                .map(|token| format!("Authorization: Bearer {}\r\n", token))
+                .unwrap_or_default();
+            write!(
+                stream,
+                "POST / HTTP/1.1\r\nHost: {}\r\n{}Content-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                address,
+                authorization,
+                body.len()
+            )?;
+            stream.write_all(&body)?;
+            let mut reader = BufReader::new(stream);
+            let head = read_http_head(&mut reader, MAX_REQUEST)?;
+            let mut body = vec![0u8; head.length];
+            reader.read_exact(&mut body)?;
+            if !head.first.contains(" 200 ") && body.is_empty() {
+                return Err(io::Error::new(ErrorKind::Other, head.first));
+            }
+            body
+        }
+    };
+    serde_json::from_slice(&response).map_err(|err| io::Error::new(ErrorKind::InvalidData, err))
+}
+
+/// The reference signer daemon. It only signs legacy transaction messages that its key pays
+/// for and whose instructions all call an allowed program. Its own SOL only goes to the allowed
+/// recipients, its own rewards are never claimed and the priority fee it pays is capped.
+pub struct SignerServer {
+    pub keypair: Keypair,
+    pub allowed_programs: Vec<Pubkey>,
+    /// Wallets the daemon may transfer its SOL to, the miners it funds.
+    pub allowed_recipients: Vec<Pubkey>,
+    /// Highest compute unit price signed, in micro-lamports.
+    pub max_cu_price: u64,
+    /// Largest compute budget signed, requested or implied by the instruction count.
+    pub max_cu_limit: u32,
+    /// Signs the Ore admin instructions as well as register, mine and claim.
+    pub allow_admin: bool,
+    /// Bearer token every HTTP request must carry. Without one only loopback addresses are
+    /// served.
+    pub token: Option<String>,
+}
+
+impl SignerServer {
+    /// A daemon allowed to sign mining, registration and SOL transfers into its key.
+    pub fn new(keypair: Keypair) -> Self {
+        SignerServer {
+            keypair,
+            allowed_programs: vec![ore::ID, compute_budget::ID, system_program::ID],
+            allowed_recipients: vec![],
+            max_cu_price: DEFAULT_MAX_CU_PRICE,
+            max_cu_limit: DEFAULT_MAX_CU_LIMIT,
+            allow_admin: false,
+            token: None,
+        }
+    }
+
+    pub fn handle(&self, request: &SignRequest) -> SignResponse {
+        match request {
+            SignRequest::Pubkey => SignResponse {
+                pubkey: Some(self.keypair.pubkey().to_string()),
+                ..Default::default()
+            },
+            SignRequest::Sign { message } => match self.sign(message) {
+                Ok(signature) => SignResponse {
+                    signature: Some(signature.to_string()),
+                    ..Default::default()
+                },
+                Err(err) => SignResponse::error(err),
+            },
+        }
+    }
+
+    fn sign(&self, message: &str) -> Result<Signature, String> {
+        let bytes = hex::decode(message).map_err(|err| err.to_string())?;
+        let decoded = bincode::deserialize::<Message>(&bytes)
+            .map_err(|err| format!("not a transaction message: {}", err))?;
+        if decoded.serialize() != bytes {
+            return Err("not a legacy transaction message".into());
+        }
+        if decoded.account_keys.first() != Some(&self.keypair.pubkey()) {
+            return Err("the signer is not the fee payer".into());
+        }
+        for instruction in &decoded.instructions {
+            let program = decoded.account_keys.get(instruction.program_id_index as usize);
+            let Some(program) = program.filter(|program| self.allowed_programs.contains(program))
+            else {
+                return Err(format!("program {:?} is not allowed", program));
+            };
+            self.check_instruction(&decoded, program, instruction)?;
+        }
+        self.check_compute_budget(&decoded)?;
+        Ok(self.keypair.sign_message(&bytes))
+    }
+
+    /// Refuses system instructions other than transfers, transfers of the daemon's SOL outside
+    /// the allowed recipients, Ore instructions mining does not use unless admin use is on, and
+    /// claims of the daemon's own rewards.
+    fn check_instruction(
+        &self,
+        message: &Message,
+        program: &Pubkey,
+        instruction: &CompiledInstruction,
+    ) -> Result<(), String> {
+        let signer = self.keypair.pubkey();
+        let account = |index: usize| {
+            instruction
+                .accounts
+                .get(index)
+                .and_then(|index| message.account_keys.get(*index as usize))
+        };
+        if *program == system_program::ID {
+            let Ok(SystemInstruction::Transfer { .. }) = bincode::deserialize(&instruction.data)
+            else {
+                return Err("only system transfers are allowed".into());
+            };
+            let to = account(1).ok_or("invalid transfer")?;
+            if account(0) == Some(&signer) && !self.allowed_recipients.contains(to) {
+                return Err(format!("transfers to {} are not allowed", to));
+            }
+        } else if *program == ore::ID {
+            let tag = instruction.data.first().copied();
+            let mining = [
+                OreInstruction::Register as u8,
+                OreInstruction::Mine as u8,
+                OreInstruction::Claim as u8,
+            ];
+            if !self.allow_admin && !tag.is_some_and(|tag| mining.contains(&tag)) {
+                return Err(format!("ore instruction {:?} is not allowed", tag));
+            }
+            if tag == Some(OreInstruction::Claim as u8) && account(0) == Some(&signer) {
+                return Err("claiming the signer's rewards is not allowed".into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Refuses compute budget instructions other than a unit limit and price, and a price or
+    /// budget above the daemon's maximum. Without a limit the budget is the runtime's default
+    /// for the message's instructions.
+    fn check_compute_budget(&self, message: &Message) -> Result<(), String> {
+        let (mut limit, mut price, mut instructions) = (None, 0, 0u32);
+        for instruction in &message.instructions {
+            let program = message.account_keys.get(instruction.program_id_index as usize);
+            if program != Some(&compute_budget::ID) {
+                instructions += 1;
+                continue;
+            }
+            let data = &instruction.data;
+            match data.first() {
+                Some(2) => {
+                    let units = data.get(1..5).and_then(|units| units.try_into().ok());
+                    limit = Some(u32::from_le_bytes(units.ok_or("invalid compute unit limit")?));
+                }
+                Some(3) => {
+                    let units = data.get(1..9).and_then(|units| units.try_into().ok());
+                    price = u64::from_le_bytes(units.ok_or("invalid compute unit price")?);
+                }
+                _ => return Err("only compute unit limits and prices are allowed".into()),
+            }
+        }
+        let limit = limit.unwrap_or_else(|| {
+            instructions
+                .saturating_mul(DEFAULT_INSTRUCTION_CU)
+                .min(MAX_TRANSACTION_CU)
+        });
+        if price > self.max_cu_price {
+            return Err(format!(
+                "compute unit price {} is above the maximum of {}",
+                price, self.max_cu_price
+            ));
+        }
+        if limit > self.max_cu_limit {
+            return Err(format!(
+                "compute unit limit {} is above the maximum of {}",
+                limit, self.max_cu_limit
+            ));
+        }
+        Ok(())
+    }
+
+    /// Serves requests until the listener fails. Connections are handled one at a time.
+    pub fn serve(&self, endpoint: &SignerEndpoint) -> io::Result<()> {
+        match endpoint {
+            SignerEndpoint::Unix(path) => {
+                if path.exists() {
+                    std::fs::remove_file(path)?;
+                }
+                let listener = UnixListener::bind(path)?;
+                std::fs::set_permissions(
+                    path,
+                    std::os::unix::fs::PermissionsExt::from_mode(0o600),
+                )?;
+                self.serve_unix(listener)
+            }
+            SignerEndpoint::Http(address) => {
+                let addresses = address.to_socket_addrs()?.collect::<Vec<SocketAddr>>();
+                if self.token.is_none() && !addresses.iter().all(|addr| addr.ip().is_loopback()) {
+                    return Err(io::Error::new(
+                        ErrorKind::PermissionDenied,
+                        "a token is needed to serve HTTP beyond loopback",
+                    ));
+                }
+                self.serve_http(TcpListener::bind(&addresses[..])?)
+            }
+        }
+    }
+
+    pub fn serve_unix(&self, listener: UnixListener) -> io::Result<()> {
+        for stream in listener.incoming() {
+            let stream = stream?;
+            if let Err(err) = self.respond_unix(stream) {
+                warn!(error = %err, "Signer connection failed");
+            }
+        }
+        Ok(())
+    }
+
+    pub fn serve_http(&self, listener: TcpListener) -> io::Result<()> {
+        for stream in listener.incoming() {
+            let stream = stream?;
+            if let Err(err) = self.respond_http(stream) {
+                warn!(error = %err, "Signer connection failed");
+            }
+        }
+        Ok(())
+    }
+
+    fn respond_unix(&self, stream: UnixStream) -> io::Result<()> {
+        stream.set_read_timeout(Some(TIMEOUT))?;
+        let mut line = String::new();
+        BufReader::new(&stream)
+            .take(MAX_REQUEST as u64)
+            .read_line(&mut line)?;
+        let response = self.respond(line.as_bytes());
+        (&stream).write_all(&serde_json::to_vec(&response)?)?;
+        (&stream).write_all(b"\n")
+    }
+
+    fn respond_http(&self, stream: TcpStream) -> io::Result<()> {
+        stream.set_read_timeout(Some(TIMEOUT))?;
+        let mut reader = BufReader::new(&stream);
+        let head = read_http_head(&mut reader, MAX_REQUEST)?;
+        let mut body = vec![0u8; head.length];
+        reader.read_exact(&mut body)?;
+
+        let (status, response) = if !self.authorized(head.bearer.as_deref()) {
+            ("401 Unauthorized", SignResponse::error("invalid token"))
+        } else if head.first.starts_with("POST ") {
+            let response = self.respond(&body);
+            let status = match response.error {
+                Some(_) => "400 Bad Request",
+                None => "200 OK",
+            };
+            (status, response)
+        } else {
+            ("405 Method Not Allowed", SignResponse::error("use POST"))
+        };
+        let body = serde_json::to_vec(&response)?;
+        write!(
+            &stream,
+            "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            status,
+            body.len()
+        )?;
+        (&stream).write_all(&body)
+    }
+
+    fn authorized(&self, bearer: Option<&str>) -> bool {
+        match &self.token {
+            Some(token) => bearer.is_some_and(|bearer| {
+                // Compared in full so the time taken does not leak the length of a match.
+                bearer.len() == token.len()
+                    && bearer
+                        .bytes()
+                        .zip(token.bytes())
+                        .fold(0, |diff, (a, b)| diff | (a ^ b))
+                        == 0
+            }),
+            None => true,
+        }
+    }
+
+    fn respond(&self, request: &[u8]) -> SignResponse {
+        match serde_json::from_slice::<SignRequest>(request) {
+            Ok(request) => self.handle(&request),
+            Err(err) => SignResponse::error(format!("invalid request: {}", err)),
+        }
+    }
+}
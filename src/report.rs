@@ -0,0 +1,352 @@
+//! Per-transaction earnings and costs for accounting. The on-chain history of every miner and the
+//! owner is the source of truth; transactions the ledger saw land but the RPC no longer serves are
+//! filled in from the ledger.
+
+use std::{
+    collections::HashSet,
+    io::{self, Write},
+    ops::Range,
+};
+
+use base64::{prelude::BASE64_STANDARD, Engine};
+use chrono::{DateTime, SecondsFormat};
+use clap::ValueEnum;
+use futures::{stream, StreamExt};
+use serde::Serialize;
+use solana_client::{
+    rpc_client::GetConfirmedSignaturesForAddress2Config, rpc_config::RpcTransactionConfig,
+};
+use solana_sdk::{
+    commitment_config::CommitmentConfig, pubkey::Pubkey, signature::Signature, signer::Signer,
+    system_instruction::SystemInstruction, system_program, transaction::VersionedTransaction,
+};
+use solana_transaction_status::{
+    option_serializer::OptionSerializer, EncodedConfirmedTransactionWithStatusMeta,
+    UiTransactionEncoding,
+};
+
+use crate::{
+    errors::Result,
+    factory::Ore,
+    ledger::{LandedTransaction, TransactionKind},
+    transaction::LAMPORTS_PER_SIGNATURE,
+};
+
+/// Signatures per `getSignaturesForAddress` request, the RPC limit.
+const SIGNATURES_PER_REQUEST: usize = 1000;
+
+/// `getTransaction` requests in flight at the same time.
+const CONCURRENT_FETCHES: usize = 8;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum ReportFormat {
+    #[default]
+    Csv,
+    /// An array of row objects.
+    Json,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportKind {
+    Mine,
+    Claim,
+    Reset,
+    Register,
+    Transfer,
+    Admin,
+}
+
+impl ReportKind {
+    pub fn label(&self) -> &'static str {
+        match self {
+            ReportKind::Mine => "mine",
+            ReportKind::Claim => "claim",
+            ReportKind::Reset => "reset",
+            ReportKind::Register => "register",
+            ReportKind::Transfer => "transfer",
+            ReportKind::Admin => "admin",
+        }
+    }
+}
+
+impl From<TransactionKind> for ReportKind {
+    fn from(kind: TransactionKind) -> Self {
+        match kind {
+            TransactionKind::Mine => ReportKind::Mine,
+            TransactionKind::Claim => ReportKind::Claim,
+            TransactionKind::Register => ReportKind::Register,
+            TransactionKind::Fund | TransactionKind::Sweep => ReportKind::Transfer,
+            TransactionKind::Admin => ReportKind::Admin,
+        }
+    }
+}
+
+/// Where a row's figures come from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportSource {
+    Chain,
+    /// Only the local ledger has the transaction, so its fee is the one it was priced at.
+    Ledger,
+}
+
+impl ReportSource {
+    pub fn label(&self) -> &'static str {
+        match self {
+            ReportSource::Chain => "chain",
+            ReportSource::Ledger => "ledger",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct ReportRow {
+    /// Block time in RFC 3339, UTC.
+    pub timestamp: String,
+    pub signature: String,
+    /// The miner the transaction was for, or the owner.
+    pub wallet: String,
+    pub kind: ReportKind,
+    /// Base and priority fee, paid by the fee payer.
+    pub fee_lamports: u64,
+    pub priority_fee_lamports: u64,
+    /// ORE base units the transaction moved: the reward of a mine, the amount of a claim. `None`
+    /// when only the ledger has a mine transaction, which does not record its reward.
+    pub ore_delta: Option<u64>,
+    /// The transaction landed with an error, paying its fee but moving no ORE.
+    pub failed: bool,
+    pub source: ReportSource,
+}
+
+impl Ore {
+    /// Every Ore transaction and transfer between the miners and the owner with a block time in
+    /// `range`, unix seconds, oldest first. Transactions found in a miner's history are filed
+    /// under it, the rest under the owner. `landed` are the ledger's transactions in `range`, added
+    /// when the RPC does not have them.
+    pub async fn report(
+        &self,
+        range: Range<i64>,
+        landed: Vec<LandedTransaction>,
+    ) -> Result<Vec<ReportRow>> {
+        let mut wallets = self
+            .miners
+            .iter()
+            .map(|miner| miner.pubkey())
+            .collect::<Vec<_>>();
+        wallets.push(self.owner.pubkey());
+        let known = wallets.iter().copied().collect::<HashSet<_>>();
+
+        let mut seen = HashSet::new();
+        let mut signatures = vec![];
+        for wallet in &wallets {
+            for signature in self.get_signatures(wallet, &range).await? {
+                if seen.insert(signature) {
+                    signatures.push((*wallet, signature));
+                }
+            }
+        }
+
+        let fetches = signatures
+            .into_iter()
+            .map(|(wallet, signature)| async move {
+                let transaction = self.get_client(None).get_transaction_with_config(
+                    &signature,
+                    RpcTransactionConfig {
+                        encoding: Some(UiTransactionEncoding::Base64),
+                        commitment: Some(CommitmentConfig::confirmed()),
+                        max_supported_transaction_version: Some(0),
+                    },
+                );
+                (wallet, signature, transaction.await)
+            });
+        let fetched = stream::iter(fetches)
+            .buffered(CONCURRENT_FETCHES)
+            .collect::<Vec<_>>()
+            .await;
+
+        let mut rows = vec![];
+        let mut on_chain = HashSet::new();
+        for (wallet, signature, transaction) in fetched {
+            if let Some(row) = report_row(wallet, signature, &transaction?, &known) {
+                on_chain.insert(row.signature.clone());
+                rows.push(row);
+            }
+        }
+
+        let owner = self.owner.pubkey();
+        for landed in landed {
+            if on_chain.contains(&landed.signature) {
+                continue;
+            }
+            let Some(kind) = landed.kind else {
+                continue;
+            };
+            let kind = ReportKind::from(kind);
+            rows.push(ReportRow {
+                timestamp: timestamp(landed.time),
+                signature: landed.signature,
+                wallet: landed.miner.unwrap_or(owner).to_string(),
+                kind,
+                fee_lamports: landed.fee_lamports,
+                priority_fee_lamports: landed.priority_fee_lamports,
+                ore_delta: match kind {
+                    ReportKind::Mine => None,
+                    ReportKind::Claim => landed.claimed,
+                    _ => Some(0),
+                },
+                failed: false,
+                source: ReportSource::Ledger,
+            });
+        }
+
+        rows.sort_by(|a, b| (&a.timestamp, &a.signature).cmp(&(&b.timestamp, &b.signature)));
+        Ok(rows)
+    }
+
+    /// Signatures of `address` with a block time in `range`, newest first.
+    async fn get_signatures(&self, address: &Pubkey, range: &Range<i64>) -> Result<Vec<Signature>> {
+        let client = self.get_client(None);
+        let mut signatures = vec![];
+        let mut before = None;
+        loop {
+            let page = client
+                .get_signatures_for_address_with_config(
+                    address,
+                    GetConfirmedSignaturesForAddress2Config {
+                        before,
+                        until: None,
+                        limit: Some(SIGNATURES_PER_REQUEST),
+                        commitment: Some(CommitmentConfig::confirmed()),
+                    },
+                )
+                .await?;
+            let Some(last) = page.last() else {
+                return Ok(signatures);
+            };
+            before = last.signature.parse().ok();
+
+            let mut older = false;
+            for status in &page {
+                match status.block_time {
+                    Some(time) if time >= range.end => {}
+                    Some(time) if time < range.start => older = true,
+                    _ => signatures.extend(status.signature.parse::<Signature>().ok()),
+                }
+            }
+            if older || page.len() < SIGNATURES_PER_REQUEST || before.is_none() {
+                return Ok(signatures);
+            }
+        }
+    }
+}
+
+fn report_row(
+    wallet: Pubkey,
+    signature: Signature,
+    transaction: &EncodedConfirmedTransactionWithStatusMeta,
+    wallets: &HashSet<Pubkey>,
+) -> Option<ReportRow> {
+    let meta = transaction.transaction.meta.as_ref()?;
+    let decoded = transaction.transaction.transaction.decode()?;
+    let return_data = match &meta.return_data {
+        OptionSerializer::Some(return_data) if return_data.program_id == ore::ID.to_string() => {
+            BASE64_STANDARD.decode(&return_data.data.0).ok()
+        }
+        _ => None,
+    };
+    let (kind, ore_delta) = classify(&decoded, return_data.as_deref(), wallets)?;
+    let failed = meta.err.is_some();
+    let base_fee = LAMPORTS_PER_SIGNATURE * decoded.signatures.len() as u64;
+
+    Some(ReportRow {
+        timestamp: timestamp(transaction.block_time.unwrap_or_default()),
+        signature: signature.to_string(),
+        wallet: wallet.to_string(),
+        kind,
+        fee_lamports: meta.fee,
+        priority_fee_lamports: meta.fee.saturating_sub(base_fee),
+        ore_delta: Some(if failed { 0 } else { ore_delta }),
+        failed,
+        source: ReportSource::Chain,
+    })
+}
+
+/// The kind of a transaction and the ORE it moved, from its first Ore instruction or else a
+/// system transfer between two of `wallets`. `None` for anything else. `return_data` is what the
+/// Ore program returned, the reward of a mine.
+pub fn classify(
+    transaction: &VersionedTransaction,
+    return_data: Option<&[u8]>,
+    wallets: &HashSet<Pubkey>,
+) -> Option<(ReportKind, u64)> {
+    let keys = transaction.message.static_account_keys();
+    let mut transfer = None;
+    for instruction in transaction.message.instructions() {
+        let program = keys.get(instruction.program_id_index as usize)?;
+        if *program == ore::ID {
+            let amount = |data: Option<&[u8]>| {
+                data.and_then(|data| data.get(..8))
+                    .map_or(0, |bytes| u64::from_le_bytes(bytes.try_into().unwrap()))
+            };
+            return match instruction.data.first()? {
+                0 => Some((ReportKind::Reset, 0)),
+                1 => Some((ReportKind::Register, 0)),
+                2 => Some((ReportKind::Mine, amount(return_data))),
+                3 => Some((ReportKind::Claim, amount(instruction.data.get(1..)))),
+                _ => Some((ReportKind::Admin, 0)),
+            };
+        }
+        if *program == system_program::ID {
+            let accounts = instruction
+                .accounts
+                .iter()
+                .filter_map(|index| keys.get(*index as usize))
+                .collect::<Vec<_>>();
+            if let (Ok(SystemInstruction::Transfer { .. }), [from, to, ..]) =
+                (bincode::deserialize(&instruction.data), accounts.as_slice())
+            {
+                if wallets.contains(from) && wallets.contains(to) {
+                    transfer = Some((ReportKind::Transfer, 0));
+                }
+            }
+        }
+    }
+    transfer
+}
+
+pub fn write_csv(rows: &[ReportRow], mut writer: impl Write) -> io::Result<()> {
+    writeln!(
+        writer,
+        "timestamp,signature,wallet,kind,fee_lamports,priority_fee_lamports,ore_delta,failed,source"
+    )?;
+    for row in rows {
+        writeln!(
+            writer,
+            "{},{},{},{},{},{},{},{},{}",
+            row.timestamp,
+            row.signature,
+            row.wallet,
+            row.kind.label(),
+            row.fee_lamports,
+            row.priority_fee_lamports,
+            row.ore_delta
+                .map(|delta| delta.to_string())
+                .unwrap_or_default(),
+            row.failed,
+            row.source.label()
+        )?;
+    }
+    Ok(())
+}
+
+pub fn write_json(rows: &[ReportRow], mut writer: impl Write) -> io::Result<()> {
+    serde_json::to_writer_pretty(&mut writer, rows)?;
+    writeln!(writer)
+}
+
+fn timestamp(time: i64) -> String {
+    DateTime::from_timestamp(time, 0)
+        .unwrap_or_default()
+        .to_rfc3339_opts(SecondsFormat::Secs, true)
+}
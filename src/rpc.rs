@@ -1,22 +1,58 @@
-use std::ops::Deref;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
-use cached::{cached_result, proc_macro::cached, TimedCache};
-use solana_client::{client_error::Result};
-use solana_sdk::{commitment_config::CommitmentConfig, hash::Hash};
+pub use solana_client::{nonblocking::rpc_client::RpcClient, rpc_sender::RpcTransportStats};
 
-pub use solana_client::nonblocking::rpc_client::RpcClient;
+/// The host of an RPC url, without the credentials, path or query an API key is often put in.
+pub fn endpoint_host(url: &str) -> &str {
+    let host = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let host = host.split(['/', '?']).next().unwrap_or_default();
+    host.rsplit('@').next().unwrap_or_default()
+}
 
+/// RPC endpoints in order of preference. Requests go to the current endpoint, which moves on to
+/// the next one whenever it is reported down.
 pub struct RpcPool {
     clients: Vec<RpcClient>,
+    current: AtomicUsize,
 }
 
 impl RpcPool {
+    /// Panics without urls.
     pub fn new(urls: Vec<String>) -> Self {
+        assert!(!urls.is_empty(), "an RPC pool needs at least one url");
         let clients = urls.iter().map(|url| RpcClient::new(url.clone())).collect();
-        RpcPool { clients }
+        RpcPool {
+            clients,
+            current: AtomicUsize::new(0),
+        }
     }
+
+    /// The current endpoint, or a fixed one per key.
     pub fn get_client(&self, key: Option<usize>) -> &RpcClient {
-        &self.clients[0]
+        let index = key.unwrap_or_else(|| self.current.load(Ordering::Relaxed));
+        &self.clients[index % self.clients.len()]
+    }
+
+    /// Moves on from `failed` if it is still the current endpoint, returning the url of the
+    /// next one. Nothing changes with a single endpoint.
+    pub fn fail_over(&self, failed: &str) -> Option<String> {
+        let current = self.current.load(Ordering::Relaxed);
+        if self.clients.len() < 2 || self.clients[current].url() != failed {
+            return None;
+        }
+        let next = (current + 1) % self.clients.len();
+        self.current
+            .compare_exchange(current, next, Ordering::Relaxed, Ordering::Relaxed)
+            .ok()
+            .map(|_| self.clients[next].url())
+    }
+
+    /// Request count and time spent per endpoint, in the order the urls were given.
+    pub fn transport_stats(&self) -> Vec<(String, RpcTransportStats)> {
+        self.clients
+            .iter()
+            .map(|client| (client.url(), client.get_transport_stats()))
+            .collect()
     }
 }
 
@@ -0,0 +1,198 @@
+use std::{
+    cmp::Reverse,
+    collections::{HashMap, HashSet},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Condvar, Mutex, RwLock,
+    },
+    time::Instant,
+};
+
+use ore::{BUS_COUNT, BUS_EPOCH_REWARDS, EPOCH_DURATION};
+use solana_sdk::{pubkey::Pubkey, signer::Signer};
+
+use crate::mine::{Miner, MinerLog};
+
+/// Snapshot of the treasury, busses and cluster clock used to rank queued miners.
+#[derive(Clone, Debug)]
+pub struct EpochWindow {
+    pub last_reset_at: i64,
+    pub reward_rate: u64,
+    pub bus_rewards: [u64; BUS_COUNT],
+    pub unix_timestamp: i64,
+    pub fetched_at: Instant,
+}
+
+impl EpochWindow {
+    pub fn now(&self) -> i64 {
+        self.unix_timestamp + self.fetched_at.elapsed().as_secs() as i64
+    }
+
+    pub fn seconds_left(&self) -> i64 {
+        (self.last_reset_at + EPOCH_DURATION - self.now()).max(0)
+    }
+
+    /// Number of proofs the bus can still pay out. Once the epoch is over every bus is refilled
+    /// by the next reset, so it counts as full.
+    pub fn bus_value(&self, bus: usize) -> u64 {
+        let rewards = if self.seconds_left() == 0 {
+            BUS_EPOCH_REWARDS
+        } else {
+            self.bus_rewards[bus % BUS_COUNT]
+        };
+        rewards / self.reward_rate.max(1)
+    }
+}
+
+struct Queued {
+    miner: Miner,
+    log: MinerLog,
+    since: Instant,
+}
+
+/// Hands a bounded number of miners to the hashers at a time and rotates the rest through a
+/// queue. The miner that waited longest goes first, so none starves, and is sent to the bus with
+/// the most proofs left once the miners already out on each bus are counted. Miners leave the
+/// queue while they are hashed or submitted and are pushed back once their next challenge is
+/// known.
+pub struct Scheduler {
+    slots: usize,
+    queue: Mutex<Vec<Queued>>,
+    ready: Condvar,
+    window: RwLock<EpochWindow>,
+    closed: AtomicBool,
+    /// Miners left in the queue until resumed. Locked after `queue`.
+    paused: Mutex<HashSet<Pubkey>>,
+    /// The bus of every miner out of the queue. Locked after `paused`.
+    claimed: Mutex<HashMap<Pubkey, usize>>,
+}
+
+impl Scheduler {
+    pub fn new(slots: usize, window: EpochWindow) -> Self {
+        Scheduler {
+            slots: slots.max(1),
+            queue: Mutex::new(vec![]),
+            ready: Condvar::new(),
+            window: RwLock::new(window),
+            closed: AtomicBool::new(false),
+            paused: Mutex::new(HashSet::new()),
+            claimed: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn slots(&self) -> usize {
+        self.slots
+    }
+
+    pub fn update_window(&self, window: EpochWindow) {
+        *self.window.write().unwrap() = window;
+    }
+
+    pub fn window(&self) -> EpochWindow {
+        self.window.read().unwrap().clone()
+    }
+
+    /// Wakes every waiting slot and makes `pop` return `None` from now on.
+    pub fn close(&self) {
+        self.closed.store(true, Ordering::SeqCst);
+        let _queue = self.queue.lock().unwrap();
+        self.ready.notify_all();
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::SeqCst)
+    }
+
+    /// Keeps the miner queued until it is resumed. A miner being hashed or submitted is paused
+    /// once it is pushed back.
+    pub fn pause(&self, miner: Pubkey) {
+        let _queue = self.queue.lock().unwrap();
+        self.paused.lock().unwrap().insert(miner);
+    }
+
+    pub fn resume(&self, miner: &Pubkey) {
+        let _queue = self.queue.lock().unwrap();
+        self.paused.lock().unwrap().remove(miner);
+        self.ready.notify_all();
+    }
+
+    pub fn is_paused(&self, miner: &Pubkey) -> bool {
+        self.paused.lock().unwrap().contains(miner)
+    }
+
+    pub fn push(&self, miner: Miner, log: MinerLog) {
+        let mut queue = self.queue.lock().unwrap();
+        self.claimed.lock().unwrap().remove(&miner.pubkey());
+        queue.push(Queued {
+            miner,
+            log,
+            since: Instant::now(),
+        });
+        self.ready.notify_one();
+    }
+
+    /// Removes a queued miner so it can be handled outside the hashing slots.
+    pub fn take(&self, miner: &Pubkey) -> Option<(Miner, MinerLog)> {
+        let mut queue = self.queue.lock().unwrap();
+        let index = queue
+            .iter()
+            .position(|queued| queued.miner.pubkey() == *miner)?;
+        let queued = queue.remove(index);
+        Some((queued.miner, queued.log))
+    }
+
+    /// Blocks until an unpaused miner is queued and takes the one that waited longest, its log
+    /// set to the bus it should mine.
+    pub fn pop(&self) -> Option<(Miner, MinerLog)> {
+        let mut queue = self
+            .ready
+            .wait_while(self.queue.lock().unwrap(), |queue| {
+                let paused = self.paused.lock().unwrap();
+                queue
+                    .iter()
+                    .all(|queued| paused.contains(&queued.miner.pubkey()))
+                    && !self.is_closed()
+            })
+            .unwrap();
+        self.take_best(&mut queue)
+    }
+
+    /// Like `pop`, but returns `None` right away if nothing is queued.
+    pub fn try_pop(&self) -> Option<(Miner, MinerLog)> {
+        self.take_best(&mut self.queue.lock().unwrap())
+    }
+
+    fn take_best(&self, queue: &mut Vec<Queued>) -> Option<(Miner, MinerLog)> {
+        if self.is_closed() {
+            return None;
+        }
+
+        let paused = self.paused.lock().unwrap();
+        let (index, _) = queue
+            .iter()
+            .enumerate()
+            .filter(|(_, queued)| !paused.contains(&queued.miner.pubkey()))
+            .min_by_key(|(_, queued)| queued.since)?;
+
+        let mut queued = queue.remove(index);
+        let mut claimed = self.claimed.lock().unwrap();
+        queued.log.bus = self.best_bus(&claimed);
+        claimed.insert(queued.miner.pubkey(), queued.log.bus);
+        Some((queued.miner, queued.log))
+    }
+
+    /// The bus with the most proofs left after one for every miner already out on it, the lowest
+    /// on a tie.
+    fn best_bus(&self, claimed: &HashMap<Pubkey, usize>) -> usize {
+        let window = self.window.read().unwrap();
+        (0..BUS_COUNT)
+            .max_by_key(|bus| {
+                let out = claimed.values().filter(|claimed| *claimed == bus).count();
+                (
+                    window.bus_value(*bus).saturating_sub(out as u64),
+                    Reverse(*bus),
+                )
+            })
+            .unwrap_or_default()
+    }
+}
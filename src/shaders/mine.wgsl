@@ -1,51 +1,59 @@
-struct Input {
+struct Job {
     prefix: array<u32, 16>,
     nonce: vec2<u32>,
     difficulty: array<u32, 8>,
 }
 
-@group(0) @binding(0) var<storage, read> input: Input;
-@group(0) @binding(1) var<storage, read_write> output_found: atomic<u32>;
-@group(0) @binding(2) var<storage, read_write> output_nonce: vec2<u32>;
+struct JobResult {
+    found: atomic<u32>,
+    nonce: vec2<u32>,
+}
+
+@group(0) @binding(0) var<storage, read> jobs: array<Job>;
+@group(0) @binding(1) var<storage, read_write> results: array<JobResult>;
 
 const prefix_u32_size: u32 = 16;
 const hash_u32_size: u32 = 256 / 32;
 const queue_size: u32 = 34;
 
+// x walks the nonces of a job, y selects the job.
 @compute @workgroup_size(16 * 16 * 2, 1) fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
-    if (atomicLoad(&output_found) >= 1) {
+    let job = global_id[1];
+    if (job >= arrayLength(&jobs)) {
+        return;
+    }
+    if (atomicLoad(&results[job].found) >= 1) {
         return;
     }
 
     var queue: array<u32, 34>;
 
     var nonce: vec2<u32>;
-    nonce[0] = input.nonce[0] + global_id[0];
-    nonce[1] = input.nonce[1] + global_id[1];
+    nonce[0] = jobs[job].nonce[0] + global_id[0];
+    nonce[1] = jobs[job].nonce[1];
+    if (nonce[0] < global_id[0]) {
+        nonce[1] = nonce[1] + 1;
+    }
 
-    absorb_prefix(&queue);
+    absorb_prefix(&queue, job);
 
-    let is_found = hash_with_nonce(&queue, nonce);
+    let is_found = hash_with_nonce(&queue, nonce, job);
     if (is_found) {
-        if (atomicLoad(&output_found) == 0) {
-            atomicStore(&output_found, 1u);
-            output_nonce = nonce;
-            return;
-        } else {
-            return;
+        if (atomicMax(&results[job].found, 1u) == 0) {
+            results[job].nonce = nonce;
         }
     }
 }
 
-fn absorb_prefix(queue: ptr<function, array<u32, 34>>) {
+fn absorb_prefix(queue: ptr<function, array<u32, 34>>, job: u32) {
     for (var i = 0u; i < prefix_u32_size; i++) {
-        (*queue)[i] = input.prefix[i];
+        (*queue)[i] = jobs[job].prefix[i];
     }
     (*queue)[prefix_u32_size + 2] = (*queue)[prefix_u32_size + 2] | 0x01;
     (*queue)[queue_size - 1] = (*queue)[queue_size - 1] | 0x80000000;
 }
 
-fn hash_with_nonce(queue: ptr<function, array<u32, 34>>, nonce: vec2<u32>) -> bool {
+fn hash_with_nonce(queue: ptr<function, array<u32, 34>>, nonce: vec2<u32>, job: u32) -> bool {
     var state: array<u32, 50>;
 
     (*queue)[prefix_u32_size] = nonce[0];
@@ -57,12 +65,16 @@ fn hash_with_nonce(queue: ptr<function, array<u32, 34>>, nonce: vec2<u32>) -> bo
 
     permutation(&state);
 
+    // Compares the digest with the difficulty byte by byte, like `hash <= difficulty` on-chain.
     for (var i = 0u; i < 8; i++) {
         let lhs_u32 = state[i];
-        let rhs_u32 = input.difficulty[i];
+        let rhs_u32 = jobs[job].difficulty[i];
         for (var j = 0u; j < 32; j += 8u) {
             let lhs_byte = lhs_u32 >> j & 0xff;
             let rhs_byte = rhs_u32 >> j & 0xff;
+            if (lhs_byte < rhs_byte) {
+                return true;
+            }
             if (lhs_byte > rhs_byte) {
                 return false;
             }
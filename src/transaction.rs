@@ -1,33 +1,116 @@
-use crate::rpc::RpcClient;
+use crate::{
+    dynamic_fee::DynamicFee,
+    errors::{CliError, Error},
+    events::EventBus,
+    fleet::CU_PER_TRANSFER,
+    jito::JitoClient,
+    ledger::{Attempt, Ledger, TransactionKind},
+    metrics::Outcome,
+    rpc::{endpoint_host, RpcClient},
+};
 use solana_client::{
     client_error::{ClientError, Result},
     rpc_config::RpcSendTransactionConfig,
 };
 use solana_sdk::{
-    clock::{Slot, MAX_HASH_AGE_IN_SECONDS, MAX_PROCESSING_AGE},
+    clock::{Slot, MAX_PROCESSING_AGE},
     commitment_config::CommitmentConfig,
     compute_budget::ComputeBudgetInstruction,
     hash::Hash,
     instruction::Instruction,
+    pubkey::Pubkey,
     signature::Signature,
     signer::Signer,
-    transaction::Transaction as RawTransaction,
+    system_instruction,
+    transaction::{Transaction as RawTransaction, TransactionError},
 };
-use solana_transaction_status::{TransactionConfirmationStatus, TransactionStatus};
+use solana_transaction_status::TransactionStatus;
 use std::{
     collections::HashSet,
     ops::{Deref, DerefMut},
-    time::{Duration, Instant},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
 };
 use tokio::time::sleep;
+use tracing::{debug, warn};
+
+/// Base fee of every signature a transaction carries.
+pub const LAMPORTS_PER_SIGNATURE: u64 = 5_000;
+
+/// Compute units an instruction gets without a compute unit limit, and the most a transaction can.
+const DEFAULT_INSTRUCTION_CU: u32 = 200_000;
+const MAX_TRANSACTION_CU: u32 = 1_400_000;
+
+/// How transactions are priced, resent and confirmed, for mining and fleet management alike.
+#[derive(Clone, Debug)]
+pub struct SendPolicy {
+    pub priority_fee: Option<u64>,
+    pub retries: usize,
+    pub confirm_interval: Duration,
+    pub skip_preflight: bool,
+    /// Replaces `priority_fee` with what recently landed, `priority_fee` stays the fallback.
+    pub dynamic_fee: Option<DynamicFee>,
+    /// Sends transactions as tipped bundles through the block engine instead of the RPC pool.
+    pub jito: Option<Arc<JitoClient>>,
+    /// Where every send attempt is recorded.
+    pub ledger: Option<Arc<Ledger>>,
+    /// Where claims and mining events are announced.
+    pub events: Option<Arc<EventBus>>,
+}
+
+impl Default for SendPolicy {
+    fn default() -> Self {
+        SendPolicy {
+            priority_fee: None,
+            retries: 10,
+            confirm_interval: Duration::from_millis(1000),
+            skip_preflight: false,
+            dynamic_fee: None,
+            jito: None,
+            ledger: None,
+            events: None,
+        }
+    }
+}
 
-pub struct Transactor {}
+impl SendPolicy {
+    /// Lamports the priority fee adds to a transaction limited to `cu_limit` compute units.
+    pub fn priority_fee_lamports(&self, cu_limit: u32) -> u64 {
+        let micro_lamports = self.priority_fee.unwrap_or(0) as u128 * cu_limit as u128;
+        micro_lamports.div_ceil(1_000_000) as u64
+    }
+
+    /// The policy with the dynamic fee, if any, read from `client` as its priority fee. Keeps the
+    /// static fee when the recent fees cannot be read.
+    pub async fn with_current_fee(&self, client: &RpcClient) -> SendPolicy {
+        let Some(dynamic_fee) = &self.dynamic_fee else {
+            return self.clone();
+        };
+        let priority_fee = match dynamic_fee.get(client).await {
+            Ok(fee) => Some(fee),
+            Err(err) => {
+                warn!(error = %err, "Failed to read recent priority fees, using the static fee");
+                self.priority_fee
+            }
+        };
+        SendPolicy {
+            priority_fee,
+            dynamic_fee: None,
+            ..self.clone()
+        }
+    }
+}
 
 #[derive(Clone)]
 pub struct Transaction {
     pub cu_limit: Option<u32>,
     pub cu_price: Option<u64>,
     pub instructions: Vec<Instruction>,
+    /// What the ledger files the transaction under.
+    pub kind: Option<TransactionKind>,
 }
 
 impl Transaction {
@@ -36,9 +119,21 @@ impl Transaction {
             cu_limit: None,
             cu_price: None,
             instructions,
+            kind: None,
         }
     }
 
+    pub fn set_kind(&mut self, kind: TransactionKind) {
+        self.kind = Some(kind);
+    }
+
+    /// Compute units the transaction is limited to, the runtime default without a limit.
+    pub fn compute_units(&self) -> u32 {
+        self.cu_limit.unwrap_or_else(|| {
+            (DEFAULT_INSTRUCTION_CU * self.instructions.len() as u32).min(MAX_TRANSACTION_CU)
+        })
+    }
+
     pub fn set_cu_limit(&mut self, units: u32) {
         self.cu_limit = Some(units);
     }
@@ -50,27 +145,24 @@ impl Transaction {
     pub async fn send(
         &self,
         client: &RpcClient,
-        signer: &dyn Signer,
-        fee_payer: Option<&dyn Signer>,
+        signer: &(dyn Signer + Sync),
+        fee_payer: Option<&(dyn Signer + Sync)>,
         skip_preflight: bool,
     ) -> Result<SentTransaction> {
-        let (blockhash, slot) = client
-            .get_latest_blockhash_with_commitment(CommitmentConfig::confirmed())
-            .await?;
-
-        let instructions = self.get_combined_instructions();
-
-        let mut signing_keypairs = vec![signer];
-        if let Some(fee_payer) = fee_payer {
-            signing_keypairs.push(fee_payer)
-        }
+        self.send_with_signers(client, &[signer], fee_payer, skip_preflight)
+            .await
+    }
 
-        let tx = RawTransaction::new_signed_with_payer(
-            &instructions,
-            fee_payer.map(|fee_payer| fee_payer.pubkey()).as_ref(),
-            &signing_keypairs,
-            blockhash,
-        );
+    /// Like `send`, for instructions that need several signers. Without a fee payer the first
+    /// signer pays.
+    pub async fn send_with_signers(
+        &self,
+        client: &RpcClient,
+        signers: &[&(dyn Signer + Sync)],
+        fee_payer: Option<&(dyn Signer + Sync)>,
+        skip_preflight: bool,
+    ) -> Result<SentTransaction> {
+        let (tx, blockhash, slot) = self.sign(client, signers, fee_payer).await?;
 
         let config = RpcSendTransactionConfig {
             skip_preflight,
@@ -88,7 +180,174 @@ impl Transaction {
         })
     }
 
-    fn get_combined_instructions(&self) -> Vec<Instruction> {
+    /// Like `send_with_signers`, sent to the block engine as a bundle of its own. The
+    /// transaction has to carry the tip.
+    pub async fn send_bundle(
+        &self,
+        client: &RpcClient,
+        jito: &JitoClient,
+        signers: &[&(dyn Signer + Sync)],
+        fee_payer: Option<&(dyn Signer + Sync)>,
+    ) -> Result<SentTransaction> {
+        let (tx, blockhash, slot) = self.sign(client, signers, fee_payer).await?;
+
+        let bundle = jito.send_jito_bundle(vec![&tx]).await?;
+        debug!(bundle, signature = %tx.signatures[0], "Sent bundle");
+
+        Ok(SentTransaction {
+            transaction: self.clone(),
+            blockhash,
+            slot,
+            signature: tx.signatures[0],
+        })
+    }
+
+    /// Signs with a fresh blockhash, returning the blockhash and the slot it was read at.
+    async fn sign(
+        &self,
+        client: &RpcClient,
+        signers: &[&(dyn Signer + Sync)],
+        fee_payer: Option<&(dyn Signer + Sync)>,
+    ) -> Result<(RawTransaction, Hash, Slot)> {
+        let (blockhash, slot) = client
+            .get_latest_blockhash_with_commitment(CommitmentConfig::confirmed())
+            .await?;
+
+        let instructions = self.get_combined_instructions();
+
+        let tx = {
+            let mut signing_keypairs: Vec<&dyn Signer> =
+                signers.iter().map(|signer| *signer as &dyn Signer).collect();
+            if let Some(fee_payer) = fee_payer {
+                signing_keypairs.push(fee_payer)
+            }
+
+            // A remote fee payer can fail to sign, which must not panic like `new_signed_with_payer`.
+            let mut tx = RawTransaction::new_with_payer(
+                &instructions,
+                fee_payer.map(|fee_payer| fee_payer.pubkey()).as_ref(),
+            );
+            tx.try_sign(&signing_keypairs, blockhash)?;
+            tx
+        };
+
+        Ok((tx, blockhash, slot))
+    }
+
+    /// Sends with the policy's priority fee, resending until the transaction lands or every
+    /// attempt has failed or expired. With a block engine the fee payer tips it and every attempt
+    /// goes out as a bundle. A transaction the program rejects, in preflight or on
+    /// chain, is not resent. Gives up with `CliError::Interrupted` once `stop` is set.
+    /// Every attempt is recorded in the policy's ledger, charged to the signer if there is only
+    /// one.
+    pub async fn send_and_confirm(
+        &self,
+        client: &RpcClient,
+        signers: &[&(dyn Signer + Sync)],
+        fee_payer: Option<&(dyn Signer + Sync)>,
+        policy: &SendPolicy,
+        stop: Option<&AtomicBool>,
+    ) -> std::result::Result<Signature, Error> {
+        let policy = &policy.with_current_fee(client).await;
+        let mut transaction = self.clone();
+        if let Some(priority_fee) = policy.priority_fee {
+            transaction.set_cu_price(priority_fee);
+        }
+        let tip = policy.jito.as_deref().map_or(0, |jito| jito.tip);
+        if let (Some(jito), Some(payer)) = (&policy.jito, fee_payer.or(signers.first().copied())) {
+            let tip_account = jito.get_random_tip_account();
+            transaction
+                .instructions
+                .push(system_instruction::transfer(&payer.pubkey(), &tip_account, tip));
+            if let Some(cu_limit) = transaction.cu_limit {
+                transaction.set_cu_limit(cu_limit + CU_PER_TRANSFER);
+            }
+        }
+
+        let url = client.url();
+        let endpoint = endpoint_host(&url);
+        let miner = match signers {
+            [signer] => Some(signer.pubkey()),
+            _ => None,
+        };
+        let mut payers = signers.iter().map(|signer| signer.pubkey()).collect::<Vec<Pubkey>>();
+        payers.extend(fee_payer.map(|fee_payer| fee_payer.pubkey()));
+        payers.sort();
+        payers.dedup();
+        let fee_lamports = LAMPORTS_PER_SIGNATURE * payers.len() as u64
+            + policy.priority_fee_lamports(transaction.compute_units())
+            + tip;
+        let record = |attempt, signature, outcome, error: Option<String>| {
+            if let Some(ledger) = &policy.ledger {
+                ledger.record_attempt(&Attempt {
+                    kind: self.kind,
+                    miner,
+                    signature,
+                    attempt,
+                    cu_limit: transaction.compute_units(),
+                    cu_price: transaction.cu_price,
+                    fee_lamports,
+                    endpoint,
+                    outcome,
+                    error,
+                });
+            }
+        };
+
+        let mut last_error = Error::CliError(CliError::TransactionNotLanded);
+        for attempt in 0..policy.retries.max(1) {
+            if stop.is_some_and(|stop| stop.load(Ordering::Relaxed)) {
+                return Err(Error::CliError(CliError::Interrupted));
+            }
+            let sent = match &policy.jito {
+                Some(jito) => transaction.send_bundle(client, jito, signers, fee_payer).await,
+                None => {
+                    transaction
+                        .send_with_signers(client, signers, fee_payer, policy.skip_preflight)
+                        .await
+                }
+            };
+            let sent = match sent {
+                Ok(sent) => sent,
+                Err(err) => {
+                    warn!(attempt, endpoint, error = %err, "Send error");
+                    record(attempt, None, Outcome::Failed, Some(err.to_string()));
+                    if is_final(&err) {
+                        return Err(Error::from(err));
+                    }
+                    last_error = Error::from(err);
+                    sleep(policy.confirm_interval).await;
+                    continue;
+                }
+            };
+            match sent
+                .confirm(client, CommitmentConfig::confirmed(), policy.confirm_interval)
+                .await
+            {
+                Ok(true) => {
+                    record(attempt, Some(sent.signature()), Outcome::Landed, None);
+                    return Ok(sent.signature());
+                }
+                Ok(false) => {
+                    warn!(attempt, endpoint, signature = %sent.signature(), "Expired");
+                    record(attempt, Some(sent.signature()), Outcome::Expired, None);
+                }
+                Err(err) => {
+                    warn!(attempt, endpoint, signature = %sent.signature(), error = %err, "Confirm error");
+                    record(attempt, Some(sent.signature()), Outcome::Failed, Some(err.to_string()));
+                    if is_final(&err) {
+                        return Err(Error::from(err));
+                    }
+                    last_error = Error::from(err);
+                }
+            }
+        }
+
+        Err(last_error)
+    }
+
+    /// The compute budget instructions followed by the transaction's own.
+    pub fn get_combined_instructions(&self) -> Vec<Instruction> {
         let mut instructions = vec![];
 
         if let Some(cu_limit) = self.cu_limit {
@@ -103,6 +362,21 @@ impl Transaction {
     }
 }
 
+/// Whether resending cannot help: the transaction itself is at fault, not the network.
+fn is_final(err: &ClientError) -> bool {
+    matches!(
+        err.get_transaction_error(),
+        Some(
+            TransactionError::InstructionError(..)
+                | TransactionError::InsufficientFundsForFee
+                | TransactionError::InsufficientFundsForRent { .. }
+                | TransactionError::AccountNotFound
+                | TransactionError::ProgramAccountNotFound
+                | TransactionError::InvalidAccountForFee
+        )
+    )
+}
+
 pub struct SentTransaction {
     transaction: Transaction,
     blockhash: Hash,
@@ -117,6 +391,20 @@ impl std::hash::Hash for SentTransaction {
 }
 
 impl SentTransaction {
+    pub fn signature(&self) -> Signature {
+        self.signature
+    }
+
+    pub fn blockhash(&self) -> Hash {
+        self.blockhash
+    }
+
+    pub fn slot(&self) -> Slot {
+        self.slot
+    }
+
+    /// Waits until the transaction reaches `commitment`, returning `false` once its blockhash
+    /// expired. A transaction that executed but failed is an error.
     pub async fn confirm(
         &self,
         client: &RpcClient,
@@ -130,7 +418,10 @@ impl SentTransaction {
                 .value[0]
             {
                 if status.satisfies_commitment(commitment) {
-                    return Ok(true);
+                    return match &status.err {
+                        Some(err) => Err(ClientError::from(err.clone())),
+                        None => Ok(true),
+                    };
                 }
             } else {
                 let current_slot = client.get_slot().await?;
@@ -144,7 +435,7 @@ impl SentTransaction {
     }
 
     pub fn is_expired(&self, current_slot: Slot) -> bool {
-        return current_slot > self.slot + MAX_PROCESSING_AGE as u64 + 1;
+        current_slot > self.slot + MAX_PROCESSING_AGE as u64 + 1
     }
 }
 
@@ -0,0 +1,469 @@
+//! `ore mine --tui`: a live dashboard of the running pipeline, with keys to pause a miner or
+//! claim its rewards.
+
+use std::{
+    collections::HashSet,
+    io::{self, Stdout},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use crossterm::{
+    event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
+    execute,
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use ore::ONE_ORE;
+use ratatui::{
+    backend::CrosstermBackend,
+    layout::{Constraint, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::Line,
+    widgets::{Block, Paragraph, Row, Table, TableState},
+    Frame, Terminal,
+};
+use solana_sdk::{native_token::lamports_to_sol, pubkey::Pubkey, signer::Signer};
+use tokio::time::sleep;
+use tracing::{info, warn};
+
+use crate::{
+    logging::LogBuffer,
+    metrics::{MinerStats, Outcome},
+    pipeline::{Pipeline, CU_LIMIT_MINE},
+    rpc::{endpoint_host, RpcTransportStats},
+    scheduler::EpochWindow,
+    transaction::SendPolicy,
+};
+
+const REFRESH: Duration = Duration::from_millis(250);
+/// How far back the RPC panel measures latency.
+const RPC_WINDOW: Duration = Duration::from_secs(10);
+
+/// Everything one frame shows.
+#[derive(Clone, Debug, Default)]
+pub struct Snapshot {
+    pub miners: Vec<MinerRow>,
+    pub window: Option<EpochWindow>,
+    pub send: SendPolicy,
+    pub fees_paid: u64,
+    pub landed: u64,
+    pub expired: u64,
+    pub failed: u64,
+    pub rpc: Vec<RpcHealth>,
+    /// `None` when logs go to a file instead.
+    pub logs: Option<Vec<String>>,
+}
+
+#[derive(Clone, Debug)]
+pub struct MinerRow {
+    pub pubkey: Pubkey,
+    pub stats: MinerStats,
+    pub paused: bool,
+    pub claiming: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct RpcHealth {
+    pub host: String,
+    pub requests: usize,
+    /// Mean latency over the last `RPC_WINDOW`, `None` without requests.
+    pub latency: Option<Duration>,
+    pub rate_limited: Duration,
+}
+
+pub struct Dashboard {
+    pipeline: Arc<Pipeline>,
+    logs: Option<LogBuffer>,
+    table: TableState,
+    claiming: Arc<Mutex<HashSet<Pubkey>>>,
+    rpc_sample: (Instant, Vec<RpcTransportStats>),
+    rpc_latency: Vec<Option<Duration>>,
+}
+
+impl Dashboard {
+    pub fn new(pipeline: Arc<Pipeline>, logs: Option<LogBuffer>) -> Self {
+        Dashboard {
+            pipeline,
+            logs,
+            table: TableState::default().with_selected(Some(0)),
+            claiming: Arc::default(),
+            rpc_sample: (Instant::now(), vec![]),
+            rpc_latency: vec![],
+        }
+    }
+
+    /// Draws until `q` is pressed or the pipeline stops on its own. Stopping the pipeline is
+    /// left to the caller.
+    pub async fn run(mut self) -> io::Result<()> {
+        let mut screen = Screen::enter()?;
+        loop {
+            let snapshot = self.snapshot().await;
+            screen
+                .0
+                .draw(|frame| draw(frame, &snapshot, &mut self.table))?;
+            if !self.pipeline.is_running().await {
+                return Ok(());
+            }
+
+            while event::poll(Duration::ZERO)? {
+                let Event::Key(key) = event::read()? else {
+                    continue;
+                };
+                if key.kind != KeyEventKind::Press {
+                    continue;
+                }
+                match key.code {
+                    KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                    KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        return Ok(())
+                    }
+                    KeyCode::Up | KeyCode::Char('k') => self.select(&snapshot, -1),
+                    KeyCode::Down | KeyCode::Char('j') => self.select(&snapshot, 1),
+                    KeyCode::Char('p') => {
+                        if let Some(row) = self.selected(&snapshot) {
+                            self.pipeline.set_paused(row.pubkey, !row.paused).await;
+                        }
+                    }
+                    KeyCode::Char('c') => {
+                        if let Some(row) = self.selected(&snapshot) {
+                            self.claim(row.pubkey);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            sleep(REFRESH).await;
+        }
+    }
+
+    async fn snapshot(&mut self) -> Snapshot {
+        let metrics = self.pipeline.metrics();
+        let claiming = self.claiming.lock().unwrap().clone();
+        let mut miners = vec![];
+        for (pubkey, stats) in metrics.miners() {
+            miners.push(MinerRow {
+                pubkey,
+                stats,
+                paused: self.pipeline.is_paused(&pubkey).await,
+                claiming: claiming.contains(&pubkey),
+            });
+        }
+
+        Snapshot {
+            miners,
+            window: metrics.window(),
+            send: self.pipeline.config().send.clone(),
+            fees_paid: metrics.priority_fee_lamports(),
+            landed: metrics.outcomes(Outcome::Landed),
+            expired: metrics.outcomes(Outcome::Expired),
+            failed: metrics.outcomes(Outcome::Failed),
+            rpc: self.rpc_health(),
+            logs: self.logs.as_ref().map(LogBuffer::lines),
+        }
+    }
+
+    /// Transport stats per endpoint, with the latency measured since the previous sample.
+    fn rpc_health(&mut self) -> Vec<RpcHealth> {
+        let stats = self.pipeline.ore().rpc_pool.transport_stats();
+        let (sampled_at, before) = &self.rpc_sample;
+        if self.rpc_latency.len() != stats.len() || sampled_at.elapsed() >= RPC_WINDOW {
+            self.rpc_latency = stats
+                .iter()
+                .enumerate()
+                .map(|(index, (_, now))| {
+                    let before = before.get(index).cloned().unwrap_or_default();
+                    let requests = now.request_count.saturating_sub(before.request_count);
+                    let elapsed = now.elapsed_time.saturating_sub(before.elapsed_time);
+                    (requests > 0).then(|| elapsed / requests as u32)
+                })
+                .collect();
+            self.rpc_sample = (
+                Instant::now(),
+                stats.iter().map(|(_, stats)| stats.clone()).collect(),
+            );
+        }
+
+        stats
+            .iter()
+            .zip(&self.rpc_latency)
+            .map(|((url, stats), latency)| RpcHealth {
+                host: endpoint_host(url).to_string(),
+                requests: stats.request_count,
+                latency: *latency,
+                rate_limited: stats.rate_limited_time,
+            })
+            .collect()
+    }
+
+    fn selected<'a>(&self, snapshot: &'a Snapshot) -> Option<&'a MinerRow> {
+        snapshot.miners.get(self.table.selected()?)
+    }
+
+    fn select(&mut self, snapshot: &Snapshot, step: isize) {
+        let count = snapshot.miners.len() as isize;
+        if count == 0 {
+            return;
+        }
+        let index = self.table.selected().unwrap_or(0) as isize + step;
+        self.table.select(Some(index.rem_euclid(count) as usize));
+    }
+
+    /// Claims everything the miner can claim in the background.
+    fn claim(&self, pubkey: Pubkey) {
+        if !self.claiming.lock().unwrap().insert(pubkey) {
+            return;
+        }
+        let pipeline = self.pipeline.clone();
+        let claiming = self.claiming.clone();
+        tokio::spawn(async move {
+            let ore = pipeline.ore();
+            if let Some(miner) = ore.miners.iter().find(|miner| miner.pubkey() == pubkey) {
+                match ore.claim(miner, None, &pipeline.config().send).await {
+                    Ok((amount, signature)) => info!(
+                        miner = %pubkey,
+                        %signature,
+                        ore = amount as f64 / ONE_ORE as f64,
+                        "Claimed"
+                    ),
+                    Err(err) => warn!(miner = %pubkey, error = %err.to_string(), "Claim failed"),
+                }
+                if let Ok(proof) = miner.get_proof(ore.get_client(None)).await {
+                    pipeline.metrics().record_proof(pubkey, &proof);
+                }
+            }
+            claiming.lock().unwrap().remove(&pubkey);
+        });
+    }
+}
+
+/// The raw mode alternate screen, restored on drop even when unwinding.
+struct Screen(Terminal<CrosstermBackend<Stdout>>);
+
+impl Screen {
+    fn enter() -> io::Result<Self> {
+        enable_raw_mode()?;
+        execute!(io::stdout(), EnterAlternateScreen)?;
+        Ok(Screen(Terminal::new(CrosstermBackend::new(io::stdout()))?))
+    }
+}
+
+impl Drop for Screen {
+    fn drop(&mut self) {
+        let _ = disable_raw_mode();
+        let _ = execute!(self.0.backend_mut(), LeaveAlternateScreen);
+        let _ = self.0.show_cursor();
+    }
+}
+
+pub fn draw(frame: &mut Frame, snapshot: &Snapshot, table: &mut TableState) {
+    let [panels, miners, logs, help] = Layout::vertical([
+        Constraint::Length(10),
+        Constraint::Min(5),
+        Constraint::Length(8),
+        Constraint::Length(1),
+    ])
+    .areas(frame.size());
+    let [epoch, busses, fees, rpc] = Layout::horizontal([
+        Constraint::Ratio(1, 4),
+        Constraint::Ratio(1, 4),
+        Constraint::Ratio(1, 4),
+        Constraint::Ratio(1, 4),
+    ])
+    .areas(panels);
+
+    draw_epoch(frame, epoch, snapshot);
+    draw_busses(frame, busses, snapshot);
+    draw_fees(frame, fees, snapshot);
+    draw_rpc(frame, rpc, snapshot);
+    draw_miners(frame, miners, snapshot, table);
+    draw_logs(frame, logs, snapshot);
+    frame.render_widget(
+        Paragraph::new(" ↑/↓ select   p pause/resume   c claim   q quit"),
+        help,
+    );
+}
+
+fn draw_epoch(frame: &mut Frame, area: Rect, snapshot: &Snapshot) {
+    let mut lines = match &snapshot.window {
+        Some(window) => {
+            let left = window.seconds_left();
+            vec![
+                Line::from(format!("Resets in     {}:{:02}", left / 60, left % 60)),
+                Line::from(format!("Reward rate   {}", format_ore(window.reward_rate))),
+            ]
+        }
+        None => vec![Line::from("Waiting for the epoch...")],
+    };
+    lines.push(Line::from(""));
+    lines.push(Line::from(format!("Landed        {}", snapshot.landed)));
+    lines.push(Line::from(format!("Expired       {}", snapshot.expired)));
+    lines.push(Line::from(format!("Failed        {}", snapshot.failed)));
+    frame.render_widget(
+        Paragraph::new(lines).block(Block::bordered().title(" Epoch ")),
+        area,
+    );
+}
+
+fn draw_busses(frame: &mut Frame, area: Rect, snapshot: &Snapshot) {
+    let lines = snapshot
+        .window
+        .iter()
+        .flat_map(|window| window.bus_rewards.iter().enumerate())
+        .map(|(bus, rewards)| Line::from(format!("Bus {}   {} ORE", bus, format_ore(*rewards))))
+        .collect::<Vec<_>>();
+    frame.render_widget(
+        Paragraph::new(lines).block(Block::bordered().title(" Busses ")),
+        area,
+    );
+}
+
+fn draw_fees(frame: &mut Frame, area: Rect, snapshot: &Snapshot) {
+    let priority_fee = snapshot.send.priority_fee.unwrap_or(0);
+    let per_transaction = snapshot.send.priority_fee_lamports(CU_LIMIT_MINE);
+    let lines = vec![
+        Line::from(format!("Priority fee  {} µlamports/CU", priority_fee)),
+        Line::from(format!("Per mine tx   {} lamports", per_transaction)),
+        Line::from(format!(
+            "Paid          {:.9} SOL",
+            lamports_to_sol(snapshot.fees_paid)
+        )),
+    ];
+    frame.render_widget(
+        Paragraph::new(lines).block(Block::bordered().title(" Fees ")),
+        area,
+    );
+}
+
+fn draw_rpc(frame: &mut Frame, area: Rect, snapshot: &Snapshot) {
+    let mut lines = vec![];
+    for rpc in &snapshot.rpc {
+        let latency = match rpc.latency {
+            Some(latency) => format!("{} ms", latency.as_millis()),
+            None => "idle".into(),
+        };
+        let style = match rpc.latency {
+            Some(latency) if latency > Duration::from_secs(1) => Style::default().fg(Color::Red),
+            _ => Style::default(),
+        };
+        lines.push(Line::styled(
+            rpc.host.clone(),
+            Style::default().add_modifier(Modifier::BOLD),
+        ));
+        lines.push(Line::styled(
+            format!("  {}  {} requests", latency, rpc.requests),
+            style,
+        ));
+        if !rpc.rate_limited.is_zero() {
+            lines.push(Line::from(format!(
+                "  rate limited {:.1}s",
+                rpc.rate_limited.as_secs_f64()
+            )));
+        }
+    }
+    frame.render_widget(
+        Paragraph::new(lines).block(Block::bordered().title(" RPC ")),
+        area,
+    );
+}
+
+fn draw_miners(frame: &mut Frame, area: Rect, snapshot: &Snapshot, table: &mut TableState) {
+    let header = Row::new([
+        "Miner",
+        "State",
+        "Challenge age",
+        "Hashrate",
+        "Landed",
+        "Last signature",
+        "Session ORE",
+        "Claimable ORE",
+    ])
+    .style(Style::default().add_modifier(Modifier::BOLD));
+    let rows = snapshot.miners.iter().map(|row| {
+        let state = if row.claiming {
+            "claiming".to_string()
+        } else if row.paused {
+            "paused".to_string()
+        } else {
+            row.stats.state.to_string()
+        };
+        let age = row
+            .stats
+            .challenge
+            .map_or("-".into(), |(_, since)| format_age(since.elapsed()));
+        let signature = row
+            .stats
+            .last_signature
+            .map_or("-".into(), |signature| shorten(&signature.to_string()));
+        Row::new([
+            shorten(&row.pubkey.to_string()),
+            state,
+            age,
+            format_rate(row.stats.hashes_per_second),
+            row.stats.landed.to_string(),
+            signature,
+            format_ore(row.stats.session_rewards()),
+            format_ore(row.stats.claimable_rewards),
+        ])
+    });
+    let widths = [
+        Constraint::Length(12),
+        Constraint::Length(10),
+        Constraint::Length(13),
+        Constraint::Length(12),
+        Constraint::Length(7),
+        Constraint::Length(14),
+        Constraint::Length(13),
+        Constraint::Length(13),
+    ];
+    let title = format!(" Miners ({}) ", snapshot.miners.len());
+    let widget = Table::new(rows, widths)
+        .header(header)
+        .block(Block::bordered().title(title))
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    frame.render_stateful_widget(widget, area, table);
+}
+
+fn draw_logs(frame: &mut Frame, area: Rect, snapshot: &Snapshot) {
+    let lines = match &snapshot.logs {
+        Some(logs) => {
+            let visible = area.height.saturating_sub(2) as usize;
+            logs[logs.len().saturating_sub(visible)..]
+                .iter()
+                .map(|line| Line::from(line.as_str()))
+                .collect()
+        }
+        None => vec![Line::from("Logs are written to --log-file")],
+    };
+    frame.render_widget(
+        Paragraph::new(lines).block(Block::bordered().title(" Log ")),
+        area,
+    );
+}
+
+/// The first and last characters of a pubkey or signature.
+fn shorten(value: &str) -> String {
+    match value.len() {
+        0..=11 => value.to_string(),
+        len => format!("{}…{}", &value[..5], &value[len - 5..]),
+    }
+}
+
+fn format_ore(amount: u64) -> String {
+    format!("{:.6}", amount as f64 / ONE_ORE as f64)
+}
+
+fn format_rate(hashes_per_second: f64) -> String {
+    match hashes_per_second {
+        rate if rate >= 1e6 => format!("{:.2} MH/s", rate / 1e6),
+        rate if rate >= 1e3 => format!("{:.1} kH/s", rate / 1e3),
+        rate => format!("{:.0} H/s", rate),
+    }
+}
+
+fn format_age(age: Duration) -> String {
+    let seconds = age.as_secs();
+    match seconds {
+        0..=59 => format!("{}s", seconds),
+        _ => format!("{}m{:02}s", seconds / 60, seconds % 60),
+    }
+}
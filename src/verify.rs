@@ -0,0 +1,65 @@
+use std::fmt::Display;
+
+use solana_sdk::{
+    keccak::{hashv, Hash as KeccakHash},
+    signer::Signer,
+};
+
+use crate::mine::{SignedWork, Work};
+
+/// Why found work was dropped instead of being submitted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Rejection {
+    HashInvalid,
+    DifficultyNotSatisfied,
+    StaleChallenge,
+}
+
+impl Rejection {
+    pub const ALL: [Rejection; 3] = [
+        Rejection::HashInvalid,
+        Rejection::DifficultyNotSatisfied,
+        Rejection::StaleChallenge,
+    ];
+
+    pub fn index(&self) -> usize {
+        *self as usize
+    }
+}
+
+impl Display for Rejection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+/// Repeats the program's `validate_hash` checks locally and makes sure the work answers the
+/// miner's current on-chain challenge, so a faulty backend never burns fees.
+pub fn verify_work(
+    signed_work: &SignedWork,
+    proof_hash: &KeccakHash,
+    difficulty: &KeccakHash,
+) -> Result<(), Rejection> {
+    let Work::ToBeProved(hash, nonce) = &signed_work.work else {
+        return Err(Rejection::HashInvalid);
+    };
+
+    let expected = hashv(&[
+        signed_work.challenge.as_ref(),
+        signed_work.signer.pubkey().as_ref(),
+        nonce.to_le_bytes().as_slice(),
+    ]);
+    if expected.ne(hash) {
+        return Err(Rejection::HashInvalid);
+    }
+
+    if hash.gt(difficulty) {
+        return Err(Rejection::DifficultyNotSatisfied);
+    }
+
+    if signed_work.challenge.ne(proof_hash) {
+        return Err(Rejection::StaleChallenge);
+    }
+
+    Ok(())
+}
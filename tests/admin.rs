@@ -0,0 +1,37 @@
+#![cfg(feature = "admin")]
+
+use ore_cli::admin::Difficulty;
+use solana_sdk::keccak::Hash as KeccakHash;
+
+#[test]
+fn leading_zero_bits_build_targets() {
+    let mut eight = [255u8; 32];
+    eight[0] = 0;
+    assert_eq!(Difficulty::from_leading_zeros(8).0, KeccakHash::new_from_array(eight));
+    assert_eq!("8".parse::<Difficulty>().unwrap().0, KeccakHash::new_from_array(eight));
+
+    let ten = Difficulty::from_leading_zeros(10).0.to_bytes();
+    assert_eq!(&ten[..3], &[0, 0x3f, 0xff]);
+    assert_eq!(Difficulty::from_leading_zeros(0).0.to_bytes(), [255; 32]);
+    assert_eq!(Difficulty::from_leading_zeros(256).0.to_bytes(), [0; 32]);
+    assert!("257".parse::<Difficulty>().is_err());
+
+    for bits in [0, 1, 7, 8, 9, 25, 255, 256] {
+        assert_eq!(Difficulty::from_leading_zeros(bits).leading_zeros(), bits);
+    }
+}
+
+#[test]
+fn hex_targets_parse() {
+    let mut target = [255u8; 32];
+    target[..4].copy_from_slice(&[0, 0, 0, 64]);
+    let hex = hex::encode(target);
+    let difficulty = hex.parse::<Difficulty>().unwrap();
+    assert_eq!(difficulty.0, KeccakHash::new_from_array(target));
+    assert_eq!(difficulty.leading_zeros(), 25);
+    assert_eq!(format!("0x{}", hex).parse::<Difficulty>().unwrap(), difficulty);
+    assert_eq!(difficulty.to_string(), format!("{} (25 leading zero bits)", hex));
+
+    assert!("00ff".parse::<Difficulty>().is_err());
+    assert!(format!("{}zz", &hex[2..]).parse::<Difficulty>().is_err());
+}
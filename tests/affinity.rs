@@ -0,0 +1,79 @@
+use ore_cli::affinity::{CoreList, WorkerConfig};
+
+#[test]
+fn core_lists_expand_ranges_sorted_and_deduplicated() {
+    let cores = "6, 0-3,2,3-4".parse::<CoreList>().unwrap();
+    assert_eq!(cores, CoreList(vec![0, 1, 2, 3, 4, 6]));
+    assert_eq!(cores.to_string(), "0,1,2,3,4,6");
+    assert_eq!(cores.to_string().parse::<CoreList>().unwrap(), cores);
+
+    assert_eq!("".parse::<CoreList>().unwrap(), CoreList(vec![]));
+    assert_eq!("5-5".parse::<CoreList>().unwrap(), CoreList(vec![5]));
+}
+
+#[test]
+fn malformed_core_lists_are_rejected() {
+    let err = "5-3".parse::<CoreList>().unwrap_err();
+    assert!(err.contains("5-3"), "{}", err);
+
+    assert!("a".parse::<CoreList>().is_err());
+    assert!("0-".parse::<CoreList>().is_err());
+    assert!("1,-2".parse::<CoreList>().is_err());
+    assert!(CoreList::try_from("3-1".to_string()).is_err());
+}
+
+#[test]
+fn explicit_cores_leave_out_the_rpc_core() {
+    let workers = WorkerConfig {
+        cores: vec![0, 1, 2, 3],
+        rpc_core: Some(2),
+        ..Default::default()
+    };
+    assert_eq!(workers.hasher_cores().unwrap(), vec![0, 1, 3]);
+    assert_eq!(workers.num_threads().unwrap(), 3);
+
+    let workers = WorkerConfig {
+        cores: vec![4, 5],
+        rpc_core: Some(0),
+        ..Default::default()
+    };
+    assert_eq!(workers.hasher_cores().unwrap(), vec![4, 5]);
+    assert_eq!(workers.num_threads().unwrap(), 2);
+}
+
+#[test]
+fn thread_count_overrides_the_core_split() {
+    let workers = WorkerConfig {
+        threads: Some(8),
+        cores: vec![0, 1],
+        ..Default::default()
+    };
+    assert_eq!(workers.hasher_cores().unwrap(), vec![0, 1]);
+    assert_eq!(workers.num_threads().unwrap(), 8);
+
+    let workers = WorkerConfig {
+        threads: Some(0),
+        ..Default::default()
+    };
+    assert_eq!(workers.num_threads().unwrap(), 1);
+}
+
+#[test]
+fn without_cores_or_rpc_core_nothing_is_pinned() {
+    let workers = WorkerConfig::default();
+    assert!(workers.hasher_cores().unwrap().is_empty());
+    assert!(workers.num_threads().unwrap() >= 1);
+}
+
+#[test]
+fn reserving_the_only_listed_core_is_an_error() {
+    let workers = WorkerConfig {
+        cores: vec![2],
+        rpc_core: Some(2),
+        ..Default::default()
+    };
+    let err = workers.hasher_cores().unwrap_err();
+    assert_eq!(err, "no cores left for hashing after reserving rpc core 2");
+    assert!(workers.num_threads().is_err());
+    assert!(workers.build_pool().is_err());
+}
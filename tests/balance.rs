@@ -0,0 +1,83 @@
+mod common;
+
+use std::{fs, thread, time::Duration};
+
+use ore_cli::{
+    balance::{BalanceConfig, BalanceGuard, BalanceLevel},
+    config::Settings,
+};
+use solana_sdk::{native_token::LAMPORTS_PER_SOL, pubkey::Pubkey};
+
+#[test]
+fn levels_follow_the_floor_and_warning() {
+    let config = BalanceConfig::default();
+    assert_eq!(config.level(LAMPORTS_PER_SOL), BalanceLevel::Healthy);
+    assert_eq!(config.level(LAMPORTS_PER_SOL / 100), BalanceLevel::Healthy);
+    assert_eq!(config.level(LAMPORTS_PER_SOL / 200), BalanceLevel::Warning);
+    assert_eq!(config.level(LAMPORTS_PER_SOL / 1000), BalanceLevel::Floor);
+    assert_eq!(config.level(0), BalanceLevel::Floor);
+}
+
+#[test]
+fn alerts_once_per_level_change_and_reports_the_depleted_wallet() {
+    let dir = common::temp_dir("balance");
+    let alerts = dir.join("alerts");
+    let wallet = Pubkey::new_unique();
+    let guard = BalanceGuard::new(
+        BalanceConfig {
+            alert_command: Some(format!(
+                "echo \"$ORE_ALERT $ORE_WALLET $ORE_BALANCE_LAMPORTS\" >> {}",
+                alerts.display()
+            )),
+            ..Default::default()
+        },
+        vec![wallet],
+    );
+
+    assert_eq!(
+        guard.observe(wallet, LAMPORTS_PER_SOL),
+        BalanceLevel::Healthy
+    );
+    thread::sleep(Duration::from_millis(200));
+    assert_eq!(guard.observe(wallet, 5_000_000), BalanceLevel::Warning);
+    thread::sleep(Duration::from_millis(200));
+    assert_eq!(guard.observe(wallet, 4_000_000), BalanceLevel::Warning);
+    assert_eq!(guard.depleted(), None);
+    thread::sleep(Duration::from_millis(200));
+    assert_eq!(guard.observe(wallet, 1_000_000), BalanceLevel::Floor);
+    assert_eq!(guard.depleted(), Some(wallet));
+
+    let mut lines = vec![];
+    for _ in 0..50 {
+        lines = fs::read_to_string(&alerts)
+            .unwrap_or_default()
+            .lines()
+            .map(String::from)
+            .collect::<Vec<_>>();
+        if lines.len() == 2 {
+            break;
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+    assert_eq!(
+        lines,
+        [
+            format!("warning {} 5000000", wallet),
+            format!("floor {} 1000000", wallet),
+        ]
+    );
+}
+
+#[test]
+fn settings_set_thresholds_in_sol() {
+    let settings = Settings {
+        balance_floor: Some(0.002),
+        top_up_target: Some(0.5),
+        ..Settings::builtin()
+    };
+    let config = settings.balance_config();
+    assert_eq!(config.floor, 2_000_000);
+    assert_eq!(config.warning, 10_000_000);
+    assert_eq!(config.top_up_target, 500_000_000);
+    assert!(config.funder.is_none());
+}
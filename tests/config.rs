@@ -0,0 +1,180 @@
+mod common;
+
+use std::{fs, path::PathBuf, process::Command, time::Duration};
+
+use ore_cli::{
+    affinity::{BackendKind, CoreList},
+    config::{ConfigFile, Settings},
+    dynamic_fee::DynamicFee,
+    fleet::{DeriveRange, KeySource},
+    jito::DEFAULT_TIP,
+};
+
+const CONFIG: &str = r#"
+profile = "mainnet"
+
+[default]
+priority_fee = 10000
+retries = 5
+miners_seed = "/keys/seed.txt"
+miners_derive = "0..4"
+cores = "0-3,6"
+
+[profiles.mainnet]
+rpc = ["https://api.mainnet-beta.solana.com"]
+
+[profiles.devnet]
+rpc = ["https://api.devnet.solana.com", "http://localhost:8899"]
+priority_fee = 0
+backend = "gpu"
+"#;
+
+#[test]
+fn profiles_lay_over_the_default_table() {
+    let file = toml::from_str::<ConfigFile>(CONFIG).unwrap();
+
+    let mainnet = file.settings(None).unwrap();
+    assert_eq!(mainnet.rpc.unwrap(), vec!["https://api.mainnet-beta.solana.com"]);
+    assert_eq!(mainnet.priority_fee, Some(10000));
+    assert_eq!(mainnet.cores, Some(CoreList(vec![0, 1, 2, 3, 6])));
+    assert_eq!(mainnet.miners_derive, Some(DeriveRange(0..4)));
+    assert_eq!(mainnet.backend, None);
+
+    let devnet = file.settings(Some("devnet")).unwrap();
+    assert_eq!(devnet.rpc.unwrap().len(), 2);
+    assert_eq!(devnet.priority_fee, Some(0));
+    assert_eq!(devnet.retries, Some(5));
+    assert_eq!(devnet.backend, Some(BackendKind::Gpu));
+
+    assert!(file.settings(Some("testnet")).is_err());
+}
+
+#[test]
+fn command_line_wins_over_the_config_file() {
+    let file = toml::from_str::<ConfigFile>(CONFIG).unwrap();
+    let cli = Settings {
+        rpc: Some(vec!["http://127.0.0.1:8899".into()]),
+        retries: Some(1),
+        ..Default::default()
+    };
+    let settings = Settings::resolve(cli, Some(&file), Some("devnet")).unwrap();
+    assert_eq!(settings.rpc.as_ref().unwrap(), &vec!["http://127.0.0.1:8899".to_string()]);
+    assert_eq!(settings.retries, Some(1));
+    assert_eq!(settings.priority_fee, Some(0));
+    assert!(settings.owner.is_some());
+
+    let policy = settings.send_policy();
+    assert_eq!(policy.retries, 1);
+    assert_eq!(policy.priority_fee, Some(0));
+    assert_eq!(policy.confirm_interval, Duration::from_millis(1000));
+    assert_eq!(settings.worker_config().backend, BackendKind::Gpu);
+    assert_eq!(settings.pipeline_config().drain_timeout, Duration::from_secs(30));
+
+    let sources = settings.key_sources("passphrase");
+    assert!(matches!(
+        &sources[..],
+        [KeySource::Derived { seed_file, passphrase, range }]
+            if seed_file == &PathBuf::from("/keys/seed.txt") && passphrase == "passphrase" && *range == (0..4)
+    ));
+
+    assert!(Settings::resolve(Settings::default(), None, Some("devnet")).is_err());
+}
+
+#[test]
+fn resolved_settings_roundtrip_through_toml() {
+    let file = toml::from_str::<ConfigFile>(CONFIG).unwrap();
+    let settings = Settings::builtin().merge(file.settings(Some("devnet")).unwrap());
+    let printed = toml::to_string(&settings).unwrap();
+    assert!(printed.contains("backend = \"gpu\""));
+    assert!(printed.contains("cores = \"0,1,2,3,6\""));
+    assert_eq!(toml::from_str::<Settings>(&printed).unwrap(), settings);
+}
+
+#[test]
+fn unknown_keys_are_rejected() {
+    assert!(toml::from_str::<ConfigFile>("[default]\nrpc_url = \"http://x\"").is_err());
+    assert!(toml::from_str::<ConfigFile>("[profiles.devnet]\nbackend = \"tpu\"").is_err());
+    assert!(toml::from_str::<ConfigFile>("[default]\ncores = \"a-b\"").is_err());
+}
+
+#[test]
+fn empty_rpc_pool_is_rejected() {
+    let file = toml::from_str::<ConfigFile>("[default]\nrpc = []").unwrap();
+    assert!(Settings::resolve(Settings::default(), Some(&file), None).is_err());
+}
+
+#[test]
+fn dynamic_fee_settings_build_the_policy() {
+    let file = toml::from_str::<ConfigFile>(
+        "[default]\ndynamic_fee = true\ndynamic_fee_min = 100000\ndynamic_fee_max = 40000000\n\
+         jito_url = \"http://127.0.0.1:1\"",
+    )
+    .unwrap();
+    let settings = Settings::builtin().merge(file.settings(None).unwrap());
+    let policy = settings.send_policy();
+    assert_eq!(
+        policy.dynamic_fee,
+        Some(DynamicFee {
+            min_fee: Some(100_000),
+            max_fee: Some(40_000_000),
+            uplift_fee: 0,
+            baseline_pct: 70,
+        })
+    );
+    // The block engine is connected to by the caller.
+    assert!(policy.jito.is_none());
+    assert_eq!(settings.jito_url.as_deref(), Some("http://127.0.0.1:1"));
+    assert_eq!(settings.jito_tip, Some(DEFAULT_TIP));
+
+    let cli = Settings {
+        dynamic_fee: Some(false),
+        ..Default::default()
+    };
+    assert_eq!(settings.merge(cli).send_policy().dynamic_fee, None);
+    assert_eq!(Settings::builtin().send_policy().dynamic_fee, None);
+}
+
+#[test]
+fn rpc_list_json_fills_the_rpc_pool() {
+    let dir = common::temp_dir("rpc-list");
+    let path = dir.join("rpc_list.json");
+    fs::write(
+        &path,
+        r#"{"default_rpc_list":["http://a:8899","http://b:8899"],"submit_rpc_list":["http://c:8899"]}"#,
+    )
+    .unwrap();
+    let rpc_list = Settings::rpc_list(&path).unwrap();
+    assert_eq!(rpc_list.rpc.clone().unwrap(), vec!["http://a:8899", "http://b:8899"]);
+
+    // A config file still wins over it.
+    let file = toml::from_str::<ConfigFile>(CONFIG).unwrap();
+    let settings = rpc_list.merge(file.settings(Some("devnet")).unwrap());
+    assert_eq!(settings.rpc.unwrap()[0], "https://api.devnet.solana.com");
+
+    fs::write(&path, r#"{"default_rpc_list":[]}"#).unwrap();
+    assert_eq!(Settings::rpc_list(&path).unwrap().rpc, None);
+    fs::write(&path, "[]").unwrap();
+    assert!(Settings::rpc_list(&path).is_err());
+}
+
+#[test]
+fn skip_preflight_can_be_turned_off_on_the_command_line() {
+    let dir = common::temp_dir("skip-preflight");
+    let config = dir.join("config.toml");
+    fs::write(&config, "[default]\nskip_preflight = true\n").unwrap();
+    let show = |flags: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_ore"))
+            .args(["--config", config.to_str().unwrap(), "--rpc", "http://127.0.0.1:1"])
+            .args(["config", "show"])
+            .args(flags)
+            .env("HOME", &dir)
+            .env_remove("ORE_PROFILE")
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        toml::from_str::<Settings>(&String::from_utf8(output.stdout).unwrap()).unwrap()
+    };
+    assert_eq!(show(&[]).skip_preflight, Some(true));
+    assert_eq!(show(&["--skip-preflight=false"]).skip_preflight, Some(false));
+    assert_eq!(show(&["--skip-preflight"]).skip_preflight, Some(true));
+}
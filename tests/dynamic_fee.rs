@@ -0,0 +1,46 @@
+use ore_cli::{dynamic_fee::DynamicFee, rpc::RpcClient, transaction::SendPolicy};
+
+#[test]
+fn dynamic_fee_takes_a_percentile_of_the_recent_fees_plus_the_uplift() {
+    let fee = DynamicFee {
+        uplift_fee: 5,
+        baseline_pct: 70,
+        ..Default::default()
+    };
+    // Zero fees are left out, the 70th percentile of 10..=100 is 80.
+    let recent = vec![0, 100, 10, 90, 20, 80, 30, 70, 40, 60, 50, 0];
+    assert_eq!(fee.fee_for(recent), 85);
+
+    let highest = DynamicFee {
+        baseline_pct: 100,
+        ..Default::default()
+    };
+    assert_eq!(highest.fee_for(vec![3, 1, 2]), 3);
+}
+
+#[test]
+fn dynamic_fee_is_clamped_and_survives_no_recent_fees() {
+    let fee = DynamicFee {
+        min_fee: Some(1_000),
+        max_fee: Some(50_000),
+        uplift_fee: 0,
+        baseline_pct: 50,
+    };
+    assert_eq!(fee.fee_for(vec![]), 1_000);
+    assert_eq!(fee.fee_for(vec![0, 0]), 1_000);
+    assert_eq!(fee.fee_for(vec![20_000]), 20_000);
+    assert_eq!(fee.fee_for(vec![1_000_000]), 50_000);
+}
+
+#[tokio::test]
+async fn unreadable_recent_fees_fall_back_to_the_static_fee() {
+    let policy = SendPolicy {
+        priority_fee: Some(10_000),
+        dynamic_fee: Some(DynamicFee::default()),
+        ..Default::default()
+    };
+    let client = RpcClient::new("http://127.0.0.1:1".into());
+    let current = policy.with_current_fee(&client).await;
+    assert_eq!(current.priority_fee, Some(10_000));
+    assert_eq!(current.dynamic_fee, None);
+}
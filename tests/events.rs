@@ -0,0 +1,154 @@
+mod common;
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::{BufRead, BufReader, Read, Write},
+    net::TcpListener,
+    path::PathBuf,
+    thread,
+    time::Duration,
+};
+
+use common::temp_dir;
+use ore_cli::events::{Event, EventBus, EventConfig, EventKind, RateLimit};
+use serde_json::{json, Value};
+
+fn read_lines(path: &PathBuf) -> Vec<Value> {
+    fs::read_to_string(path)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect()
+}
+
+fn landed(miner: &str) -> Event {
+    Event::ProofLanded {
+        miner: miner.into(),
+        signature: "sig".into(),
+    }
+}
+
+#[test]
+fn filters_and_rate_limits_per_kind() {
+    let dir = temp_dir("events-log");
+    let log = dir.join("events.jsonl");
+    let bus = EventBus::start(EventConfig {
+        log_file: Some(log.clone()),
+        kinds: vec![EventKind::ProofLanded, EventKind::BusDrained],
+        rate_limits: BTreeMap::from([(EventKind::ProofLanded, Duration::from_millis(300))]),
+        ..Default::default()
+    })
+    .unwrap();
+
+    assert!(bus.emit(landed("a")));
+    assert!(!bus.emit(landed("b")));
+    assert!(!bus.emit(landed("c")));
+    assert!(bus.emit(Event::BusDrained { bus: 3, rewards: 7 }));
+    assert!(!bus.emit(Event::EpochReset {
+        last_reset_at: 1,
+        reward_rate: 2
+    }));
+    thread::sleep(Duration::from_millis(350));
+    assert!(bus.emit(landed("d")));
+    bus.close();
+    assert!(!bus.emit(Event::BusDrained { bus: 4, rewards: 0 }));
+
+    let lines = read_lines(&log);
+    assert_eq!(lines.len(), 3);
+    assert_eq!(lines[0]["event"], "proof-landed");
+    assert_eq!(lines[0]["miner"], "a");
+    assert!(lines[0].get("suppressed").is_none());
+    assert_eq!(lines[1]["event"], "bus-drained");
+    assert_eq!(lines[1]["bus"], 3);
+    assert_eq!(lines[2]["miner"], "d");
+    assert_eq!(lines[2]["suppressed"], 2);
+}
+
+#[test]
+fn runs_scripts_with_the_payload() {
+    let dir = temp_dir("events-script");
+    let out = dir.join("out");
+    let bus = EventBus::start(EventConfig {
+        script: Some(format!(
+            "printf '%s %s' \"$ORE_EVENT\" \"$ORE_EVENT_JSON\" > {}",
+            out.display()
+        )),
+        ..Default::default()
+    })
+    .unwrap();
+    bus.emit(Event::FeePayerLow {
+        wallet: "owner".into(),
+        lamports: 900_000,
+        level: "floor",
+    });
+    drop(bus);
+
+    let out = fs::read_to_string(out).unwrap();
+    let (kind, payload) = out.split_once(' ').unwrap();
+    assert_eq!(kind, "fee-payer-low");
+    let mut payload = serde_json::from_str::<Value>(payload).unwrap();
+    assert!(payload["time"].as_str().unwrap().ends_with('Z'));
+    payload.as_object_mut().unwrap().remove("time");
+    assert_eq!(
+        payload,
+        json!({ "event": "fee-payer-low", "wallet": "owner", "lamports": 900_000, "level": "floor" })
+    );
+}
+
+#[test]
+fn posts_to_webhooks() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let server = thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream);
+        let (mut request, mut length) = (String::new(), 0);
+        reader.read_line(&mut request).unwrap();
+        loop {
+            let mut header = String::new();
+            reader.read_line(&mut header).unwrap();
+            if header.trim().is_empty() {
+                break;
+            }
+            if let Some(value) = header.to_lowercase().strip_prefix("content-length:") {
+                length = value.trim().parse().unwrap();
+            }
+        }
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body).unwrap();
+        reader
+            .get_mut()
+            .write_all(b"HTTP/1.1 204 No Content\r\nConnection: close\r\n\r\n")
+            .unwrap();
+        (request, body)
+    });
+
+    let bus = EventBus::start(EventConfig {
+        webhooks: vec![format!("http://{}/hook", address)],
+        ..Default::default()
+    })
+    .unwrap();
+    bus.emit(Event::ClaimCompleted {
+        miner: "miner".into(),
+        amount: 42,
+        signature: "sig".into(),
+    });
+    drop(bus);
+
+    let (request, body) = server.join().unwrap();
+    assert!(request.starts_with("POST /hook "));
+    let body = serde_json::from_slice::<Value>(&body).unwrap();
+    assert_eq!(body["event"], "claim-completed");
+    assert_eq!(body["amount"], 42);
+}
+
+#[test]
+fn parses_rate_limits() {
+    assert_eq!(
+        "submission-failed=300".parse::<RateLimit>(),
+        Ok(RateLimit(EventKind::SubmissionFailed, 300))
+    );
+    assert!("rpc-down".parse::<RateLimit>().is_err());
+    assert!("stalled=5".parse::<RateLimit>().is_err());
+}
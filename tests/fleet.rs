@@ -0,0 +1,183 @@
+mod common;
+
+use std::fs;
+
+use common::temp_dir;
+use ore_cli::{
+    fleet::{
+        batch_transaction, derivation_path, derive_keypairs, read_seed_file, DeriveRange,
+        KeySource, CU_PER_REGISTER, CU_PER_TRANSFER, FUND_BATCH, REGISTER_BATCH,
+    },
+    keystore::{PassphraseSource, Unlocker},
+    ledger::TransactionKind,
+    Miner,
+};
+use solana_sdk::{
+    compute_budget::ComputeBudgetInstruction,
+    pubkey::Pubkey,
+    signature::write_keypair_file,
+    signer::{keypair::keypair_from_seed_and_derivation_path, Signer},
+    system_instruction,
+};
+
+const MNEMONIC: &str =
+    "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+
+#[test]
+fn derive_range_parses() {
+    assert_eq!("0..64".parse::<DeriveRange>().unwrap().0, 0..64);
+    assert_eq!(" 3 .. 5 ".parse::<DeriveRange>().unwrap().0, 3..5);
+    assert_eq!("7".parse::<DeriveRange>().unwrap().0, 7..8);
+    assert!("a..3".parse::<DeriveRange>().is_err());
+    assert!("5..2".parse::<DeriveRange>().unwrap_err().contains("5..2"));
+    assert!("3..3".parse::<DeriveRange>().is_err());
+    assert!(DeriveRange::try_from("9..1".to_string()).is_err());
+}
+
+#[test]
+fn mnemonic_and_hex_seed_derive_the_same_miners() {
+    let dir = temp_dir("seed");
+    let mnemonic_path = dir.join("mnemonic");
+    fs::write(
+        &mnemonic_path,
+        format!("  {}\n", MNEMONIC.replace(' ', "\n")),
+    )
+    .unwrap();
+    let seed = read_seed_file(&mnemonic_path, "").unwrap();
+    assert_eq!(seed.len(), 64);
+
+    let hex_path = dir.join("seed.hex");
+    let hex = seed.iter().fold(String::new(), |mut hex, byte| {
+        hex.push_str(&format!("{:02x}", byte));
+        hex
+    });
+    fs::write(&hex_path, hex).unwrap();
+    assert_eq!(read_seed_file(&hex_path, "").unwrap(), seed);
+    assert_ne!(read_seed_file(&mnemonic_path, "passphrase").unwrap(), seed);
+
+    let derived = Miner::load(
+        &KeySource::Derived {
+            seed_file: hex_path,
+            passphrase: String::new(),
+            range: 2..5,
+        },
+        &Unlocker::new(PassphraseSource::Prompt),
+    )
+    .unwrap();
+    let keypairs = derive_keypairs(&seed, 0..5).unwrap();
+    assert_eq!(derived.len(), 3);
+    for (miner, keypair) in derived.iter().zip(&keypairs[2..]) {
+        assert_eq!(miner.pubkey(), keypair.pubkey());
+    }
+    assert_eq!(format!("{:?}", derivation_path(3)), "m/44'/501'/3'/0'");
+    assert_eq!(
+        keypairs[3].pubkey(),
+        keypair_from_seed_and_derivation_path(&seed, Some(derivation_path(3)))
+            .unwrap()
+            .pubkey()
+    );
+
+    let mut pubkeys = keypairs
+        .iter()
+        .map(|keypair| keypair.pubkey())
+        .collect::<Vec<_>>();
+    pubkeys.sort();
+    pubkeys.dedup();
+    assert_eq!(pubkeys.len(), 5);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn mnemonic_derives_the_known_wallet() {
+    let dir = temp_dir("known-seed");
+    let path = dir.join("mnemonic");
+    fs::write(&path, MNEMONIC).unwrap();
+
+    // The BIP39 test vector's seed and the wallet Solana wallets derive from it.
+    let seed = read_seed_file(&path, "").unwrap();
+    assert_eq!(
+        hex::encode(&seed),
+        "5eb00bbddcf069084889a8ab9155568165f5c453ccb85e70811aaed6f6da5fc1\
+         9a5ac40b389cd370d086206dec8aa6c43daea6690f20ad3d8d48b2d2ce9e38e4"
+    );
+    assert_eq!(
+        derive_keypairs(&seed, 0..1).unwrap()[0]
+            .pubkey()
+            .to_string(),
+        "HAgk14JpMQLgt6rVgv7cBQFJWFto5Dqxi472uT3DKpqk"
+    );
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn bad_mnemonic_is_rejected() {
+    let dir = temp_dir("bad-seed");
+    let path = dir.join("mnemonic");
+    fs::write(&path, MNEMONIC.replace("about", "abandon")).unwrap();
+    assert!(read_seed_file(&path, "").is_err());
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn keypair_dir_loads_json_files() {
+    let dir = temp_dir("keys");
+    let keypairs = derive_keypairs(&[7; 64], 0..3).unwrap();
+    for keypair in &keypairs {
+        write_keypair_file(keypair, dir.join(format!("{}.json", keypair.pubkey()))).unwrap();
+    }
+    fs::write(dir.join("notes.txt"), "not a keypair").unwrap();
+
+    let mut expected = keypairs
+        .iter()
+        .map(|keypair| keypair.pubkey())
+        .collect::<Vec<_>>();
+    expected.sort_by_key(|pubkey| pubkey.to_string());
+    let loaded = Miner::load(
+        &KeySource::Dir(dir.clone()),
+        &Unlocker::new(PassphraseSource::Prompt),
+    )
+    .unwrap();
+    assert_eq!(
+        loaded
+            .iter()
+            .map(|miner| miner.pubkey())
+            .collect::<Vec<_>>(),
+        expected
+    );
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn batches_are_limited_to_the_units_they_use() {
+    let owner = Pubkey::new_unique();
+    let transfers = (0..FUND_BATCH)
+        .map(|_| system_instruction::transfer(&owner, &Pubkey::new_unique(), 1))
+        .collect::<Vec<_>>();
+    let mut transaction =
+        batch_transaction(TransactionKind::Fund, CU_PER_TRANSFER, transfers.clone());
+    transaction.set_cu_price(10_000);
+
+    let limit = transaction.compute_units();
+    assert_eq!(limit, 300 + FUND_BATCH as u32 * CU_PER_TRANSFER);
+    assert!(limit < 200_000);
+    let mut expected = vec![
+        ComputeBudgetInstruction::set_compute_unit_limit(limit),
+        ComputeBudgetInstruction::set_compute_unit_price(10_000),
+    ];
+    expected.extend(transfers);
+    assert_eq!(transaction.get_combined_instructions(), expected);
+
+    let registrations = (0..REGISTER_BATCH)
+        .map(|_| ore::instruction::register(Pubkey::new_unique()))
+        .collect();
+    let transaction = batch_transaction(TransactionKind::Register, CU_PER_REGISTER, registrations);
+    assert_eq!(
+        transaction.get_combined_instructions()[0],
+        ComputeBudgetInstruction::set_compute_unit_limit(
+            300 + REGISTER_BATCH as u32 * CU_PER_REGISTER
+        )
+    );
+}
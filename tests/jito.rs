@@ -0,0 +1,91 @@
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    net::TcpListener,
+    sync::mpsc::{self, Receiver},
+    thread,
+};
+
+use ore_cli::jito::JitoClient;
+use serde_json::{json, Value};
+use solana_sdk::{
+    hash::Hash, pubkey::Pubkey, signature::Keypair, signer::Signer, system_instruction,
+    transaction::Transaction,
+};
+
+/// Serves a block engine with `tip_accounts`, sending every bundle it receives to the receiver.
+fn block_engine(tip_accounts: Vec<String>) -> (String, Receiver<Value>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let (bundles, received) = mpsc::channel();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut reader = BufReader::new(stream.unwrap());
+            let (mut request_line, mut line, mut length) = (String::new(), String::new(), 0);
+            reader.read_line(&mut request_line).unwrap();
+            while reader.read_line(&mut line).unwrap() > 2 {
+                if let Some(value) = line.to_ascii_lowercase().strip_prefix("content-length:") {
+                    length = value.trim().parse().unwrap();
+                }
+                line.clear();
+            }
+            let mut request = vec![0; length];
+            reader.read_exact(&mut request).unwrap();
+            let body = if request_line.starts_with("GET /getTipAccounts ") {
+                json!({ "data": tip_accounts })
+            } else {
+                bundles
+                    .send(serde_json::from_slice::<Value>(&request).unwrap())
+                    .unwrap();
+                json!({ "data": "bundle-id" })
+            }
+            .to_string();
+            write!(
+                reader.get_mut(),
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            )
+            .unwrap();
+        }
+    });
+    (format!("http://{}", address), received)
+}
+
+#[tokio::test]
+async fn bundles_carry_the_signed_transactions() {
+    let tip_account = Pubkey::new_unique();
+    let (url, bundles) = block_engine(vec![tip_account.to_string()]);
+    let jito = JitoClient::new(&url, 5_000).await.unwrap();
+    assert_eq!(jito.tip_accounts, vec![tip_account]);
+    assert_eq!(jito.get_random_tip_account(), tip_account);
+
+    let payer = Keypair::new();
+    let transfer = system_instruction::transfer(&payer.pubkey(), &tip_account, jito.tip);
+    let transaction = Transaction::new_signed_with_payer(
+        &[transfer],
+        Some(&payer.pubkey()),
+        &[&payer],
+        Hash::new_unique(),
+    );
+    let bundle = jito.send_jito_bundle(vec![&transaction]).await.unwrap();
+    assert_eq!(bundle, "bundle-id");
+
+    let request = bundles.recv().unwrap();
+    let sent = &request["transactions"][0];
+    assert_eq!(
+        sent["messageData"],
+        bs58::encode(transaction.message_data()).into_string()
+    );
+    assert_eq!(sent["signatures"][0], transaction.signatures[0].to_string());
+}
+
+#[tokio::test]
+async fn block_engines_without_tip_accounts_are_refused() {
+    let (url, _) = block_engine(vec![]);
+    let err = JitoClient::new(&url, 5_000).await.unwrap_err();
+    assert!(err.to_string().contains("no tip accounts"), "{}", err);
+
+    let (url, _) = block_engine(vec!["not-a-pubkey".into()]);
+    assert!(JitoClient::new(&url, 5_000).await.is_err());
+    assert!(JitoClient::new("not a url", 5_000).await.is_err());
+}
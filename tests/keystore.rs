@@ -0,0 +1,119 @@
+mod common;
+
+use std::fs;
+
+use common::temp_dir;
+use ore_cli::{
+    fleet::KeySource,
+    keystore::{write_plain_keypair, KdfParams, Keystore, KeystoreError, PassphraseSource, Unlocker},
+    Miner,
+};
+use solana_sdk::{pubkey::Pubkey, signature::Keypair, signer::Signer};
+
+/// Cheap scrypt parameters so the tests stay fast.
+const TEST_KDF: KdfParams = KdfParams {
+    log_n: 4,
+    r: 8,
+    p: 1,
+};
+
+#[test]
+fn keystore_roundtrips() {
+    let keypair = Keypair::new();
+    let keystore = Keystore::encrypt_with(&keypair, "hunter2", TEST_KDF).unwrap();
+    assert_eq!(keystore.pubkey().unwrap(), keypair.pubkey());
+    assert!(!keystore.ciphertext.contains(&hex_secret(&keypair)));
+
+    let decrypted = keystore.decrypt("hunter2").unwrap();
+    assert_eq!(decrypted.to_bytes(), keypair.to_bytes());
+
+    let again = Keystore::encrypt_with(&keypair, "hunter2", TEST_KDF).unwrap();
+    assert_ne!(again.salt, keystore.salt);
+    assert_ne!(again.ciphertext, keystore.ciphertext);
+}
+
+#[test]
+fn wrong_passphrase_and_tampering_fail() {
+    let keypair = Keypair::new();
+    let keystore = Keystore::encrypt_with(&keypair, "hunter2", TEST_KDF).unwrap();
+    assert!(matches!(keystore.decrypt("hunter3"), Err(KeystoreError::Decrypt)));
+
+    let mut swapped = keystore.clone();
+    swapped.pubkey = Pubkey::new_unique().to_string();
+    assert!(matches!(swapped.decrypt("hunter2"), Err(KeystoreError::Decrypt)));
+
+    let mut flipped = keystore.clone();
+    let last = if flipped.ciphertext.ends_with('0') { "1" } else { "0" };
+    flipped.ciphertext.replace_range(flipped.ciphertext.len() - 1.., last);
+    assert!(matches!(flipped.decrypt("hunter2"), Err(KeystoreError::Decrypt)));
+
+    let mut weakened = keystore;
+    weakened.kdf_params.log_n += 1;
+    assert!(matches!(weakened.decrypt("hunter2"), Err(KeystoreError::Decrypt)));
+}
+
+#[test]
+fn unlocker_reads_plain_and_encrypted_keypairs() {
+    let dir = temp_dir("keystore");
+    let var = format!("ORE_CLI_TEST_PASSPHRASE_{}", std::process::id());
+    std::env::set_var(&var, "hunter2");
+
+    let plain = Keypair::new();
+    write_plain_keypair(&plain, &dir.join("a.json")).unwrap();
+    let encrypted = Keypair::new();
+    Keystore::encrypt_with(&encrypted, "hunter2", TEST_KDF)
+        .unwrap()
+        .save(&dir.join("b.json"))
+        .unwrap();
+
+    let unlocker = Unlocker::new(PassphraseSource::Env(var.clone()));
+    let miners = Miner::load(&KeySource::Dir(dir.clone()), &unlocker).unwrap();
+    assert_eq!(
+        miners.iter().map(|miner| miner.pubkey()).collect::<Vec<_>>(),
+        vec![plain.pubkey(), encrypted.pubkey()]
+    );
+
+    std::env::set_var(&var, "wrong");
+    let unlocker = Unlocker::new(PassphraseSource::Env(var.clone()));
+    assert!(unlocker.read_keypair(&dir.join("a.json")).is_ok());
+    assert!(unlocker.read_keypair(&dir.join("b.json")).is_err());
+
+    std::env::remove_var(&var);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn keystore_files_are_private() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = temp_dir("keystore-mode");
+    let path = dir.join("owner.json");
+    Keystore::encrypt_with(&Keypair::new(), "hunter2", TEST_KDF)
+        .unwrap()
+        .save(&path)
+        .unwrap();
+    assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn passphrase_fd_is_read_once_and_left_open() {
+    use std::os::fd::AsRawFd;
+
+    let dir = temp_dir("keystore-fd");
+    let path = dir.join("passphrase");
+    fs::write(&path, "hunter2\nsecond line\n").unwrap();
+    let file = fs::File::open(&path).unwrap();
+
+    let source = PassphraseSource::Fd(file.as_raw_fd());
+    assert_eq!(*source.read("").unwrap(), "hunter2");
+    assert!(source.read("").is_err());
+    assert!(file.metadata().is_ok());
+
+    drop(file);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+fn hex_secret(keypair: &Keypair) -> String {
+    hex::encode(&keypair.to_bytes()[..32])
+}
@@ -0,0 +1,108 @@
+mod common;
+
+use ore::state::{Hash, Proof};
+use ore_cli::{
+    fleet::Transfer,
+    ledger::{Attempt, Grouping, Ledger, Summary, TransactionKind},
+    metrics::Outcome,
+    Miner, SignedWork, Work,
+};
+use solana_sdk::{
+    keccak::Hash as KeccakHash,
+    pubkey::Pubkey,
+    signature::{Keypair, Signature},
+    signer::Signer,
+};
+
+fn proof(authority: Pubkey, total_rewards: u64) -> Proof {
+    Proof {
+        authority,
+        claimable_rewards: total_rewards,
+        hash: Hash([0; 32]),
+        total_hashes: 0,
+        total_rewards,
+    }
+}
+
+fn attempt(kind: TransactionKind, miner: Option<Pubkey>, outcome: Outcome) -> Attempt<'static> {
+    Attempt {
+        kind: Some(kind),
+        miner,
+        signature: Some(Signature::default()),
+        attempt: 0,
+        cu_limit: 3200,
+        cu_price: Some(1_000),
+        fee_lamports: 5_004,
+        endpoint: "rpc.example.com",
+        outcome,
+        error: None,
+    }
+}
+
+#[test]
+fn summarises_earnings_against_fees_across_reopens() {
+    let path = common::temp_dir("ledger").join("ledger.sqlite3");
+
+    let miner = Miner::new(Keypair::new());
+    let pubkey = miner.pubkey();
+    {
+        let ledger = Ledger::open(&path).unwrap();
+        ledger.record_work(&SignedWork {
+            signer: miner,
+            challenge: KeccakHash::default(),
+            bus: 3,
+            work: Work::ToBeProved(KeccakHash::default(), u64::MAX),
+        });
+        ledger.record_proof(pubkey, &proof(pubkey, 100));
+        ledger.record_attempt(&attempt(
+            TransactionKind::Mine,
+            Some(pubkey),
+            Outcome::Expired,
+        ));
+        ledger.record_attempt(&attempt(
+            TransactionKind::Mine,
+            Some(pubkey),
+            Outcome::Landed,
+        ));
+        ledger.record_proof(pubkey, &proof(pubkey, 350));
+    }
+
+    let ledger = Ledger::open(&path).unwrap();
+    ledger.record_proof(pubkey, &proof(pubkey, 400));
+    ledger.record_claim(pubkey, 400, Signature::default());
+    ledger.record_attempt(&attempt(TransactionKind::Fund, None, Outcome::Landed));
+    let transfer = Transfer {
+        miner: pubkey,
+        lamports: 1_000_000,
+    };
+    ledger.record_transfer(TransactionKind::Fund, &transfer, Signature::default());
+
+    let by_miner = ledger.summary(Grouping::Miner).unwrap();
+    let expected = Summary {
+        found: 1,
+        landed: 1,
+        failed: 1,
+        earned: 300,
+        claimed: 400,
+        fees: 5_004,
+    };
+    assert!(by_miner.contains(&(pubkey.to_string(), expected)));
+    assert!(by_miner.contains(&(
+        "owner".to_string(),
+        Summary {
+            fees: 5_004,
+            ..Default::default()
+        }
+    )));
+
+    let by_day = ledger.summary(Grouping::Day).unwrap();
+    assert_eq!(by_day.len(), 1);
+    assert_eq!(by_day[0].0.len(), "YYYY-MM-DD".len());
+    assert_eq!(
+        by_day[0].1,
+        Summary {
+            fees: 10_008,
+            ..expected
+        }
+    );
+}
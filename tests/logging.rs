@@ -0,0 +1,31 @@
+mod common;
+
+use ore_cli::logging::{self, LogConfig, LogFormat, LogRotation};
+use tracing::{info, info_span};
+
+#[test]
+fn json_file_logs_carry_span_fields() {
+    let dir = common::temp_dir("logs");
+    let path = dir.join("ore.log");
+    let guard = logging::init(&LogConfig {
+        format: LogFormat::Json,
+        filter: "info,ore_cli::transaction=debug".into(),
+        file: Some(path.clone()),
+        rotation: LogRotation::Never,
+        buffer: None,
+    })
+    .unwrap();
+
+    info_span!("submit", miner = "Miner1111").in_scope(|| info!(nonce = 42, "Mined"));
+    tracing::debug!("filtered out");
+    drop(guard);
+
+    let contents = std::fs::read_to_string(&path).unwrap();
+    let lines = contents.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 1);
+    let line: serde_json::Value = serde_json::from_str(lines[0]).unwrap();
+    assert_eq!(line["fields"]["message"], "Mined");
+    assert_eq!(line["fields"]["nonce"], 42);
+    assert_eq!(line["span"]["miner"], "Miner1111");
+    std::fs::remove_dir_all(dir).unwrap();
+}
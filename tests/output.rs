@@ -0,0 +1,158 @@
+use ore::state::{Hash, Proof};
+use ore_cli::{
+    errors::{CliError, Error},
+    fleet::MinerAccount,
+    output::{
+        AdminAction, AdminOutput, Amount, ErrorOutput, KeyAction, KeyOutput, KeysOutput,
+        MinersOutput, NewMinersOutput, Render,
+    },
+};
+use serde_json::json;
+use solana_sdk::pubkey::Pubkey;
+
+#[test]
+fn amounts_keep_raw_units_next_to_exact_decimals() {
+    assert_eq!(Amount::sol(1_500_000_000).ui, "1.500000000");
+    assert_eq!(Amount::sol(5_000).ui, "0.000005000");
+    assert_eq!(Amount::ore(u64::MAX).ui, "18446744073.709551615");
+    assert_eq!(Amount::ore(42).raw, 42);
+    assert_eq!(format!("{:>14}", Amount::ore(0)), "   0.000000000");
+}
+
+#[test]
+fn errors_carry_their_kind() {
+    let miner = Pubkey::new_unique();
+    let error = Error::CliError(CliError::NothingToClaim(miner));
+    let output = ErrorOutput::from(&error);
+    assert_eq!(output.kind, "NothingToClaim");
+    assert_eq!(
+        serde_json::to_value(&output).unwrap(),
+        json!({ "kind": "NothingToClaim", "message": error.to_string() })
+    );
+
+    let missing = Error::from(std::io::Error::from(std::io::ErrorKind::NotFound));
+    assert_eq!(ErrorOutput::from(&missing).kind, "Io");
+    let config = Error::CliError(CliError::Config("No RPC configured".into()));
+    assert_eq!(
+        serde_json::to_value(ErrorOutput::from(&config)).unwrap(),
+        json!({ "kind": "Config", "message": "Config: No RPC configured" })
+    );
+}
+
+#[test]
+fn miners_serialize_to_a_stable_schema() {
+    let (registered, unregistered) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let accounts = [
+        MinerAccount {
+            pubkey: registered,
+            lamports: 2_000_000,
+            proof: Some(Proof {
+                authority: registered,
+                claimable_rewards: 250_000_000,
+                hash: Hash([0; 32]),
+                total_hashes: 3,
+                total_rewards: 1_250_000_000,
+            }),
+            proof_lamports: 1_559_040,
+        },
+        MinerAccount {
+            pubkey: unregistered,
+            lamports: 0,
+            proof: None,
+            proof_lamports: 0,
+        },
+    ];
+    let output = MinersOutput::new(&accounts);
+
+    assert_eq!(
+        serde_json::to_value(&output).unwrap(),
+        json!({
+            "miners": [
+                {
+                    "miner": registered.to_string(),
+                    "registered": true,
+                    "sol": { "raw": 2_000_000, "ui": "0.002000000" },
+                    "claimable": { "raw": 250_000_000, "ui": "0.250000000" },
+                    "total_rewards": { "raw": 1_250_000_000, "ui": "1.250000000" },
+                    "total_hashes": 3,
+                    "proof_rent": { "raw": 1_559_040, "ui": "0.001559040" },
+                },
+                {
+                    "miner": unregistered.to_string(),
+                    "registered": false,
+                    "sol": { "raw": 0, "ui": "0.000000000" },
+                    "claimable": { "raw": 0, "ui": "0.000000000" },
+                    "total_rewards": { "raw": 0, "ui": "0.000000000" },
+                    "total_hashes": 0,
+                    "proof_rent": { "raw": 0, "ui": "0.000000000" },
+                },
+            ],
+            "total_sol": { "raw": 2_000_000, "ui": "0.002000000" },
+            "total_claimable": { "raw": 250_000_000, "ui": "0.250000000" },
+        })
+    );
+    assert_eq!(
+        output.plain(),
+        format!(
+            "{}\t0.002000000\ttrue\t0.250000000\n{}\t0.000000000\tfalse\t0.000000000\n",
+            registered, unregistered
+        )
+    );
+}
+
+#[test]
+fn keys_and_admin_results_serialize() {
+    let pubkey = Pubkey::new_unique().to_string();
+    let keys = KeysOutput {
+        action: KeyAction::Unlocked,
+        keys: vec![
+            KeyOutput {
+                path: "a.json".into(),
+                pubkey: Some(pubkey.clone()),
+                error: None,
+            },
+            KeyOutput {
+                path: "b.json".into(),
+                pubkey: None,
+                error: Some("wrong passphrase".into()),
+            },
+        ],
+    };
+    assert_eq!(
+        serde_json::to_value(&keys).unwrap(),
+        json!({
+            "action": "unlocked",
+            "keys": [
+                { "path": "a.json", "pubkey": pubkey, "error": null },
+                { "path": "b.json", "pubkey": null, "error": "wrong passphrase" },
+            ],
+        })
+    );
+    assert_eq!(
+        keys.table(),
+        format!("a.json {}\nb.json failed: wrong passphrase\n", pubkey)
+    );
+
+    let miners = NewMinersOutput {
+        dir: "/keys".into(),
+        miners: vec![pubkey.clone()],
+    };
+    assert_eq!(miners.table(), format!("{}\n", pubkey));
+
+    let declined = AdminOutput {
+        action: AdminAction::UpdateAdmin,
+        value: pubkey.clone(),
+        treasury: None,
+        signature: None,
+    };
+    assert_eq!(
+        serde_json::to_value(&declined).unwrap(),
+        json!({ "action": "update_admin", "value": pubkey, "treasury": null, "signature": null })
+    );
+    assert_eq!(declined.table(), "Declined\n");
+    let sent = AdminOutput {
+        signature: Some("5ig".into()),
+        ..declined
+    };
+    assert_eq!(sent.table(), "Admin updated: 5ig\n");
+}
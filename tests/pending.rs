@@ -0,0 +1,84 @@
+mod common;
+
+use std::{fs, time::Instant};
+
+use ore::BUS_COUNT;
+use ore_cli::{
+    mine::MinerLog,
+    pending::{PendingWork, PendingWorks},
+    scheduler::{EpochWindow, Scheduler},
+    Miner, SignedWork, Work,
+};
+use solana_sdk::{keccak::hashv, signature::Keypair, signer::Signer};
+
+fn scheduler() -> Scheduler {
+    Scheduler::new(
+        1,
+        EpochWindow {
+            last_reset_at: 0,
+            reward_rate: 1,
+            bus_rewards: [0; BUS_COUNT],
+            unix_timestamp: 0,
+            fetched_at: Instant::now(),
+        },
+    )
+}
+
+fn signed_work(miner: &Miner, challenge: &[u8]) -> SignedWork {
+    SignedWork {
+        signer: miner.clone(),
+        challenge: hashv(&[challenge]),
+        bus: 3,
+        work: Work::ToBeProved(hashv(&[challenge, b"solution"]), 42),
+    }
+}
+
+#[test]
+fn pending_work_survives_a_save_and_load() {
+    let path = common::temp_dir("pending").join("pending.json");
+    let miner = Miner::new(Keypair::new());
+    let work = signed_work(&miner, b"challenge");
+    let pending = PendingWorks::default();
+    pending.insert(&work);
+    pending.save(&path).unwrap();
+
+    let saved = PendingWorks::load(&path).unwrap();
+    assert_eq!(saved.len(), 1);
+    assert_eq!(saved[0].miner(), Some(miner.pubkey()));
+    assert_eq!(saved[0].challenge(), Some(work.challenge));
+    let Some(Work::ToBeProved(hash, 42)) = saved[0].work() else {
+        panic!("lost the nonce");
+    };
+    assert_eq!(&hash, work.work.hash());
+    assert_eq!(saved[0].bus, 3);
+
+    fs::remove_file(&path).unwrap();
+    assert!(PendingWorks::load(&path).unwrap().is_empty());
+}
+
+#[test]
+fn resume_drops_work_for_a_stale_challenge() {
+    let scheduler = scheduler();
+    let (current, stale) = (Miner::new(Keypair::new()), Miner::new(Keypair::new()));
+    for miner in [&current, &stale] {
+        let log = MinerLog {
+            last_work: Work::Proved(hashv(&[b"challenge"])),
+            bus: 0,
+        };
+        scheduler.push(miner.clone(), log);
+    }
+
+    let resumed = PendingWork::from(&signed_work(&current, b"challenge"))
+        .resume(&scheduler)
+        .unwrap();
+    assert_eq!(resumed.signer, current);
+    assert_eq!(resumed.bus, 3);
+
+    assert!(PendingWork::from(&signed_work(&stale, b"old challenge"))
+        .resume(&scheduler)
+        .is_none());
+    assert_eq!(scheduler.pop().unwrap().0, stale);
+    assert!(PendingWork::from(&signed_work(&current, b"challenge"))
+        .resume(&scheduler)
+        .is_none());
+}
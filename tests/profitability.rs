@@ -0,0 +1,136 @@
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::TcpListener,
+    thread,
+    time::Duration,
+};
+
+use ore_cli::{
+    config::Settings,
+    profitability::{
+        parse_price, GuardAction, Margin, PriceFeed, ProfitConfig, ProfitGuard, Verdict,
+    },
+    transaction::SendPolicy,
+};
+use serde_json::json;
+
+const CU_LIMIT: u32 = 3200;
+
+fn guard(feed: PriceFeed, action: GuardAction) -> ProfitGuard {
+    ProfitGuard::new(ProfitConfig {
+        feed,
+        min_margin: 0.0,
+        action,
+        slow_down: Duration::from_secs(5),
+        refresh: Duration::from_secs(60),
+    })
+}
+
+#[test]
+fn margin_values_the_reward_at_the_ore_price() {
+    let margin = Margin::new(24_000, 0.5, 8_000);
+    assert_eq!(margin.value_lamports, 12_000.0);
+    assert_eq!(margin.ratio(), 0.5);
+    assert_eq!(Margin::new(1, 0.5, 0).ratio(), f64::INFINITY);
+}
+
+#[test]
+fn judges_proofs_by_action() {
+    // Two signatures and 1_000_000 microlamports per compute unit cost 13_200 lamports.
+    let policy = SendPolicy {
+        priority_fee: Some(1_000_000),
+        ..Default::default()
+    };
+    let judge = |action, reward_rate| {
+        guard(PriceFeed::Static(0.5), action).judge(reward_rate, &policy, 2, CU_LIMIT)
+    };
+
+    assert_eq!(
+        judge(GuardAction::Pause, 30_000),
+        Verdict::Submit(Some(1_000_000))
+    );
+    assert_eq!(judge(GuardAction::Pause, 20_000), Verdict::Hold);
+    assert_eq!(
+        judge(GuardAction::Slow, 20_000),
+        Verdict::Delay(Duration::from_secs(5))
+    );
+    // 12_000 lamports of reward leave 2_000 for the priority fee.
+    assert_eq!(
+        judge(GuardAction::ReduceFee, 24_000),
+        Verdict::Submit(Some(625_000))
+    );
+    assert_eq!(
+        judge(GuardAction::ReduceFee, 20_000),
+        Verdict::Submit(Some(0))
+    );
+    assert_eq!(judge(GuardAction::ReduceFee, 16_000), Verdict::Hold);
+
+    let unpriced = guard(
+        PriceFeed::Http {
+            url: "http://127.0.0.1:1".into(),
+            pointer: String::new(),
+        },
+        GuardAction::Pause,
+    );
+    assert_eq!(unpriced.price(), None);
+    assert_eq!(
+        unpriced.judge(0, &policy, 2, CU_LIMIT),
+        Verdict::Submit(Some(1_000_000))
+    );
+}
+
+#[test]
+fn parses_prices_at_a_pointer() {
+    let document = json!({ "data": { "ORE": { "price": 0.25 }, "SOL": { "price": "150.5" } } });
+    assert_eq!(parse_price(&document, "/data/ORE/price"), Some(0.25));
+    assert_eq!(parse_price(&document, "/data/SOL/price"), Some(150.5));
+    assert_eq!(parse_price(&document, "/data/BTC/price"), None);
+    assert_eq!(parse_price(&json!(0.5), ""), Some(0.5));
+    assert_eq!(parse_price(&json!(0), ""), None);
+}
+
+#[tokio::test]
+async fn fetches_the_price_over_http() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream);
+        let mut line = String::new();
+        while reader.read_line(&mut line).unwrap() > 2 {
+            line.clear();
+        }
+        let body = r#"{"data":{"ORE":{"price":0.002}}}"#;
+        write!(
+            reader.get_mut(),
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            body.len(),
+            body
+        )
+        .unwrap();
+    });
+
+    let guard = guard(
+        PriceFeed::Http {
+            url: format!("http://{}/price?ids=ORE", address),
+            pointer: "/data/ORE/price".into(),
+        },
+        GuardAction::Pause,
+    );
+    guard.refresh().await;
+    assert_eq!(guard.price(), Some(0.002));
+}
+
+#[test]
+fn settings_turn_the_guard_on_with_a_price() {
+    assert_eq!(Settings::builtin().profit_config(), None);
+    let settings = Settings {
+        ore_price: Some(0.001),
+        unprofitable: Some(GuardAction::ReduceFee),
+        ..Settings::builtin()
+    };
+    let config = settings.profit_config().unwrap();
+    assert_eq!(config.feed, PriceFeed::Static(0.001));
+    assert_eq!(config.action, GuardAction::ReduceFee);
+    assert_eq!(config.min_margin, 0.0);
+}
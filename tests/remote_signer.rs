@@ -0,0 +1,251 @@
+mod common;
+
+use std::{io::ErrorKind, net::TcpListener, os::unix::net::UnixListener, thread};
+
+use ore_cli::remote_signer::{RemoteSigner, SignRequest, SignerEndpoint, SignerServer};
+use solana_sdk::{
+    compute_budget::ComputeBudgetInstruction,
+    hash::Hash,
+    instruction::Instruction,
+    message::Message,
+    pubkey::Pubkey,
+    signature::Keypair,
+    signer::{Signer, SignerError},
+    system_instruction,
+    transaction::Transaction,
+};
+
+/// Starts a stand-in daemon on a Unix socket and on a local TCP port.
+fn start_daemons(name: &str, keypair: &Keypair) -> Vec<SignerEndpoint> {
+    let path = common::temp_dir(name).join("signer.sock");
+    let unix = UnixListener::bind(&path).unwrap();
+    let tcp = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = tcp.local_addr().unwrap();
+
+    let server = SignerServer::new(Keypair::from_bytes(&keypair.to_bytes()).unwrap());
+    thread::spawn(move || server.serve_unix(unix));
+    let server = SignerServer::new(Keypair::from_bytes(&keypair.to_bytes()).unwrap());
+    thread::spawn(move || server.serve_http(tcp));
+
+    vec![
+        format!("unix:{}", path.display()).parse().unwrap(),
+        format!("http://{}", address).parse().unwrap(),
+    ]
+}
+
+#[test]
+fn endpoints_parse() {
+    assert!(SignerEndpoint::is_endpoint("unix:/run/ore.sock"));
+    assert!(!SignerEndpoint::is_endpoint("owner.json"));
+    assert_eq!(
+        "http://127.0.0.1:9000/".parse::<SignerEndpoint>().unwrap(),
+        SignerEndpoint::Http("127.0.0.1:9000".into())
+    );
+    assert_eq!(
+        "unix:/run/ore.sock"
+            .parse::<SignerEndpoint>()
+            .unwrap()
+            .to_string(),
+        "unix:/run/ore.sock"
+    );
+    assert!("owner.json".parse::<SignerEndpoint>().is_err());
+}
+
+#[test]
+fn remote_fee_payer_signs_transactions() {
+    let owner = Keypair::new();
+    let miner = Keypair::new();
+    for endpoint in start_daemons("sign", &owner) {
+        let remote = RemoteSigner::connect(endpoint, None).unwrap();
+        assert_eq!(remote.pubkey(), owner.pubkey());
+
+        let mut tx = Transaction::new_with_payer(
+            &[ore::instruction::register(miner.pubkey())],
+            Some(&remote.pubkey()),
+        );
+        tx.try_sign(&[&miner as &dyn Signer, &remote], Hash::new_unique())
+            .unwrap();
+        tx.verify().unwrap();
+    }
+}
+
+#[test]
+fn daemon_refuses_foreign_messages() {
+    let owner = Keypair::new();
+    let other = Keypair::new();
+    for endpoint in start_daemons("refuse", &owner) {
+        let remote = RemoteSigner::connect(endpoint, None).unwrap();
+
+        let paid_by_other = Message::new(
+            &[system_instruction::transfer(
+                &other.pubkey(),
+                &owner.pubkey(),
+                1,
+            )],
+            Some(&other.pubkey()),
+        );
+        assert!(matches!(
+            remote.try_sign_message(&paid_by_other.serialize()),
+            Err(SignerError::Custom(_))
+        ));
+
+        let unknown_program = Message::new(
+            &[Instruction::new_with_bytes(
+                Pubkey::new_unique(),
+                &[],
+                vec![],
+            )],
+            Some(&owner.pubkey()),
+        );
+        assert!(matches!(
+            remote.try_sign_message(&unknown_program.serialize()),
+            Err(SignerError::Custom(_))
+        ));
+        assert!(remote.try_sign_message(b"not a message").is_err());
+    }
+}
+
+fn sign(server: &SignerServer, instructions: &[Instruction]) -> Result<String, String> {
+    let message = Message::new(instructions, Some(&server.keypair.pubkey()));
+    let response = server.handle(&SignRequest::Sign {
+        message: hex::encode(message.serialize()),
+    });
+    response.signature.ok_or_else(|| response.error.unwrap())
+}
+
+#[test]
+fn daemon_answers_requests_directly() {
+    let owner = Keypair::new();
+    let miner = Pubkey::new_unique();
+    let mut server = SignerServer::new(Keypair::from_bytes(&owner.to_bytes()).unwrap());
+    server.allowed_recipients.push(miner);
+    let response = server.handle(&SignRequest::Pubkey);
+    assert_eq!(response.pubkey.unwrap(), owner.pubkey().to_string());
+
+    let transfer = [system_instruction::transfer(&owner.pubkey(), &miner, 1)];
+    assert_eq!(
+        sign(&server, &transfer).unwrap(),
+        owner
+            .sign_message(&Message::new(&transfer, Some(&owner.pubkey())).serialize())
+            .to_string()
+    );
+}
+
+#[test]
+fn daemon_keeps_the_owners_funds() {
+    let owner = Keypair::new();
+    let (miner, attacker) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let mut server = SignerServer::new(Keypair::from_bytes(&owner.to_bytes()).unwrap());
+    server.allowed_recipients.push(miner);
+
+    assert!(sign(
+        &server,
+        &[system_instruction::transfer(&owner.pubkey(), &attacker, 1)]
+    )
+    .is_err());
+    assert!(sign(
+        &server,
+        &[system_instruction::transfer(&miner, &owner.pubkey(), 1)]
+    )
+    .is_ok());
+    assert!(sign(
+        &server,
+        &[system_instruction::transfer(&miner, &attacker, 1)]
+    )
+    .is_ok());
+    assert!(sign(
+        &server,
+        &[system_instruction::assign(&owner.pubkey(), &attacker)]
+    )
+    .is_err());
+    assert!(sign(
+        &server,
+        &[ore::instruction::claim(owner.pubkey(), attacker, 1)]
+    )
+    .is_err());
+    assert!(sign(&server, &[ore::instruction::claim(miner, attacker, 1)]).is_ok());
+}
+
+#[test]
+fn daemon_caps_the_priority_fee() {
+    let owner = Keypair::new();
+    let mut server = SignerServer::new(Keypair::from_bytes(&owner.to_bytes()).unwrap());
+    server.max_cu_price = 10_000;
+    server.max_cu_limit = 50_000;
+    let register = ore::instruction::register(owner.pubkey());
+
+    let fee = |price, limit| {
+        vec![
+            ComputeBudgetInstruction::set_compute_unit_limit(limit),
+            ComputeBudgetInstruction::set_compute_unit_price(price),
+            register.clone(),
+        ]
+    };
+    assert!(sign(&server, &fee(10_000, 50_000)).is_ok());
+    assert!(sign(&server, &fee(u64::MAX, 50_000))
+        .unwrap_err()
+        .contains("price"));
+    assert!(sign(&server, &fee(10_000, 1_400_000))
+        .unwrap_err()
+        .contains("limit"));
+
+    // Without a limit the runtime grants each instruction 200k units.
+    assert!(sign(&server, &[register.clone()])
+        .unwrap_err()
+        .contains("limit"));
+    assert!(sign(
+        &server,
+        &[
+            ComputeBudgetInstruction::request_heap_frame(256 * 1024),
+            register
+        ]
+    )
+    .is_err());
+}
+
+#[test]
+fn daemon_refuses_admin_instructions_unless_allowed() {
+    let owner = Keypair::new();
+    let mut server = SignerServer::new(Keypair::from_bytes(&owner.to_bytes()).unwrap());
+    let limit = ComputeBudgetInstruction::set_compute_unit_limit(50_000);
+    let admin = [
+        ore::instruction::initialize(owner.pubkey()),
+        ore::instruction::update_admin(owner.pubkey(), Pubkey::new_unique()),
+        ore::instruction::update_difficulty(owner.pubkey(), ore::state::Hash([0; 32])),
+        ore::instruction::reset(owner.pubkey()),
+    ];
+    for instruction in &admin {
+        assert!(sign(&server, &[limit.clone(), instruction.clone()])
+            .unwrap_err()
+            .contains("not allowed"));
+    }
+
+    server.allow_admin = true;
+    for instruction in &admin {
+        assert!(sign(&server, &[limit.clone(), instruction.clone()]).is_ok());
+    }
+}
+
+#[test]
+fn http_daemon_checks_the_token() {
+    let owner = Keypair::new();
+    let tcp = TcpListener::bind("127.0.0.1:0").unwrap();
+    let endpoint = format!("http://{}", tcp.local_addr().unwrap())
+        .parse::<SignerEndpoint>()
+        .unwrap();
+    let mut server = SignerServer::new(Keypair::from_bytes(&owner.to_bytes()).unwrap());
+    server.token = Some("secret".into());
+    thread::spawn(move || server.serve_http(tcp));
+
+    assert!(RemoteSigner::connect(endpoint.clone(), None).is_err());
+    assert!(RemoteSigner::connect(endpoint.clone(), Some("wrong".into())).is_err());
+    let remote = RemoteSigner::connect(endpoint, Some("secret".into())).unwrap();
+    assert_eq!(remote.pubkey(), owner.pubkey());
+
+    let server = SignerServer::new(Keypair::new());
+    let open = "http://0.0.0.0:0".parse().unwrap();
+    assert_eq!(
+        server.serve(&open).unwrap_err().kind(),
+        ErrorKind::PermissionDenied
+    );
+}
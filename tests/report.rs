@@ -0,0 +1,120 @@
+mod common;
+
+use std::collections::HashSet;
+
+use ore::BUS_ADDRESSES;
+use ore_cli::{
+    ledger::{Attempt, Ledger, TransactionKind},
+    metrics::Outcome,
+    report::{classify, write_csv, ReportKind, ReportRow, ReportSource},
+};
+use solana_sdk::{
+    compute_budget::ComputeBudgetInstruction,
+    hash::Hash,
+    instruction::Instruction,
+    keccak::Hash as KeccakHash,
+    message::Message,
+    pubkey::Pubkey,
+    signature::Signature,
+    system_instruction,
+    transaction::{Transaction, VersionedTransaction},
+};
+
+fn transaction(instructions: &[Instruction], payer: &Pubkey) -> VersionedTransaction {
+    let message = Message::new_with_blockhash(instructions, Some(payer), &Hash::default());
+    Transaction::new_unsigned(message).into()
+}
+
+#[test]
+fn classifies_ore_instructions_and_transfers() {
+    let (owner, miner, stranger) = (
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+    );
+    let wallets = HashSet::from([owner, miner]);
+    let cu_limit = ComputeBudgetInstruction::set_compute_unit_limit(3200);
+
+    let mine = ore::instruction::mine(miner, BUS_ADDRESSES[0], KeccakHash::default().into(), 7);
+    let reward = 1_000_000_000u64.to_le_bytes();
+    assert_eq!(
+        classify(
+            &transaction(&[cu_limit.clone(), mine], &owner),
+            Some(&reward),
+            &wallets
+        ),
+        Some((ReportKind::Mine, 1_000_000_000))
+    );
+
+    let claim = ore::instruction::claim(miner, Pubkey::new_unique(), 42);
+    assert_eq!(
+        classify(&transaction(&[cu_limit, claim], &owner), None, &wallets),
+        Some((ReportKind::Claim, 42))
+    );
+
+    let fund = system_instruction::transfer(&owner, &miner, 5);
+    assert_eq!(
+        classify(&transaction(&[fund], &owner), None, &wallets),
+        Some((ReportKind::Transfer, 0))
+    );
+
+    let withdrawal = system_instruction::transfer(&owner, &stranger, 5);
+    assert_eq!(
+        classify(&transaction(&[withdrawal], &owner), None, &wallets),
+        None
+    );
+}
+
+#[test]
+fn ledger_lists_landed_transactions_with_claimed_amounts() {
+    let dir = common::temp_dir("report");
+    let ledger = Ledger::open(&dir.join("ledger.sqlite3")).unwrap();
+    let miner = Pubkey::new_unique();
+    let signature = Signature::new_unique();
+    let attempt = |outcome, signature| Attempt {
+        kind: Some(TransactionKind::Claim),
+        miner: Some(miner),
+        signature: Some(signature),
+        attempt: 0,
+        cu_limit: 11_000,
+        cu_price: Some(100_000),
+        fee_lamports: 6_100,
+        endpoint: "rpc.example.com",
+        outcome,
+        error: None,
+    };
+    ledger.record_attempt(&attempt(Outcome::Expired, Signature::new_unique()));
+    ledger.record_attempt(&attempt(Outcome::Landed, signature));
+    ledger.record_claim(miner, 42, signature);
+
+    let landed = ledger.landed(0..i64::MAX).unwrap();
+    assert_eq!(landed.len(), 1);
+    assert_eq!(landed[0].signature, signature.to_string());
+    assert_eq!(landed[0].kind, Some(TransactionKind::Claim));
+    assert_eq!(landed[0].miner, Some(miner));
+    assert_eq!(landed[0].priority_fee_lamports, 1_100);
+    assert_eq!(landed[0].claimed, Some(42));
+    assert!(ledger.landed(0..1).unwrap().is_empty());
+}
+
+#[test]
+fn writes_csv_rows() {
+    let rows = [ReportRow {
+        timestamp: "2024-05-01T12:00:00Z".into(),
+        signature: "sig".into(),
+        wallet: "miner".into(),
+        kind: ReportKind::Mine,
+        fee_lamports: 5_004,
+        priority_fee_lamports: 4,
+        ore_delta: None,
+        failed: false,
+        source: ReportSource::Ledger,
+    }];
+    let mut csv = vec![];
+    write_csv(&rows, &mut csv).unwrap();
+    assert_eq!(
+        String::from_utf8(csv).unwrap(),
+        "timestamp,signature,wallet,kind,fee_lamports,priority_fee_lamports,ore_delta,failed,source\n\
+         2024-05-01T12:00:00Z,sig,miner,mine,5004,4,,false,ledger\n"
+    );
+}
@@ -0,0 +1,124 @@
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+use ore::BUS_COUNT;
+use ore_cli::{
+    mine::{MinerLog, Work},
+    scheduler::{EpochWindow, Scheduler},
+    Miner,
+};
+use solana_sdk::{signature::Keypair, signer::Signer};
+
+fn scheduler() -> Scheduler {
+    with_busses([0; BUS_COUNT])
+}
+
+/// A scheduler early in an epoch whose busses can pay `bus_rewards` proofs each.
+fn with_busses(bus_rewards: [u64; BUS_COUNT]) -> Scheduler {
+    Scheduler::new(
+        1,
+        EpochWindow {
+            last_reset_at: 0,
+            reward_rate: 1,
+            bus_rewards,
+            unix_timestamp: 0,
+            fetched_at: Instant::now(),
+        },
+    )
+}
+
+fn log() -> MinerLog {
+    MinerLog {
+        last_work: Work::Proved(Default::default()),
+        bus: 0,
+    }
+}
+
+#[test]
+fn paused_miners_stay_queued_until_resumed() {
+    let scheduler = scheduler();
+    let paused = Miner::new(Keypair::new());
+    let active = Miner::new(Keypair::new());
+    scheduler.pause(paused.pubkey());
+    scheduler.push(paused.clone(), log());
+    scheduler.push(active.clone(), log());
+
+    assert_eq!(scheduler.pop().unwrap().0, active);
+    assert!(scheduler.try_pop().is_none());
+
+    scheduler.resume(&paused.pubkey());
+    assert!(!scheduler.is_paused(&paused.pubkey()));
+    assert_eq!(scheduler.pop().unwrap().0, paused);
+}
+
+#[test]
+fn pop_waits_for_a_paused_miner_to_resume() {
+    let scheduler = std::sync::Arc::new(scheduler());
+    let miner = Miner::new(Keypair::new());
+    scheduler.pause(miner.pubkey());
+    scheduler.push(miner.clone(), log());
+
+    let waiting = {
+        let scheduler = scheduler.clone();
+        std::thread::spawn(move || scheduler.pop().map(|(miner, _)| miner))
+    };
+    std::thread::sleep(std::time::Duration::from_millis(50));
+    assert!(!waiting.is_finished());
+    scheduler.resume(&miner.pubkey());
+    assert_eq!(waiting.join().unwrap(), Some(miner));
+}
+
+#[test]
+fn longest_waiting_miner_goes_first() {
+    let scheduler = scheduler();
+    let (first, second) = (Miner::new(Keypair::new()), Miner::new(Keypair::new()));
+    scheduler.push(first.clone(), log());
+    thread::sleep(Duration::from_millis(5));
+    scheduler.push(second.clone(), log());
+
+    let (miner, miner_log) = scheduler.pop().unwrap();
+    assert_eq!(miner, first);
+    scheduler.push(miner, miner_log);
+    assert_eq!(scheduler.pop().unwrap().0, second);
+    assert_eq!(scheduler.pop().unwrap().0, first);
+}
+
+#[test]
+fn miners_are_sent_to_the_busses_with_the_most_left() {
+    let mut bus_rewards = [0; BUS_COUNT];
+    bus_rewards[2] = 10;
+    bus_rewards[5] = 10;
+    bus_rewards[7] = 9;
+    let scheduler = with_busses(bus_rewards);
+    let miners = (0..4)
+        .map(|_| Miner::new(Keypair::new()))
+        .collect::<Vec<_>>();
+    for miner in &miners {
+        scheduler.push(miner.clone(), log());
+        thread::sleep(Duration::from_millis(5));
+    }
+
+    let popped = (0..3).map(|_| scheduler.pop().unwrap()).collect::<Vec<_>>();
+    assert_eq!(
+        popped.iter().map(|(_, log)| log.bus).collect::<Vec<_>>(),
+        vec![2, 5, 2]
+    );
+
+    // A miner coming back frees its bus for the next one: 2, 5 and 7 have 9 left each.
+    let (miner, miner_log) = popped.into_iter().next().unwrap();
+    scheduler.push(miner, miner_log);
+    let (miner, miner_log) = scheduler.pop().unwrap();
+    assert_eq!(miner, miners[3]);
+    assert_eq!(miner_log.bus, 2);
+}
+
+#[test]
+fn drained_busses_are_left_alone() {
+    let mut bus_rewards = [0; BUS_COUNT];
+    bus_rewards[4] = 1;
+    let scheduler = with_busses(bus_rewards);
+    scheduler.push(Miner::new(Keypair::new()), log());
+    assert_eq!(scheduler.pop().unwrap().1.bus, 4);
+}
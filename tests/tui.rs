@@ -0,0 +1,96 @@
+use std::time::{Duration, Instant};
+
+use ore::BUS_COUNT;
+use ore_cli::{
+    metrics::{MinerState, MinerStats},
+    scheduler::EpochWindow,
+    transaction::SendPolicy,
+    tui::{draw, MinerRow, RpcHealth, Snapshot},
+};
+use ratatui::{backend::TestBackend, widgets::TableState, Terminal};
+use solana_sdk::pubkey::Pubkey;
+
+fn render(snapshot: &Snapshot) -> String {
+    let mut terminal = Terminal::new(TestBackend::new(140, 40)).unwrap();
+    terminal
+        .draw(|frame| draw(frame, snapshot, &mut TableState::default()))
+        .unwrap();
+    let buffer = terminal.backend().buffer();
+    buffer
+        .content()
+        .chunks(buffer.area.width as usize)
+        .map(|row| row.iter().map(|cell| cell.symbol()).collect::<String>())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[test]
+fn dashboard_shows_miners_and_panels() {
+    let snapshot = Snapshot {
+        miners: vec![
+            MinerRow {
+                pubkey: Pubkey::new_unique(),
+                stats: MinerStats {
+                    state: MinerState::Confirming,
+                    hashes_per_second: 2_500_000.0,
+                    landed: 3,
+                    rewards: Some((1_000_000_000, 1_500_000_000)),
+                    claimable_rewards: 2_000_000_000,
+                    challenge: Some((Default::default(), Instant::now())),
+                    ..Default::default()
+                },
+                paused: false,
+                claiming: false,
+            },
+            MinerRow {
+                pubkey: Pubkey::new_unique(),
+                stats: MinerStats::default(),
+                paused: true,
+                claiming: false,
+            },
+        ],
+        window: Some(EpochWindow {
+            last_reset_at: 0,
+            reward_rate: 1_000_000,
+            bus_rewards: [250_000_000; BUS_COUNT],
+            unix_timestamp: 30,
+            fetched_at: Instant::now(),
+        }),
+        send: SendPolicy {
+            priority_fee: Some(1_000),
+            ..Default::default()
+        },
+        landed: 3,
+        rpc: vec![RpcHealth {
+            host: "rpc.example.com".into(),
+            requests: 12,
+            latency: Some(Duration::from_millis(80)),
+            rate_limited: Duration::ZERO,
+        }],
+        logs: Some(vec!["INFO Landed".into()]),
+        ..Default::default()
+    };
+
+    let screen = render(&snapshot);
+    for expected in [
+        "Miners (2)",
+        "confirming",
+        "paused",
+        "2.50 MH/s",
+        "0.500000",
+        "2.000000",
+        "Resets in     0:30",
+        "Bus 7   0.250000 ORE",
+        "Per mine tx   4 lamports",
+        "rpc.example.com",
+        "80 ms  12 requests",
+        "INFO Landed",
+    ] {
+        assert!(
+            screen.contains(expected),
+            "missing {:?} in\n{}",
+            expected,
+            screen
+        );
+    }
+}
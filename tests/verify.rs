@@ -0,0 +1,97 @@
+use ore_cli::{
+    verify::{verify_work, Rejection},
+    Miner, SignedWork, Work,
+};
+use solana_sdk::{
+    keccak::{hashv, Hash as KeccakHash},
+    signature::Keypair,
+    signer::Signer,
+};
+
+/// Hashes with a leading zero byte satisfy it, about one in 256.
+fn difficulty() -> KeccakHash {
+    let mut difficulty = [0xff; 32];
+    difficulty[0] = 0;
+    KeccakHash::new_from_array(difficulty)
+}
+
+fn hash(challenge: &KeccakHash, miner: &Miner, nonce: u64) -> KeccakHash {
+    hashv(&[
+        challenge.as_ref(),
+        miner.pubkey().as_ref(),
+        nonce.to_le_bytes().as_slice(),
+    ])
+}
+
+/// Searches nonces like a backend would, keeping the first that meets `accept`.
+fn solve(challenge: KeccakHash, miner: &Miner, accept: impl Fn(&KeccakHash) -> bool) -> SignedWork {
+    let nonce = (0..)
+        .find(|nonce| accept(&hash(&challenge, miner, *nonce)))
+        .unwrap();
+    SignedWork {
+        signer: miner.clone(),
+        challenge,
+        bus: 0,
+        work: Work::ToBeProved(hash(&challenge, miner, nonce), nonce),
+    }
+}
+
+#[test]
+fn accepts_a_solution_to_the_current_challenge() {
+    let miner = Miner::new(Keypair::new());
+    let challenge = hashv(&[b"challenge"]);
+    let work = solve(challenge, &miner, |hash| hash.le(&difficulty()));
+    assert_eq!(verify_work(&work, &challenge, &difficulty()), Ok(()));
+}
+
+#[test]
+fn rejects_hashes_that_do_not_match_the_nonce() {
+    let miner = Miner::new(Keypair::new());
+    let challenge = hashv(&[b"challenge"]);
+    let mut work = solve(challenge, &miner, |hash| hash.le(&difficulty()));
+    let Work::ToBeProved(hash, nonce) = work.work else {
+        unreachable!()
+    };
+    work.work = Work::ToBeProved(hash, nonce + 1);
+    assert_eq!(
+        verify_work(&work, &challenge, &difficulty()),
+        Err(Rejection::HashInvalid)
+    );
+
+    let other = Miner::new(Keypair::new());
+    work.work = Work::ToBeProved(hash, nonce);
+    work.signer = other;
+    assert_eq!(
+        verify_work(&work, &challenge, &difficulty()),
+        Err(Rejection::HashInvalid)
+    );
+
+    work.work = Work::Proved(hash);
+    assert_eq!(
+        verify_work(&work, &challenge, &difficulty()),
+        Err(Rejection::HashInvalid)
+    );
+}
+
+#[test]
+fn rejects_hashes_above_the_difficulty() {
+    let miner = Miner::new(Keypair::new());
+    let challenge = hashv(&[b"challenge"]);
+    let work = solve(challenge, &miner, |hash| hash.gt(&difficulty()));
+    assert_eq!(
+        verify_work(&work, &challenge, &difficulty()),
+        Err(Rejection::DifficultyNotSatisfied)
+    );
+}
+
+#[test]
+fn rejects_work_for_an_old_challenge() {
+    let miner = Miner::new(Keypair::new());
+    let challenge = hashv(&[b"challenge"]);
+    let work = solve(challenge, &miner, |hash| hash.le(&difficulty()));
+    let current = hashv(&[b"next challenge"]);
+    assert_eq!(
+        verify_work(&work, &current, &difficulty()),
+        Err(Rejection::StaleChallenge)
+    );
+}
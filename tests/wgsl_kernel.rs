@@ -0,0 +1,554 @@
+//! Checks the WGSL kernels without a GPU. The shaders are parsed and validated with naga, and
+//! `kernel` below is a port of `src/shaders/mine.wgsl` that is run against `keccak::hashv`. Jobs
+//! reach the port through `gpu_batch::pack_jobs` and its results go back through
+//! `gpu_batch::decode_results`, so the host side of the batch layout is covered too.
+
+use naga::{
+    valid::{Capabilities, ValidationFlags, Validator},
+    Expression, Literal, Module, TypeInner,
+};
+use ore_cli::gpu_batch::{
+    advance_jobs, decode_results, nonces_per_job, pack_jobs, GpuJob, JOB_SIZE, RESULT_SIZE,
+    WORKGROUP_SIZE,
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use solana_sdk::{
+    keccak::{hashv, Hash as KeccakHash},
+    pubkey::Pubkey,
+};
+
+const MINE_WGSL: &str = include_str!("../src/shaders/mine.wgsl");
+const HASH_WGSL: &str = include_str!("../examples/hash.wgsl");
+
+/// Port of `src/shaders/mine.wgsl`, kept in the same order and with the same names. The `jobs`
+/// binding is passed in as a slice. WGSL `u32` arithmetic wraps and shifts only use the low 5
+/// bits of the shift amount, hence `wrapping_*`.
+#[allow(clippy::needless_range_loop)]
+mod kernel {
+    pub struct Job {
+        pub prefix: [u32; 16],
+        pub nonce: [u32; 2],
+        pub difficulty: [u32; 8],
+    }
+
+    #[derive(Clone, Copy, Debug, Default)]
+    pub struct JobResult {
+        pub found: u32,
+        pub nonce: [u32; 2],
+    }
+
+    const PREFIX_U32_SIZE: usize = 16;
+    const QUEUE_SIZE: usize = 34;
+
+    /// One invocation of `main`.
+    pub fn main(jobs: &[Job], results: &mut [JobResult], global_id: [u32; 3]) {
+        let job = global_id[1] as usize;
+        if job >= jobs.len() {
+            return;
+        }
+        if results[job].found >= 1 {
+            return;
+        }
+
+        let mut queue = [0u32; 34];
+
+        let mut nonce = [0u32; 2];
+        nonce[0] = jobs[job].nonce[0].wrapping_add(global_id[0]);
+        nonce[1] = jobs[job].nonce[1];
+        if nonce[0] < global_id[0] {
+            nonce[1] = nonce[1].wrapping_add(1);
+        }
+
+        absorb_prefix(jobs, &mut queue, job);
+
+        let is_found = hash_with_nonce(jobs, &mut queue, nonce, job, &mut [0; 50]);
+        if is_found {
+            // atomicMax
+            let found = results[job].found;
+            results[job].found = found.max(1);
+            if found == 0 {
+                results[job].nonce = nonce;
+            }
+        }
+    }
+
+    pub fn absorb_prefix(jobs: &[Job], queue: &mut [u32; 34], job: usize) {
+        queue[..PREFIX_U32_SIZE].copy_from_slice(&jobs[job].prefix);
+        queue[PREFIX_U32_SIZE + 2] |= 0x01;
+        queue[QUEUE_SIZE - 1] |= 0x80000000;
+    }
+
+    /// `state` is a local in the shader, it is passed in so the digest can be checked.
+    pub fn hash_with_nonce(
+        jobs: &[Job],
+        queue: &mut [u32; 34],
+        nonce: [u32; 2],
+        job: usize,
+        state: &mut [u32; 50],
+    ) -> bool {
+        queue[PREFIX_U32_SIZE] = nonce[0];
+        queue[PREFIX_U32_SIZE + 1] = nonce[1];
+
+        state[..QUEUE_SIZE].copy_from_slice(&queue[..QUEUE_SIZE]);
+
+        permutation(state);
+
+        for i in 0..8 {
+            let lhs_u32 = state[i];
+            let rhs_u32 = jobs[job].difficulty[i];
+            for j in (0..32).step_by(8) {
+                let lhs_byte = lhs_u32 >> j & 0xff;
+                let rhs_byte = rhs_u32 >> j & 0xff;
+                if lhs_byte < rhs_byte {
+                    return true;
+                }
+                if lhs_byte > rhs_byte {
+                    return false;
+                }
+            }
+        }
+
+        true
+    }
+
+    pub fn permutation(state: &mut [u32; 50]) {
+        for r in 0..24 {
+            let mut c = [0u32; 10];
+            let mut w: [u32; 2];
+            // Theta
+            for i in 0..10 {
+                c[i] = state[i] ^ state[i + 10] ^ state[i + 20] ^ state[i + 30] ^ state[i + 40];
+            }
+            for x in 0..5 {
+                w = [c[((x + 1) % 5) * 2], c[((x + 1) % 5) * 2 + 1]];
+                let t = w;
+                w = [t[0] << 1 | t[1] >> 31, t[1] << 1 | t[0] >> 31];
+                let d = [c[(x + 4) % 5 * 2] ^ w[0], c[(x + 4) % 5 * 2 + 1] ^ w[1]];
+                for y in (0..25).step_by(5) {
+                    state[(x + y) * 2] ^= d[0];
+                    state[(x + y) * 2 + 1] ^= d[1];
+                }
+            }
+            // Rho and pi
+            w = [state[2], state[3]];
+            for i in 0..24u32 {
+                let p = P[i as usize] as usize;
+                let c = [state[p * 2], state[p * 2 + 1]];
+                let r = (i + 1) * (i + 2) / 2 % 64;
+                let t = w;
+                if r < 32 {
+                    w[0] = t[0].wrapping_shl(r) | t[1].wrapping_shr(32u32.wrapping_sub(r));
+                    w[1] = t[1].wrapping_shl(r) | t[0].wrapping_shr(32u32.wrapping_sub(r));
+                } else {
+                    w[0] = t[1].wrapping_shl(r) | t[0].wrapping_shr(32u32.wrapping_sub(r));
+                    w[1] = t[0].wrapping_shl(r) | t[1].wrapping_shr(32u32.wrapping_sub(r));
+                }
+                state[p * 2] = w[0];
+                state[p * 2 + 1] = w[1];
+                w = c;
+            }
+            // Chi
+            for y in (0..25).step_by(5) {
+                for x in 0..5 {
+                    c[x * 2] = state[(x + y) * 2];
+                    c[x * 2 + 1] = state[(x + y) * 2 + 1];
+                }
+                for x in 0..5 {
+                    let xy = (x + y) * 2;
+                    let x1 = (x + 1) % 5 * 2;
+                    let x2 = (x + 2) % 5 * 2;
+                    state[xy] ^= !c[x1] & c[x2];
+                    state[xy + 1] ^= !c[x1 + 1] & c[x2 + 1];
+                }
+            }
+            // Iota
+            state[0] ^= RC[r * 2];
+            state[1] ^= RC[r * 2 + 1];
+        }
+    }
+
+    pub const P: [u32; 24] = [
+        10, 7, 11, 17, 18, 3, 5, 16, 8, 21, 24, 4, 15, 23, 19, 13, 12, 2, 20, 14, 22, 9, 6, 1,
+    ];
+    #[rustfmt::skip]
+    pub const RC: [u32; 48] = [
+        0x00000001, 0x00000000,
+        0x00008082, 0x00000000,
+        0x0000808a, 0x80000000,
+        0x80008000, 0x80000000,
+        0x0000808b, 0x00000000,
+        0x80000001, 0x00000000,
+        0x80008081, 0x80000000,
+        0x00008009, 0x80000000,
+        0x0000008a, 0x00000000,
+        0x00000088, 0x00000000,
+        0x80008009, 0x00000000,
+        0x8000000a, 0x00000000,
+        0x8000808b, 0x00000000,
+        0x0000008b, 0x80000000,
+        0x00008089, 0x80000000,
+        0x00008003, 0x80000000,
+        0x00008002, 0x80000000,
+        0x00000080, 0x80000000,
+        0x0000800a, 0x00000000,
+        0x8000000a, 0x80000000,
+        0x80008081, 0x80000000,
+        0x00008080, 0x80000000,
+        0x80000001, 0x00000000,
+        0x80008008, 0x80000000,
+    ];
+}
+
+fn validate(name: &str, source: &str) -> Module {
+    let module = naga::front::wgsl::parse_str(source)
+        .unwrap_or_else(|err| panic!("{}", err.emit_to_string_with_path(source, name)));
+    Validator::new(ValidationFlags::all(), Capabilities::all())
+        .validate(&module)
+        .unwrap_or_else(|err| panic!("{}: {:?}", name, err));
+    module
+}
+
+/// Reads the `u32` values of a global array initialised with a constant constructor.
+fn global_u32s(module: &Module, name: &str) -> Vec<u32> {
+    let (_, global) = module
+        .global_variables
+        .iter()
+        .find(|(_, global)| global.name.as_deref() == Some(name))
+        .unwrap_or_else(|| panic!("no global {}", name));
+    let Expression::Compose { components, .. } = &module.const_expressions[global.init.unwrap()]
+    else {
+        panic!("{} is not initialised with a constructor", name);
+    };
+    components
+        .iter()
+        .map(|component| match module.const_expressions[*component] {
+            Expression::Literal(Literal::U32(value)) => value,
+            ref expression => panic!("{} holds {:?}", name, expression),
+        })
+        .collect()
+}
+
+fn to_words<const N: usize>(bytes: &[u8]) -> [u32; N] {
+    let mut words = [0u32; N];
+    for (word, chunk) in words.iter_mut().zip(bytes.chunks_exact(4)) {
+        *word = u32::from_le_bytes(chunk.try_into().unwrap());
+    }
+    words
+}
+
+fn to_bytes(words: &[u32]) -> Vec<u8> {
+    words.iter().flat_map(|word| word.to_le_bytes()).collect()
+}
+
+/// Reads a `pack_jobs` buffer the way the shader does.
+fn unpack(data: &[u8]) -> Vec<kernel::Job> {
+    data.chunks_exact(JOB_SIZE)
+        .map(|job| kernel::Job {
+            prefix: to_words(&job[0..64]),
+            nonce: to_words(&job[64..72]),
+            difficulty: to_words(&job[72..104]),
+        })
+        .collect()
+}
+
+/// Writes the port's results the way the shader lays them out for `decode_results`.
+fn encode(results: &[kernel::JobResult]) -> Vec<u8> {
+    let mut data = vec![0u8; results.len() * RESULT_SIZE];
+    for (result, out) in results.iter().zip(data.chunks_exact_mut(RESULT_SIZE)) {
+        out[0..4].copy_from_slice(&result.found.to_le_bytes());
+        out[8..16].copy_from_slice(&to_bytes(&result.nonce));
+    }
+    data
+}
+
+fn job(prefix: &[u8; 64], nonce: u64, difficulty: [u8; 32]) -> GpuJob {
+    GpuJob {
+        challenge: KeccakHash::new(&prefix[..32]),
+        pubkey: Pubkey::try_from(&prefix[32..]).unwrap(),
+        difficulty: KeccakHash::new_from_array(difficulty),
+        nonce,
+    }
+}
+
+/// Runs one invocation's hash through the port and returns the digest bytes.
+fn kernel_hash(job: &GpuJob, nonce: u64) -> Vec<u8> {
+    let jobs = unpack(&pack_jobs(std::slice::from_ref(job)));
+    let mut queue = [0u32; 34];
+    let mut state = [0u32; 50];
+    kernel::absorb_prefix(&jobs, &mut queue, 0);
+    kernel::hash_with_nonce(
+        &jobs,
+        &mut queue,
+        to_words(&nonce.to_le_bytes()),
+        0,
+        &mut state,
+    );
+    to_bytes(&state[..8])
+}
+
+/// Runs invocations `0..invocations` of every job like a dispatch and decodes the results.
+fn dispatch(jobs: &[GpuJob], invocations: u32) -> Vec<Option<u64>> {
+    let kernel_jobs = unpack(&pack_jobs(jobs));
+    let mut results = vec![kernel::JobResult::default(); jobs.len()];
+    for y in 0..jobs.len() as u32 {
+        for x in 0..invocations {
+            kernel::main(&kernel_jobs, &mut results, [x, y, 0]);
+        }
+    }
+    decode_results(&encode(&results), jobs.len())
+}
+
+fn expected_hash(prefix: &[u8; 64], nonce: u64) -> Vec<u8> {
+    hashv(&[&prefix[..32], &prefix[32..], &nonce.to_le_bytes()])
+        .to_bytes()
+        .to_vec()
+}
+
+fn random_prefix(rng: &mut StdRng) -> [u8; 64] {
+    let mut prefix = [0u8; 64];
+    rng.fill(&mut prefix[..]);
+    prefix
+}
+
+/// Size and member offsets of a struct declared in the shader.
+fn struct_layout(module: &Module, name: &str) -> (u32, Vec<u32>) {
+    module
+        .types
+        .iter()
+        .find_map(|(_, ty)| match &ty.inner {
+            TypeInner::Struct { members, span } if ty.name.as_deref() == Some(name) => {
+                Some((*span, members.iter().map(|member| member.offset).collect()))
+            }
+            _ => None,
+        })
+        .unwrap_or_else(|| panic!("no struct {}", name))
+}
+
+/// Stride of the runtime-sized array bound as `name`.
+fn binding_stride(module: &Module, name: &str) -> u32 {
+    let (_, global) = module
+        .global_variables
+        .iter()
+        .find(|(_, global)| global.name.as_deref() == Some(name))
+        .unwrap_or_else(|| panic!("no global {}", name));
+    match module.types[global.ty].inner {
+        TypeInner::Array { stride, .. } => stride,
+        ref inner => panic!("{} is a {:?}", name, inner),
+    }
+}
+
+#[test]
+fn shaders_validate() {
+    validate("src/shaders/mine.wgsl", MINE_WGSL);
+    validate("examples/hash.wgsl", HASH_WGSL);
+}
+
+#[test]
+fn port_matches_shader_constants() {
+    let module = validate("src/shaders/mine.wgsl", MINE_WGSL);
+    assert_eq!(global_u32s(&module, "P"), kernel::P);
+    assert_eq!(global_u32s(&module, "RC"), kernel::RC);
+
+    let entry_point = module
+        .entry_points
+        .iter()
+        .find(|entry_point| entry_point.name == "main")
+        .unwrap();
+    assert_eq!(entry_point.workgroup_size, [WORKGROUP_SIZE, 1, 1]);
+}
+
+#[test]
+fn batch_layout_matches_shader() {
+    let module = validate("src/shaders/mine.wgsl", MINE_WGSL);
+    assert_eq!(
+        struct_layout(&module, "Job"),
+        (JOB_SIZE as u32, vec![0, 64, 72])
+    );
+    assert_eq!(
+        struct_layout(&module, "JobResult"),
+        (RESULT_SIZE as u32, vec![0, 8])
+    );
+    assert_eq!(binding_stride(&module, "jobs"), JOB_SIZE as u32);
+    assert_eq!(binding_stride(&module, "results"), RESULT_SIZE as u32);
+}
+
+#[test]
+fn hash_matches_hashv() {
+    let mut rng = StdRng::seed_from_u64(0x6f7265);
+    for _ in 0..2000 {
+        let prefix = random_prefix(&mut rng);
+        let nonce = rng.gen::<u64>();
+        let job = job(&prefix, nonce, [0xff; 32]);
+        assert_eq!(
+            kernel_hash(&job, nonce),
+            expected_hash(&prefix, nonce),
+            "nonce {}",
+            nonce
+        );
+        assert_eq!(
+            job.hash(nonce).to_bytes().to_vec(),
+            expected_hash(&prefix, nonce)
+        );
+    }
+}
+
+#[test]
+fn invocations_match_hashv() {
+    let mut rng = StdRng::seed_from_u64(0x6e6f6e6365);
+    for _ in 0..50 {
+        let jobs = (0..8)
+            .map(|_| job(&random_prefix(&mut rng), rng.gen(), [0xff; 32]))
+            .collect::<Vec<_>>();
+        let kernel_jobs = unpack(&pack_jobs(&jobs));
+        for (y, job) in jobs.iter().enumerate() {
+            let x = rng.gen_range(0..nonces_per_job(jobs.len()) as u32);
+            let mut results = vec![kernel::JobResult::default(); jobs.len()];
+            kernel::main(&kernel_jobs, &mut results, [x, y as u32, 0]);
+
+            let nonce = job.nonce.wrapping_add(x as u64);
+            let decoded = decode_results(&encode(&results), jobs.len());
+            assert_eq!(decoded[y], Some(nonce));
+            assert!(decoded
+                .iter()
+                .enumerate()
+                .all(|(i, result)| i == y || result.is_none()));
+            assert_eq!(kernel_hash(job, nonce), job.hash(nonce).to_bytes().to_vec());
+        }
+    }
+}
+
+#[test]
+fn nonce_carry_matches_hashv() {
+    let mut rng = StdRng::seed_from_u64(0x6361727279);
+    let prefix = random_prefix(&mut rng);
+    let nonces = [
+        0,
+        u32::MAX as u64 - 1,
+        u32::MAX as u64,
+        u32::MAX as u64 + 1,
+        u32::MAX as u64 + 2,
+        (7 << 32) | u32::MAX as u64,
+        8 << 32,
+        u64::MAX - 1,
+        u64::MAX,
+    ];
+    for nonce in nonces {
+        let job = job(&prefix, nonce, [0xff; 32]);
+        assert_eq!(
+            kernel_hash(&job, nonce),
+            expected_hash(&prefix, nonce),
+            "nonce {}",
+            nonce
+        );
+    }
+
+    // Invocations whose offset overflows `nonce[0]` carry into `nonce[1]`, so a job may start
+    // anywhere and still search consecutive nonces.
+    for nonce1 in [0, 1, rng.gen(), u32::MAX - 1, u32::MAX] {
+        for before in [0, 1, 5, rng.gen_range(0..WORKGROUP_SIZE)] {
+            let base = (u64::from(nonce1) << 32) | u64::from(u32::MAX - before);
+            let jobs = [job(&prefix, base, [0xff; 32])];
+            let kernel_jobs = unpack(&pack_jobs(&jobs));
+            for x in [before, before + 1, before + 2, before + WORKGROUP_SIZE] {
+                let mut results = [kernel::JobResult::default()];
+                kernel::main(&kernel_jobs, &mut results, [x, 0, 0]);
+
+                let nonce = base.wrapping_add(x as u64);
+                assert_eq!(results[0].nonce, to_words::<2>(&nonce.to_le_bytes()));
+                assert_eq!(kernel_hash(&jobs[0], nonce), expected_hash(&prefix, nonce));
+            }
+        }
+    }
+}
+
+#[test]
+fn difficulty_check() {
+    let mut rng = StdRng::seed_from_u64(0x64696666);
+    let prefix = random_prefix(&mut rng);
+
+    let mut leading_zero = [0xff; 32];
+    leading_zero[0] = 0;
+    let mut leading_nibble = [0xff; 32];
+    leading_nibble[0] = 0x0f;
+
+    let mut found = 0;
+    for nonce in 0..8192u64 {
+        let hash = expected_hash(&prefix, nonce);
+        let mut close = [0u8; 32];
+        close.copy_from_slice(&hash);
+        close[31] = close[31].wrapping_add(rng.gen_range(0..3)).wrapping_sub(1);
+
+        for difficulty in [[0xff; 32], leading_zero, leading_nibble, rng.gen(), close] {
+            let jobs = [job(&prefix, nonce, difficulty)];
+            let is_found = dispatch(&jobs, 1)[0].is_some();
+            assert_eq!(
+                is_found,
+                hash.as_slice() <= difficulty.as_slice(),
+                "nonce {}",
+                nonce
+            );
+        }
+        found += (hash[0] == 0) as usize;
+    }
+    assert!(found > 0);
+}
+
+#[test]
+fn batch_results_decode() {
+    let mut rng = StdRng::seed_from_u64(0x6261746368);
+    let mut leading_zero = [0xff; 32];
+    leading_zero[0] = 0;
+    let mut leading_nibble = [0xff; 32];
+    leading_nibble[0] = 0x0f;
+
+    let difficulties = [
+        [0xff; 32],
+        leading_zero,
+        [0; 32],
+        leading_nibble,
+        leading_zero,
+    ];
+    let mut jobs = difficulties
+        .iter()
+        .map(|difficulty| job(&random_prefix(&mut rng), rng.gen(), *difficulty))
+        .collect::<Vec<_>>();
+    let starts = jobs.iter().map(|job| job.nonce).collect::<Vec<_>>();
+
+    let invocations = 4096;
+    let results = dispatch(&jobs, invocations);
+    for (job, result) in jobs.iter().zip(&results) {
+        // Invocations run in order here, so the first satisfying nonce wins.
+        let first = (0..invocations as u64)
+            .map(|x| job.nonce.wrapping_add(x))
+            .find(|nonce| job.hash(*nonce) <= job.difficulty);
+        assert_eq!(*result, first);
+    }
+    assert_eq!(results[0], Some(starts[0]));
+    assert_eq!(results[2], None);
+
+    advance_jobs(&mut jobs, &results);
+    for ((job, start), result) in jobs.iter().zip(starts).zip(&results) {
+        match result {
+            Some(_) => assert_eq!(job.nonce, start),
+            None => assert_eq!(
+                job.nonce,
+                start.wrapping_add(nonces_per_job(difficulties.len()))
+            ),
+        }
+    }
+
+    // Buffers are reused for smaller batches, trailing results are ignored.
+    let mut data = encode(&[kernel::JobResult::default(); 8]);
+    data[3 * RESULT_SIZE] = 1;
+    assert_eq!(decode_results(&data, 2), vec![None, None]);
+    assert_eq!(pack_jobs(&jobs).len(), jobs.len() * JOB_SIZE);
+}
+
+#[test]
+fn batches_share_a_dispatch() {
+    for jobs in [1, 2, 3, 8, 50, 1000] {
+        let nonces = nonces_per_job(jobs);
+        assert!(nonces >= WORKGROUP_SIZE as u64);
+        assert!(nonces * jobs as u64 <= 2 * nonces_per_job(1));
+    }
+}